@@ -0,0 +1,211 @@
+#ifndef WFDB_RUST_H
+#define WFDB_RUST_H
+
+/* Generated from src/capi.rs by cbindgen; do not edit. */
+
+#include <stdint.h>
+
+/*
+ `stat` of an annotator opened for reading.
+ */
+#define WFDB_READ 0
+
+typedef double WFDB_Gain;
+
+typedef int WFDB_Sample;
+
+typedef unsigned int WFDB_Group;
+
+typedef int64_t WFDB_Time;
+
+/*
+ Description of a signal, filled in by [`isigopen`].
+ */
+typedef struct WFDB_Siginfo {
+  /*
+   Name of the signal file.
+   */
+  char *fname;
+  /*
+   Signal description.
+   */
+  char *desc;
+  /*
+   Physical units.
+   */
+  char *units;
+  /*
+   ADC units per physical unit (0: uncalibrated).
+   */
+  WFDB_Gain gain;
+  /*
+   Value of sample number 0.
+   */
+  WFDB_Sample initval;
+  /*
+   Signal group number: signals in the same file share a group.
+   */
+  WFDB_Group group;
+  /*
+   Storage format (8, 16, 212, ...).
+   */
+  int fmt;
+  /*
+   Samples per frame.
+   */
+  int spf;
+  /*
+   Block size (for character special files only).
+   */
+  int bsize;
+  /*
+   ADC resolution in bits.
+   */
+  int adcres;
+  /*
+   ADC output given 0 VDC input.
+   */
+  int adczero;
+  /*
+   ADC output given 0 physical units input.
+   */
+  int baseline;
+  /*
+   Number of samples (0: unspecified).
+   */
+  WFDB_Time nsamp;
+  /*
+   16-bit checksum of all samples.
+   */
+  int cksum;
+} WFDB_Siginfo;
+
+/*
+ An annotator to open with [`annopen`].
+ */
+typedef struct WFDB_Anninfo {
+  /*
+   Annotator name, such as `atr`.
+   */
+  char *name;
+  /*
+   How to open the annotator; only [`WFDB_READ`] is supported.
+   */
+  int stat;
+} WFDB_Anninfo;
+
+typedef unsigned int WFDB_Annotator;
+
+/*
+ An annotation, filled in by [`getann`].
+ */
+typedef struct WFDB_Annotation {
+  /*
+   Time of the annotation, in samples from the beginning of the record.
+   */
+  WFDB_Time time;
+  /*
+   Annotation code.
+   */
+  char anntyp;
+  int8_t subtyp;
+  uint8_t chan;
+  int8_t num;
+  /*
+   Auxiliary information: a length byte followed by that many bytes and a null byte, or null
+   when the annotation has none.
+   */
+  uint8_t *aux;
+} WFDB_Annotation;
+
+typedef double WFDB_Frequency;
+
+/*
+ Value of invalid samples returned by [`getvec`], as in the WFDB library.
+ */
+#define WFDB_INVALID_SAMPLE -32768
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/*
+ Open the signals of `record`, filling in `siarray` with the description of each signal.
+
+ Up to `nsig` signals are opened for reading with [`getvec`]. When `nsig` is negative, up to
+ `-nsig` signals are described but none are opened. Returns the number of signals opened (or
+ the number of signals in the record, for a negative `nsig`), -1 if the record cannot be read
+ or -2 if its header is invalid.
+ */
+int isigopen(const char *record, struct WFDB_Siginfo *siarray, int nsig);
+
+/*
+ Read the next sample of each open signal into `vector`, averaging the samples in the frame of
+ signals with several samples per frame. Returns the number of samples read, or -1 at the end of
+ the record (or when no record is open).
+ */
+int getvec(WFDB_Sample *vector);
+
+/*
+ Read the next frame of the open signals into `vector`: every sample of each signal in the
+ frame, so that a signal with `spf` samples per frame fills `spf` entries. Returns the number of
+ signals read, or -1 at the end of the record (or when no record is open).
+ */
+int getframe(WFDB_Sample *vector);
+
+/*
+ Move the input position to sample number `t` (or `-t`, for a negative time as returned by
+ [`strtim`]). Returns 0, or -1 if no record is open or `t` is past its end.
+ */
+int isigsettime(WFDB_Time t);
+
+/*
+ Open the annotators listed in `aiarray` for `record`, closing any open annotators. Each
+ annotator is then read with [`getann`], by its index in `aiarray`. Returns 0, -3 if an
+ annotation file cannot be read or -4 for annotators not opened with [`WFDB_READ`].
+ */
+int annopen(const char *record, const struct WFDB_Anninfo *aiarray, unsigned int nann);
+
+/*
+ Read the next annotation from annotator `an` into `annot`. Returns 0, -1 at the end of the
+ annotations or -2 if the annotator is not open. The `aux` string stays valid until the next
+ call for the same annotator.
+ */
+int getann(WFDB_Annotator an, struct WFDB_Annotation *annot);
+
+/*
+ Convert a time string into a sample number, using the sampling frequency of the open record.
+
+ Elapsed times (`HH:MM:SS`, `MM:SS` or `SS`, with optional fractional seconds) are converted
+ into a number of samples, and `sNNN` is sample number NNN. `e` is the end of the record and
+ `i` the current input position. An absolute time in brackets (`[HH:MM:SS DD/MM/YYYY]`) gives
+ the negated sample number at that time. Returns 0 for strings that cannot be parsed.
+ */
+WFDB_Time strtim(const char *string);
+
+/*
+ Format a sample number as a time, using the sampling frequency of the open record.
+
+ Positive times are formatted as elapsed times (`H:MM:SS`, or `MM:SS` under an hour), and
+ negative times (and 0, for records with a base time) as absolute times in brackets, as
+ `[HH:MM:SS DD/MM/YYYY]`. The string stays valid until the next call.
+ */
+const char *timstr(WFDB_Time t);
+
+/*
+ Sampling frequency of the open record (or of `record`, if not null), in frames per second: the
+ sampling frequency of signals with one sample per frame. Returns -1 if the record cannot be
+ read.
+ */
+WFDB_Frequency sampfreq(const char *record);
+
+/*
+ Close the open record and annotators.
+ */
+void wfdbquit(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* WFDB_RUST_H */
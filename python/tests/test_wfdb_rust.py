@@ -0,0 +1,93 @@
+import numpy as np
+import pytest
+
+import wfdb_rust
+
+HEADER = """100 2 360 650000 0:0:0 0/0/0
+100.dat 212 200 11 1024 995 -22131 0 MLII
+100.dat 212 200 11 1024 1011 20052 0 V5
+"""
+
+
+def test_parse_header():
+    header = wfdb_rust.parse_header(HEADER)
+    assert header["record_name"] == "100"
+    assert header["n_sig"] == 2
+    assert header["fs"] == 360.0
+    assert header["sig_len"] == 650000
+    assert header["base_time"] == "0:0:0"
+    assert header["fmt"] == ["212", "212"]
+    assert header["adc_gain"] == [200.0, 200.0]
+    assert header["units"] == ["mV", "mV"]
+    assert header["init_value"] == [995, 1011]
+    assert header["sig_name"] == ["MLII", "V5"]
+
+
+def write(tmp_path, d_signal, **kwargs):
+    wfdb_rust.write_record(
+        "rec",
+        d_signal,
+        360,
+        sig_name=["MLII", "BP"],
+        units=["mV", "mmHg"],
+        adc_gain=[200, 50],
+        baseline=[0, 10],
+        write_dir=str(tmp_path),
+        **kwargs,
+    )
+    return str(tmp_path / "rec")
+
+
+def test_write_and_read_record(tmp_path):
+    d_signal = np.array([[100, 60], [200, -32768], [-100, 35]], dtype=np.int32)
+    record_name = write(tmp_path, d_signal)
+    assert (tmp_path / "rec.hea").exists() and (tmp_path / "rec.dat").exists()
+
+    header, adc = wfdb_rust.read_record(record_name, physical=False)
+    assert header["sig_name"] == ["MLII", "BP"]
+    assert header["fmt"] == ["16", "16"]
+    assert adc.dtype == np.int32
+    np.testing.assert_array_equal(adc, d_signal)
+
+    header, signals = wfdb_rust.read_record(record_name)
+    assert signals.dtype == np.float64
+    assert signals.shape == (3, 2)
+    np.testing.assert_allclose(signals[:, 0], [0.5, 1.0, -0.5])
+    assert np.isnan(signals[1, 1])
+    assert signals[2, 1] == 0.5
+
+
+def test_read_sample_window(tmp_path):
+    d_signal = np.arange(20, dtype=np.int32).reshape(10, 2)
+    record_name = write(tmp_path, d_signal)
+    _, adc = wfdb_rust.read_record(record_name, physical=False, sampfrom=2, sampto=5)
+    np.testing.assert_array_equal(adc, d_signal[2:5])
+
+
+def test_write_format_32(tmp_path):
+    d_signal = np.array([[100_000, 1], [-100_000, 2]], dtype=np.int32)
+    record_name = write(tmp_path, d_signal, fmt=32)
+    header, adc = wfdb_rust.read_record(record_name, physical=False)
+    assert header["fmt"] == ["32", "32"]
+    np.testing.assert_array_equal(adc, d_signal)
+
+
+def test_unsupported_format(tmp_path):
+    with pytest.raises(ValueError):
+        write(tmp_path, np.zeros((2, 2), dtype=np.int32), fmt=212)
+
+
+def test_read_annotations(tmp_path):
+    # MIT format: 6-bit annotation code and 10-bit sample increment per word, then an end marker
+    words = [(1 << 10) | 18, (5 << 10) | 59, 0]
+    (tmp_path / "rec.atr").write_bytes(np.array(words, dtype="<u2").tobytes())
+    annotations = wfdb_rust.read_annotations(str(tmp_path / "rec"), "atr")
+    np.testing.assert_array_equal(annotations["sample"], [18, 77])
+    np.testing.assert_array_equal(annotations["code"], [1, 5])
+    assert annotations["symbol"] == ["N", "V"]
+    assert annotations["aux_note"] == ["", ""]
+
+
+def test_missing_record(tmp_path):
+    with pytest.raises(FileNotFoundError):
+        wfdb_rust.read_record(str(tmp_path / "missing"))
@@ -0,0 +1,485 @@
+//! Parsing logic for WFDB annotation files in MIT format.
+//!
+//! An annotation file is a sequence of 16-bit little-endian words. The upper 6 bits of each word
+//! hold an annotation code and the lower 10 bits hold either the number of samples since the
+//! previous annotation or, for the pseudo-annotation codes below, data modifying the annotation
+//! that precedes it.
+use std::convert::TryFrom;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::storage::{self, FileStorage, Storage};
+
+/// Pseudo-annotation: the next 4 bytes hold a 32-bit interval to add to the annotation time.
+const SKIP: u16 = 59;
+/// Pseudo-annotation: set `num` for the preceding annotation and those that follow.
+const NUM: u16 = 60;
+/// Pseudo-annotation: set `subtype` for the preceding annotation.
+const SUB: u16 = 61;
+/// Pseudo-annotation: set `chan` for the preceding annotation and those that follow.
+const CHN: u16 = 62;
+/// Pseudo-annotation: the next `I` bytes (padded to an even length) hold auxiliary information.
+const AUX: u16 = 63;
+/// Highest annotation code (`ACMAX` in the WFDB library).
+const ACMAX: u8 = 49;
+/// Longest auxiliary information written, in bytes, as in the WFDB library.
+const MAX_AUX_LEN: usize = 255;
+
+/// A single annotation, with the same fields as the WFDB library's `WFDB_Annotation`.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Annotation {
+    /// Time of the annotation, in samples from the beginning of the record.
+    pub sample: u64,
+    /// Annotation code, e.g. 1 for a normal beat (see [`code_mnemonic`]).
+    pub code: u8,
+    pub subtype: i8,
+    pub chan: u8,
+    pub num: i8,
+    pub aux: Option<String>,
+}
+
+impl Annotation {
+    /// The mnemonic used by the WFDB tools for this annotation's code (`N`, `V`, `+`, ...).
+    pub fn mnemonic(&self) -> Option<&'static str> {
+        code_mnemonic(self.code)
+    }
+}
+
+/// Mnemonics for the standard annotation codes, indexed by code.
+const MNEMONICS: [Option<&str>; 42] = [
+    None,
+    Some("N"),
+    Some("L"),
+    Some("R"),
+    Some("a"),
+    Some("V"),
+    Some("F"),
+    Some("J"),
+    Some("A"),
+    Some("S"),
+    Some("E"),
+    Some("j"),
+    Some("/"),
+    Some("Q"),
+    Some("~"),
+    None,
+    Some("|"),
+    None,
+    Some("s"),
+    Some("T"),
+    Some("*"),
+    Some("D"),
+    Some("\""),
+    Some("="),
+    Some("p"),
+    Some("B"),
+    Some("^"),
+    Some("t"),
+    Some("+"),
+    Some("u"),
+    Some("?"),
+    Some("!"),
+    Some("["),
+    Some("]"),
+    Some("e"),
+    Some("n"),
+    Some("@"),
+    Some("x"),
+    Some("f"),
+    Some("("),
+    Some(")"),
+    Some("r"),
+];
+
+/// The mnemonic used by the WFDB tools for an annotation code, if the code is a standard one.
+pub fn code_mnemonic(code: u8) -> Option<&'static str> {
+    MNEMONICS.get(code as usize).copied().flatten()
+}
+
+/// The annotation code for a mnemonic used by the WFDB tools, such as 5 for `V`.
+pub fn mnemonic_code(mnemonic: &str) -> Option<u8> {
+    MNEMONICS
+        .iter()
+        .position(|&m| m == Some(mnemonic))
+        .map(|code| code as u8)
+}
+
+/// Sign-extend the 10-bit data field of an annotation word.
+fn sign_extend_10bit(data: u16) -> i8 {
+    (((data << 6) as i16) >> 6) as i8
+}
+
+/// Parse a byte buffer of annotations in MIT format.
+///
+/// Parsing stops at the end of the annotations, at a truncated annotation, or at an annotation
+/// whose time a negative `SKIP` has moved before the start of the record.
+pub fn parse_mit_annotations(buf: &[u8]) -> Vec<Annotation> {
+    let mut annotations: Vec<Annotation> = vec![];
+    // Signed, as a negative SKIP may move the time back before the next annotation word
+    let mut time = 0_i64;
+    let mut num = 0_i8;
+    let mut chan = 0_u8;
+    let mut idx = 0;
+    while idx + 1 < buf.len() {
+        let word = u16::from_le_bytes([buf[idx], buf[idx + 1]]);
+        idx += 2;
+        let code = word >> 10;
+        let data = word & 0x03FF;
+        match code {
+            0 if data == 0 => break,
+            SKIP => {
+                if idx + 3 >= buf.len() {
+                    break;
+                }
+                // The interval is stored as two 16-bit words, most significant word first
+                let high = u16::from_le_bytes([buf[idx], buf[idx + 1]]) as u32;
+                let low = u16::from_le_bytes([buf[idx + 2], buf[idx + 3]]) as u32;
+                time = match time.checked_add(((high << 16) | low) as i32 as i64) {
+                    Some(time) => time,
+                    None => break,
+                };
+                idx += 4;
+            }
+            NUM => {
+                num = sign_extend_10bit(data);
+                if let Some(last) = annotations.last_mut() {
+                    last.num = num;
+                }
+            }
+            SUB => {
+                if let Some(last) = annotations.last_mut() {
+                    last.subtype = sign_extend_10bit(data);
+                }
+            }
+            CHN => {
+                chan = data as u8;
+                if let Some(last) = annotations.last_mut() {
+                    last.chan = chan;
+                }
+            }
+            AUX => {
+                let len = data as usize;
+                let end = (idx + len).min(buf.len());
+                if let Some(last) = annotations.last_mut() {
+                    let aux = &buf[idx..end];
+                    let aux = aux.split(|&b| b == 0).next().unwrap_or(aux);
+                    last.aux = Some(String::from_utf8_lossy(aux).into_owned());
+                }
+                idx += len + (len & 1);
+            }
+            _ => {
+                time += data as i64;
+                let sample = match u64::try_from(time) {
+                    Ok(sample) => sample,
+                    Err(_) => break,
+                };
+                annotations.push(Annotation {
+                    sample,
+                    code: code as u8,
+                    subtype: 0,
+                    chan,
+                    num,
+                    aux: None,
+                });
+            }
+        }
+    }
+    annotations
+}
+
+/// Encode annotations in MIT format, as read by [`parse_mit_annotations`].
+///
+/// Intervals that do not fit in an annotation word are written with a `SKIP` pseudo-annotation,
+/// and `subtype`, `chan`, `num` and `aux` with the pseudo-annotations that set them. Auxiliary
+/// information is cut to 255 bytes, as in the WFDB library. Codes must be between 1 and 49.
+pub fn encode_mit_annotations(annotations: &[Annotation]) -> io::Result<Vec<u8>> {
+    fn push_word(buf: &mut Vec<u8>, code: u16, data: u16) {
+        buf.extend_from_slice(&((code << 10) | (data & 0x03FF)).to_le_bytes());
+    }
+
+    let mut buf = vec![];
+    let mut time = 0_u64;
+    let mut chan = 0_u8;
+    let mut num = 0_i8;
+    for annotation in annotations {
+        if annotation.code == 0 || annotation.code > ACMAX {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Annotation code {} at sample {} is not between 1 and {}",
+                    annotation.code, annotation.sample, ACMAX
+                ),
+            ));
+        }
+        let code = annotation.code as u16;
+        let interval = annotation.sample.wrapping_sub(time) as i64;
+        if (0..0x0400).contains(&interval) {
+            push_word(&mut buf, code, interval as u16);
+        } else {
+            let interval = i32::try_from(interval).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "Annotation at sample {} is too far from the one before it",
+                        annotation.sample
+                    ),
+                )
+            })? as u32;
+            push_word(&mut buf, SKIP, 0);
+            buf.extend_from_slice(&((interval >> 16) as u16).to_le_bytes());
+            buf.extend_from_slice(&(interval as u16).to_le_bytes());
+            push_word(&mut buf, code, 0);
+        }
+        time = annotation.sample;
+
+        if annotation.subtype != 0 {
+            push_word(&mut buf, SUB, annotation.subtype as u16);
+        }
+        if annotation.chan != chan {
+            chan = annotation.chan;
+            push_word(&mut buf, CHN, chan as u16);
+        }
+        if annotation.num != num {
+            num = annotation.num;
+            push_word(&mut buf, NUM, num as u16);
+        }
+        if let Some(aux) = &annotation.aux {
+            let aux = &aux.as_bytes()[..aux.len().min(MAX_AUX_LEN)];
+            push_word(&mut buf, AUX, aux.len() as u16);
+            buf.extend_from_slice(aux);
+            if aux.len() % 2 == 1 {
+                buf.push(0);
+            }
+        }
+    }
+    push_word(&mut buf, 0, 0);
+    Ok(buf)
+}
+
+/// Write annotations in MIT format as the annotation file of a record made by `annotator`,
+/// relative to the current working directory: `write_annotations("out/100", "atr", ...)` writes
+/// `out/100.atr`.
+pub fn write_annotations<P: AsRef<Path>>(
+    record_name: P,
+    annotator: &str,
+    annotations: &[Annotation],
+) -> io::Result<()> {
+    let path = format!("{}.{}", record_name.as_ref().to_string_lossy(), annotator);
+    fs::write(path, encode_mit_annotations(annotations)?)
+}
+
+/// Read the annotations made by `annotator` (e.g. `atr`) for a record, relative to the current
+/// working directory. The annotation file is found by appending the annotator name to the record
+/// name, so `read_annotations("mitdb/100", "atr")` reads `mitdb/100.atr`.
+pub fn read_annotations<P: AsRef<Path>>(
+    record_name: P,
+    annotator: &str,
+) -> io::Result<Vec<Annotation>> {
+    read_annotations_from(
+        &FileStorage::new(""),
+        &record_name.as_ref().to_string_lossy(),
+        annotator,
+    )
+}
+
+/// Read the annotations made by `annotator` for a record held in `storage`.
+pub fn read_annotations_from(
+    storage: &dyn Storage,
+    record_name: &str,
+    annotator: &str,
+) -> io::Result<Vec<Annotation>> {
+    let buf = storage::read_file(storage, &format!("{}.{}", record_name, annotator))?;
+    Ok(parse_mit_annotations(&buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(code: u16, data: u16) -> [u8; 2] {
+        ((code << 10) | data).to_le_bytes()
+    }
+
+    #[test]
+    fn basic_annotations() {
+        let mut buf = vec![];
+        buf.extend_from_slice(&word(28, 18)); // (N rhythm change at sample 18
+        buf.extend_from_slice(&word(AUX, 3));
+        buf.extend_from_slice(b"(N\0\0");
+        buf.extend_from_slice(&word(1, 59)); // N beat at sample 77
+        buf.extend_from_slice(&word(5, 293)); // V beat at sample 370
+        buf.extend_from_slice(&word(SUB, 2));
+        buf.extend_from_slice(&word(0, 0));
+        assert_eq!(
+            parse_mit_annotations(&buf),
+            vec![
+                Annotation {
+                    sample: 18,
+                    code: 28,
+                    subtype: 0,
+                    chan: 0,
+                    num: 0,
+                    aux: Some(String::from("(N"))
+                },
+                Annotation {
+                    sample: 77,
+                    code: 1,
+                    subtype: 0,
+                    chan: 0,
+                    num: 0,
+                    aux: None
+                },
+                Annotation {
+                    sample: 370,
+                    code: 5,
+                    subtype: 2,
+                    chan: 0,
+                    num: 0,
+                    aux: None
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn skip_num_and_chan() {
+        let mut buf = vec![];
+        buf.extend_from_slice(&word(SKIP, 0));
+        buf.extend_from_slice(&1_u16.to_le_bytes());
+        buf.extend_from_slice(&2_u16.to_le_bytes());
+        buf.extend_from_slice(&word(1, 4)); // 0x10002 + 4
+        buf.extend_from_slice(&word(NUM, 0x3FF));
+        buf.extend_from_slice(&word(CHN, 1));
+        buf.extend_from_slice(&word(1, 10)); // num and chan carry over
+        assert_eq!(
+            parse_mit_annotations(&buf),
+            vec![
+                Annotation {
+                    sample: 65542,
+                    code: 1,
+                    subtype: 0,
+                    chan: 1,
+                    num: -1,
+                    aux: None
+                },
+                Annotation {
+                    sample: 65552,
+                    code: 1,
+                    subtype: 0,
+                    chan: 1,
+                    num: -1,
+                    aux: None
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn negative_skip() {
+        let skip = |buf: &mut Vec<u8>, interval: i32| {
+            let interval = interval as u32;
+            buf.extend_from_slice(&word(SKIP, 0));
+            buf.extend_from_slice(&((interval >> 16) as u16).to_le_bytes());
+            buf.extend_from_slice(&(interval as u16).to_le_bytes());
+        };
+        let mut buf = vec![];
+        buf.extend_from_slice(&word(1, 100));
+        skip(&mut buf, -150);
+        buf.extend_from_slice(&word(5, 80)); // Back at sample 30
+        skip(&mut buf, -100);
+        buf.extend_from_slice(&word(1, 10)); // Before the start of the record
+        buf.extend_from_slice(&word(1, 1000));
+        let annotations = parse_mit_annotations(&buf);
+        assert_eq!(
+            annotations
+                .iter()
+                .map(|a| (a.sample, a.code))
+                .collect::<Vec<_>>(),
+            vec![(100, 1), (30, 5)]
+        );
+    }
+
+    #[test]
+    fn mnemonics() {
+        assert_eq!(code_mnemonic(1), Some("N"));
+        assert_eq!(code_mnemonic(28), Some("+"));
+        assert_eq!(code_mnemonic(15), None);
+        assert_eq!(code_mnemonic(49), None);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_round_trip() {
+        let annotation = Annotation {
+            sample: 18,
+            code: 1,
+            subtype: 0,
+            chan: 1,
+            num: 0,
+            aux: Some(String::from("(N")),
+        };
+        let json = serde_json::to_string(&annotation).unwrap();
+        assert_eq!(
+            json,
+            r#"{"sample":18,"code":1,"subtype":0,"chan":1,"num":0,"aux":"(N"}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<Annotation>(&json).unwrap(),
+            annotation
+        );
+    }
+
+    #[test]
+    fn encode_round_trip() {
+        let annotations = vec![
+            Annotation {
+                sample: 18,
+                code: 28,
+                subtype: 0,
+                chan: 0,
+                num: 0,
+                aux: Some(String::from("(AFIB")),
+            },
+            Annotation {
+                sample: 77,
+                code: 1,
+                subtype: -1,
+                chan: 1,
+                num: 3,
+                aux: None,
+            },
+            // Far enough from the previous annotation to need a SKIP
+            Annotation {
+                sample: 70_000,
+                code: 5,
+                subtype: 2,
+                chan: 1,
+                num: 3,
+                aux: Some(String::from("ok")),
+            },
+            // Before the previous annotation, with a negative SKIP
+            Annotation {
+                sample: 69_000,
+                code: 14,
+                subtype: 0,
+                chan: 0,
+                num: 0,
+                aux: None,
+            },
+        ];
+        let buf = encode_mit_annotations(&annotations).unwrap();
+        assert_eq!(&buf[..2], &word(28, 18));
+        assert_eq!(&buf[buf.len() - 2..], &[0, 0]);
+        assert_eq!(parse_mit_annotations(&buf), annotations);
+
+        let invalid = Annotation {
+            code: 60,
+            ..annotations[0].clone()
+        };
+        assert!(encode_mit_annotations(&[invalid]).is_err());
+        assert_eq!(mnemonic_code("V"), Some(5));
+        assert_eq!(mnemonic_code("(AFIB"), None);
+    }
+}
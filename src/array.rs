@@ -0,0 +1,229 @@
+//! Reading records into `ndarray` matrices.
+//!
+//! A record is read into an [`Array2`] of shape (samples, channels), with one column per signal in
+//! the order the signals are listed in the header and one row per frame. Signals with several
+//! samples per frame hold the average of their samples in each frame, as the WFDB library returns
+//! outside high-resolution mode. The matrix is filled straight from the decoded signal files, so
+//! reading a record this way never holds a separate copy of each signal.
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+
+use ndarray::Array2;
+
+use crate::header::Header;
+use crate::record::{self, Record};
+pub use crate::record::{ArraySample, Units};
+use crate::storage::{FileStorage, Storage};
+
+/// Read a record, relative to the current working directory, into a matrix of shape (samples,
+/// channels). `window` restricts the matrix to the samples between two times, in seconds from the
+/// beginning of the record. The record's header is returned alongside the matrix.
+pub fn read_array<T, P>(
+    record_name: P,
+    units: Units,
+    window: Option<Range<f64>>,
+) -> io::Result<(Header, Array2<T>)>
+where
+    T: ArraySample,
+    P: AsRef<Path>,
+{
+    read_array_from(
+        &FileStorage::new(""),
+        &record_name.as_ref().to_string_lossy(),
+        units,
+        window,
+    )
+}
+
+/// Read a record held in `storage` into a matrix of shape (samples, channels), as for
+/// [`read_array`].
+pub fn read_array_from<T: ArraySample>(
+    storage: &dyn Storage,
+    record_name: &str,
+    units: Units,
+    window: Option<Range<f64>>,
+) -> io::Result<(Header, Array2<T>)> {
+    read_rows_from(storage, record_name, units, |header, length| {
+        window_rows(header, length, window)
+    })
+}
+
+/// Read the rows of a record in `rows`, relative to the current working directory, into a
+/// matrix of shape (samples, channels), as for [`read_array`] with the window given in sample
+/// numbers (frames) instead of seconds, like `sampfrom` and `sampto` in the `wfdb` Python
+/// package.
+pub fn read_array_rows<T, P>(
+    record_name: P,
+    units: Units,
+    rows: Range<usize>,
+) -> io::Result<(Header, Array2<T>)>
+where
+    T: ArraySample,
+    P: AsRef<Path>,
+{
+    read_array_rows_from(
+        &FileStorage::new(""),
+        &record_name.as_ref().to_string_lossy(),
+        units,
+        rows,
+    )
+}
+
+/// Read the rows of a record held in `storage` in `rows` into a matrix, as for
+/// [`read_array_rows`].
+pub fn read_array_rows_from<T: ArraySample>(
+    storage: &dyn Storage,
+    record_name: &str,
+    units: Units,
+    rows: Range<usize>,
+) -> io::Result<(Header, Array2<T>)> {
+    read_rows_from(storage, record_name, units, |_, length| {
+        let start = rows.start.min(length);
+        start..rows.end.clamp(start, length)
+    })
+}
+
+/// Read the rows of a record chosen by `rows`, given the record's header and its length (or
+/// `usize::MAX` when the header does not give it).
+fn read_rows_from<T, F>(
+    storage: &dyn Storage,
+    record_name: &str,
+    units: Units,
+    rows: F,
+) -> io::Result<(Header, Array2<T>)>
+where
+    T: ArraySample,
+    F: FnOnce(&Header, usize) -> Range<usize>,
+{
+    let header = record::read_header_in(storage, record_name)?;
+    let length = header
+        .record
+        .samples_per_signal
+        .map_or(usize::MAX, |len| len as usize);
+    let rows = rows(&header, length);
+    let files = record::read_signal_files(storage, record_name, &header, rows.clone())?;
+    // Records whose header does not give their length end with their signal files
+    let rows = rows.start.min(files.len())..rows.end.min(files.len());
+    let array = fill(&header, rows, units, |signal, idx| {
+        files.frame_sample(signal, idx)
+    });
+    Ok((header, array))
+}
+
+impl Record {
+    /// Copy the record's samples into a matrix of shape (samples, channels), optionally
+    /// restricted to the samples between two times in seconds.
+    pub fn to_array<T: ArraySample>(&self, units: Units, window: Option<Range<f64>>) -> Array2<T> {
+        let rows = window_rows(&self.header, self.num_samples(), window);
+        fill(&self.header, rows, units, |signal, idx| {
+            self.frame_sample(signal, idx)
+        })
+    }
+}
+
+/// The rows of a record of `length` samples falling inside a time window.
+fn window_rows(header: &Header, length: usize, window: Option<Range<f64>>) -> Range<usize> {
+    let window = match window {
+        Some(window) => window,
+        None => return 0..length,
+    };
+    let frequency = header.sampling_frequency();
+    let sample_at = |seconds: f64| ((seconds * frequency).round().max(0.0) as usize).min(length);
+    let start = sample_at(window.start);
+    start..sample_at(window.end).max(start)
+}
+
+fn fill<T, F>(header: &Header, rows: Range<usize>, units: Units, sample: F) -> Array2<T>
+where
+    T: ArraySample,
+    F: Fn(usize, usize) -> i32,
+{
+    let signals = &header.signal_specs;
+    Array2::from_shape_fn((rows.len(), signals.len()), |(row, signal)| {
+        T::from_sample(sample(signal, rows.start + row), &signals[signal], units)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signal;
+    use crate::storage::MemoryStorage;
+
+    fn storage() -> MemoryStorage {
+        let header =
+            "rec 2 10 4\nrec.dat 16 100(10) 16 0 0 0 0 ECG\nrec.dat 16 50 16 0 0 0 0 PLETH\n";
+        let samples: [i16; 8] = [100, 50, 200, -32768, -100, 25, 0, 0];
+        let data = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        let mut storage = MemoryStorage::new();
+        storage.insert("db/rec.hea", header.as_bytes().to_vec());
+        storage.insert("db/rec.dat", data);
+        storage
+    }
+
+    #[test]
+    fn adc_units() {
+        let (header, array) =
+            read_array_from::<i32>(&storage(), "db/rec", Units::Adc, None).unwrap();
+        assert_eq!(header.signal_specs.len(), 2);
+        assert_eq!(array.shape(), &[4, 2]);
+        assert_eq!(array.row(1).to_vec(), vec![200, signal::INVALID_SAMPLE]);
+        assert_eq!(array.column(0).to_vec(), vec![100, 200, -100, 0]);
+    }
+
+    #[test]
+    fn physical_units() {
+        let (_, array) =
+            read_array_from::<f64>(&storage(), "db/rec", Units::Physical, None).unwrap();
+        assert_eq!(array.column(0).to_vec(), vec![0.9, 1.9, -1.1, -0.1]);
+        assert_eq!(array[[0, 1]], 1.0);
+        assert!(array[[1, 1]].is_nan());
+
+        let (_, array) =
+            read_array_from::<f32>(&storage(), "db/rec", Units::Physical, None).unwrap();
+        assert_eq!(array[[2, 1]], 0.5);
+        let (_, array) =
+            read_array_from::<i32>(&storage(), "db/rec", Units::Physical, None).unwrap();
+        assert_eq!(
+            array.column(1).to_vec(),
+            vec![1, signal::INVALID_SAMPLE, 1, 0]
+        );
+    }
+
+    #[test]
+    fn time_window() {
+        // 10 Hz, so the window from 0.1 s to 0.3 s holds samples 1 and 2
+        let (_, array) =
+            read_array_from::<i32>(&storage(), "db/rec", Units::Adc, Some(0.1..0.3)).unwrap();
+        assert_eq!(array.column(0).to_vec(), vec![200, -100]);
+        let (_, array) =
+            read_array_from::<i32>(&storage(), "db/rec", Units::Adc, Some(0.3..9.0)).unwrap();
+        assert_eq!(array.shape(), &[1, 2]);
+        let (_, array) =
+            read_array_from::<i32>(&storage(), "db/rec", Units::Adc, Some(2.0..1.0)).unwrap();
+        assert_eq!(array.shape(), &[0, 2]);
+    }
+
+    #[test]
+    fn sample_window() {
+        let (_, array) =
+            read_array_rows_from::<i32>(&storage(), "db/rec", Units::Adc, 1..3).unwrap();
+        assert_eq!(array.column(0).to_vec(), vec![200, -100]);
+        let (_, array) =
+            read_array_rows_from::<i32>(&storage(), "db/rec", Units::Adc, 3..100).unwrap();
+        assert_eq!(array.shape(), &[1, 2]);
+        let (_, array) =
+            read_array_rows_from::<i32>(&storage(), "db/rec", Units::Adc, 5..9).unwrap();
+        assert_eq!(array.shape(), &[0, 2]);
+    }
+
+    #[test]
+    fn record_to_array() {
+        let record = Record::open_from(&storage(), "db/rec").unwrap();
+        let (_, array) =
+            read_array_from::<i32>(&storage(), "db/rec", Units::Adc, Some(0.1..0.4)).unwrap();
+        assert_eq!(record.to_array::<i32>(Units::Adc, Some(0.1..0.4)), array);
+        assert_eq!(record.to_array::<i32>(Units::Adc, None).shape(), &[4, 2]);
+    }
+}
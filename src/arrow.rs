@@ -0,0 +1,281 @@
+//! Exporting records and annotations as Arrow record batches, and writing them to Parquet.
+//!
+//! A record becomes a table with a `time` column (seconds from the beginning of the record) and
+//! one column per signal, named after the signal's description. Each signal's units, gain and
+//! baseline are kept in the field metadata, and the record's name and sampling frequency in the
+//! schema metadata. Invalid samples are null.
+use std::collections::HashMap;
+#[cfg(feature = "parquet")]
+use std::io::{self, Write};
+use std::sync::Arc;
+
+use arrow_array::{
+    ArrayRef, Float64Array, Int32Array, Int8Array, RecordBatch, StringArray, UInt64Array,
+    UInt8Array,
+};
+use arrow_schema::{DataType, Field, Schema};
+
+use crate::annotation::Annotation;
+use crate::header::{self, SignalSpecLine};
+use crate::record::{Record, Units};
+use crate::signal;
+
+/// Convert a record into a record batch, with signal columns in ADC units (`Int32`) or physical
+/// units (`Float64`).
+pub fn record_batch(record: &Record, units: Units) -> RecordBatch {
+    let num_samples = record.num_samples();
+    let mut fields = vec![Field::new("time", DataType::Float64, false)];
+    let mut columns: Vec<ArrayRef> = vec![Arc::new(Float64Array::from_iter_values(
+        (0..num_samples).map(|sample| record.time_of(sample)),
+    ))];
+
+    let signals = record.header.signal_specs.iter().enumerate();
+    for ((idx, spec), name) in signals.zip(record.signal_names()) {
+        let samples = (0..num_samples).map(|frame| record.frame_sample(idx, frame));
+        let (data_type, column): (DataType, ArrayRef) = match units {
+            Units::Adc => (
+                DataType::Int32,
+                Arc::new(
+                    samples
+                        .map(|sample| Some(sample).filter(|&s| signal::is_valid(s)))
+                        .collect::<Int32Array>(),
+                ),
+            ),
+            Units::Physical => (
+                DataType::Float64,
+                Arc::new(
+                    samples
+                        .map(|sample| spec.to_physical(sample))
+                        .collect::<Float64Array>(),
+                ),
+            ),
+        };
+        fields.push(Field::new(name, data_type, true).with_metadata(signal_metadata(spec)));
+        columns.push(column);
+    }
+
+    let mut metadata = HashMap::new();
+    metadata.insert(
+        String::from("record_name"),
+        record.header.record.record_name.clone(),
+    );
+    metadata.insert(
+        String::from("sampling_frequency"),
+        record.sampling_frequency().to_string(),
+    );
+    let schema = Schema::new(fields).with_metadata(metadata);
+    RecordBatch::try_new(Arc::new(schema), columns).expect("All columns have one row per sample")
+}
+
+fn signal_metadata(spec: &SignalSpecLine) -> HashMap<String, String> {
+    let mut metadata = HashMap::new();
+    metadata.insert(
+        String::from("units"),
+        spec.units.clone().unwrap_or_else(|| String::from("mV")),
+    );
+    let gain = spec
+        .adc_gain
+        .filter(|&gain| gain != 0.0)
+        .unwrap_or(header::DEFGAIN);
+    metadata.insert(String::from("adc_gain"), gain.to_string());
+    metadata.insert(
+        String::from("baseline"),
+        spec.baseline.unwrap_or(0).to_string(),
+    );
+    metadata
+}
+
+/// Convert annotations into a record batch with one row per annotation. The `time` column gives
+/// each annotation's time in seconds, computed from the record's sampling frequency.
+pub fn annotation_batch(annotations: &[Annotation], sampling_frequency: f64) -> RecordBatch {
+    let schema = Schema::new(vec![
+        Field::new("sample", DataType::UInt64, false),
+        Field::new("time", DataType::Float64, false),
+        Field::new("code", DataType::UInt8, false),
+        Field::new("mnemonic", DataType::Utf8, true),
+        Field::new("subtype", DataType::Int8, false),
+        Field::new("chan", DataType::UInt8, false),
+        Field::new("num", DataType::Int8, false),
+        Field::new("aux", DataType::Utf8, true),
+    ]);
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(UInt64Array::from_iter_values(
+            annotations.iter().map(|a| a.sample),
+        )),
+        Arc::new(Float64Array::from_iter_values(
+            annotations
+                .iter()
+                .map(|a| a.sample as f64 / sampling_frequency),
+        )),
+        Arc::new(UInt8Array::from_iter_values(
+            annotations.iter().map(|a| a.code),
+        )),
+        Arc::new(
+            annotations
+                .iter()
+                .map(|a| a.mnemonic())
+                .collect::<StringArray>(),
+        ),
+        Arc::new(Int8Array::from_iter_values(
+            annotations.iter().map(|a| a.subtype),
+        )),
+        Arc::new(UInt8Array::from_iter_values(
+            annotations.iter().map(|a| a.chan),
+        )),
+        Arc::new(Int8Array::from_iter_values(
+            annotations.iter().map(|a| a.num),
+        )),
+        Arc::new(
+            annotations
+                .iter()
+                .map(|a| a.aux.as_deref())
+                .collect::<StringArray>(),
+        ),
+    ];
+    RecordBatch::try_new(Arc::new(schema), columns)
+        .expect("All columns have one row per annotation")
+}
+
+/// Write a record batch (such as one made by [`record_batch`] or [`annotation_batch`]) to
+/// `writer` as a Parquet file.
+#[cfg(feature = "parquet")]
+pub fn write_parquet<W: Write + Send>(writer: W, batch: &RecordBatch) -> io::Result<()> {
+    let mut writer = parquet::arrow::ArrowWriter::try_new(writer, batch.schema(), None)
+        .map_err(io::Error::other)?;
+    writer.write(batch).map_err(io::Error::other)?;
+    writer.close().map_err(io::Error::other)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::Array;
+
+    fn record() -> Record {
+        let header =
+            "rec 2 10 3\nrec.dat 16 100(10) 16 0 0 0 0 ECG\nrec.dat 16 50/mmHg 16 0 0 0 0\n";
+        let samples: [i16; 6] = [100, 50, 200, -32768, -100, 25];
+        Record::from_header_str(header, |_| {
+            Ok(samples.iter().flat_map(|s| s.to_le_bytes()).collect())
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn physical_record_batch() {
+        let batch = record_batch(&record(), Units::Physical);
+        let schema = batch.schema();
+        assert_eq!(schema.metadata()["record_name"], "rec");
+        assert_eq!(schema.metadata()["sampling_frequency"], "10");
+        let names: Vec<&str> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+        assert_eq!(names, vec!["time", "ECG", "signal 1"]);
+        assert_eq!(schema.field(1).metadata()["units"], "mV");
+        assert_eq!(schema.field(1).metadata()["baseline"], "10");
+        assert_eq!(schema.field(2).metadata()["units"], "mmHg");
+
+        let time = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(time.values().to_vec(), vec![0.0, 0.1, 0.2]);
+        let ecg = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(ecg.values().to_vec(), vec![0.9, 1.9, -1.1]);
+        let pressure = batch
+            .column(2)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(pressure.null_count(), 1);
+        assert!(pressure.is_null(1));
+        assert_eq!(pressure.value(2), 0.5);
+    }
+
+    #[test]
+    fn adc_record_batch() {
+        let batch = record_batch(&record(), Units::Adc);
+        let ecg = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<Int32Array>()
+            .unwrap();
+        assert_eq!(ecg.values().to_vec(), vec![100, 200, -100]);
+        let pressure = batch
+            .column(2)
+            .as_any()
+            .downcast_ref::<Int32Array>()
+            .unwrap();
+        assert_eq!(
+            pressure.iter().collect::<Vec<_>>(),
+            vec![Some(50), None, Some(25)]
+        );
+    }
+
+    #[test]
+    fn annotations() {
+        let annotations = vec![
+            Annotation {
+                sample: 18,
+                code: 28,
+                subtype: 0,
+                chan: 0,
+                num: 0,
+                aux: Some(String::from("(N")),
+            },
+            Annotation {
+                sample: 77,
+                code: 1,
+                subtype: 0,
+                chan: 1,
+                num: 0,
+                aux: None,
+            },
+        ];
+        let batch = annotation_batch(&annotations, 360.0);
+        assert_eq!(batch.num_rows(), 2);
+        let time = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(time.value(0), 0.05);
+        let mnemonic = batch
+            .column(3)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(
+            mnemonic.iter().collect::<Vec<_>>(),
+            vec![Some("+"), Some("N")]
+        );
+        let aux = batch
+            .column(7)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(aux.iter().collect::<Vec<_>>(), vec![Some("(N"), None]);
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn parquet_round_trip() {
+        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+        let batch = record_batch(&record(), Units::Physical);
+        let path = std::env::temp_dir().join(format!("wfdb-rust-{}.parquet", std::process::id()));
+        write_parquet(std::fs::File::create(&path).unwrap(), &batch).unwrap();
+        let builder =
+            ParquetRecordBatchReaderBuilder::try_new(std::fs::File::open(&path).unwrap()).unwrap();
+        assert_eq!(builder.schema().metadata()["record_name"], "rec");
+        assert_eq!(builder.schema().field(2).metadata()["units"], "mmHg");
+        let batches: Vec<RecordBatch> = builder.build().unwrap().collect::<Result<_, _>>().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].columns(), batch.columns());
+    }
+}
@@ -0,0 +1,65 @@
+//! Parsing of command-line options in the style of the WFDB tools: single options such as `-r
+//! 100`, and options followed by a list of values such as `-s 0 2 5`.
+use std::io;
+use std::str::FromStr;
+
+pub struct Args<'a> {
+    args: &'a [String],
+    position: usize,
+    usage: &'static str,
+}
+
+impl<'a> Args<'a> {
+    pub fn new(args: &'a [String], usage: &'static str) -> Args<'a> {
+        Args {
+            args,
+            position: 0,
+            usage,
+        }
+    }
+
+    /// The next option, or `None` once all arguments have been read.
+    pub fn next_option(&mut self) -> Option<&'a str> {
+        let arg = self.args.get(self.position)?;
+        self.position += 1;
+        Some(arg)
+    }
+
+    /// The value following `option`.
+    pub fn value(&mut self, option: &str) -> io::Result<&'a str> {
+        self.next_option()
+            .ok_or_else(|| self.error(&format!("{} needs a value", option)))
+    }
+
+    /// The value following `option`, parsed as a `T`.
+    pub fn parsed_value<T: FromStr>(&mut self, option: &str) -> io::Result<T> {
+        let value = self.value(option)?;
+        value
+            .parse()
+            .map_err(|_| self.error(&format!("invalid value {} for {}", value, option)))
+    }
+
+    /// The values following `option`, up to the next option.
+    pub fn values(&mut self, option: &str) -> io::Result<Vec<&'a str>> {
+        let mut values = vec![];
+        while let Some(arg) = self.args.get(self.position) {
+            if arg.starts_with('-') {
+                break;
+            }
+            values.push(arg.as_str());
+            self.position += 1;
+        }
+        if values.is_empty() {
+            return Err(self.error(&format!("{} needs at least one value", option)));
+        }
+        Ok(values)
+    }
+
+    /// An error for invalid arguments, followed by the command's usage.
+    pub fn error(&self, message: &str) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{}\n{}", message, self.usage),
+        )
+    }
+}
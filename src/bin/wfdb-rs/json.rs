@@ -0,0 +1,33 @@
+//! The little JSON the commands write, without pulling in a serializer.
+//!
+//! The commands write a few objects of fixed shape, which these two functions are enough for.
+//! Cargo has no dependencies for binaries alone, so serializing them with `serde_json` would make
+//! it a dependency of every user of the library, and the binary would need the `serde` feature.
+
+/// A string as a JSON string literal.
+pub fn string(value: &str) -> String {
+    let mut literal = String::with_capacity(value.len() + 2);
+    literal.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            c if (c as u32) < 0x20 => literal.push_str(&format!("\\u{:04x}", c as u32)),
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+/// A number as a JSON number, or `null` for NaN and infinities.
+pub fn number(value: f64) -> String {
+    if value.is_finite() {
+        value.to_string()
+    } else {
+        String::from("null")
+    }
+}
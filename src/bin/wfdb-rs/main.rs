@@ -0,0 +1,63 @@
+//! `wfdb-rs`: command-line tools built on wfdb-rust, each taking the options of the WFDB tool of
+//! the same name.
+//!
+//! ```text
+//! wfdb-rs rdsamp -r mitdb/100 -f 0:10 -t 0:20 -s MLII -p -c -v
+//! wfdb-rs rdann -r mitdb/100 -a atr -p V > pvcs.txt
+//! wfdb-rs wrann -r mitdb/100 -a pvc < pvcs.txt
+//! wfdb-rs wfdbdesc mitdb/100 --json
+//! ```
+mod args;
+mod json;
+mod rdann;
+mod rdsamp;
+mod wfdbdesc;
+mod wrann;
+
+use std::env;
+use std::io;
+use std::process;
+
+const USAGE: &str = "usage: wfdb-rs COMMAND [OPTIONS ...]
+
+Commands:
+  rdann     print the annotations of a record
+  rdsamp    print the samples of a record
+  wfdbdesc  describe a record and its signals
+  wrann     write an annotation file from the text printed by rdann
+
+Run `wfdb-rs COMMAND -h` for the options of a command.
+";
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let command = args.next().unwrap_or_default();
+    let args: Vec<String> = args.collect();
+    let result = match command.as_str() {
+        "rdann" => rdann::run(&args),
+        "rdsamp" => rdsamp::run(&args),
+        "wfdbdesc" => wfdbdesc::run(&args),
+        "wrann" => wrann::run(&args),
+        "-h" | "--help" => {
+            print!("{}", USAGE);
+            return;
+        }
+        _ => {
+            eprint!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+    match result {
+        Ok(()) => {}
+        // The output was piped into a program that stopped reading, such as `head`
+        Err(error) if error.kind() == io::ErrorKind::BrokenPipe => {}
+        Err(error) if error.kind() == io::ErrorKind::InvalidInput => {
+            eprintln!("wfdb-rs {}: {}", command, error);
+            process::exit(2);
+        }
+        Err(error) => {
+            eprintln!("wfdb-rs {}: {}", command, error);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,188 @@
+//! `rdann`: print the annotations of a record, one per line, in the layout of the WFDB `rdann`.
+use std::io::{self, BufWriter, Write};
+
+use wfdb_rust::annotation::{self, Annotation};
+use wfdb_rust::{time, Record};
+
+use crate::args::Args;
+
+const USAGE: &str = "usage: wfdb-rs rdann -r RECORD -a ANNOTATOR [OPTIONS ...]
+
+Options:
+  -r RECORD      record to read (required)
+  -a ANNOTATOR   annotator to read, such as atr (required)
+  -f TIME        start at TIME (default: the beginning of the record)
+  -t TIME        stop at TIME (default: the end of the annotations)
+  -p TYPE ...    print only annotations of these types, such as N V
+  -c CHAN        print only annotations on this channel
+  -n NUM         print only annotations with this number
+  -s SUBTYPE     print only annotations with this subtype
+  -v             print column headings
+  -h             print this usage
+
+TIME is HH:MM:SS (elapsed time), sNNN (sample number), [HH:MM:SS DD/MM/YYYY]
+(time of day and date) or e (the end of the record). Each line gives the elapsed
+time, sample number, type, subtype, channel and number of an annotation, then a
+tab and its auxiliary information, if any, with backslashes, tabs, newlines and
+carriage returns written as \\\\, \\t, \\n and \\r. `wfdb-rs wrann` reads this
+back.
+";
+
+/// Column headings, as printed by `rdann -v`.
+pub const HEADINGS: &str = "      Time   Sample #  Type  Sub Chan  Num\tAux";
+
+pub fn run(args: &[String]) -> io::Result<()> {
+    let mut args = Args::new(args, USAGE);
+    let mut record_name = None;
+    let mut annotator = None;
+    let mut from = None;
+    let mut to = None;
+    let mut types = None;
+    let mut chan = None;
+    let mut num = None;
+    let mut subtype = None;
+    let mut headings = false;
+    while let Some(option) = args.next_option() {
+        match option {
+            "-r" => record_name = Some(args.value(option)?),
+            "-a" => annotator = Some(args.value(option)?),
+            "-f" => from = Some(args.value(option)?),
+            "-t" => to = Some(args.value(option)?),
+            "-p" => {
+                let codes = args.values(option)?.into_iter().map(|mnemonic| {
+                    parse_type(mnemonic)
+                        .ok_or_else(|| args.error(&format!("unknown annotation type {}", mnemonic)))
+                });
+                types = Some(codes.collect::<io::Result<Vec<u8>>>()?);
+            }
+            "-c" => chan = Some(args.parsed_value::<u8>(option)?),
+            "-n" => num = Some(args.parsed_value::<i8>(option)?),
+            "-s" => subtype = Some(args.parsed_value::<i8>(option)?),
+            "-v" => headings = true,
+            "-h" => {
+                print!("{}", USAGE);
+                return Ok(());
+            }
+            _ => return Err(args.error(&format!("unknown option {}", option))),
+        }
+    }
+    let record_name = record_name.ok_or_else(|| args.error("no record given (-r)"))?;
+    let annotator = annotator.ok_or_else(|| args.error("no annotator given (-a)"))?;
+
+    let header = Record::read_header(record_name)
+        .map_err(|error| io::Error::new(error.kind(), format!("{}: {}", record_name, error)))?;
+    let sampling_frequency = header.sampling_frequency();
+    let sample_at = |spec: &str| {
+        time::parse_time_spec(spec, &header)
+            .map(|seconds| (seconds * sampling_frequency).round() as u64)
+            .ok_or_else(|| args.error(&format!("invalid time {}", spec)))
+    };
+    let start = from.map_or(Ok(0), sample_at)?;
+    let end = to.map_or(Ok(u64::MAX), sample_at)?;
+
+    let annotations = annotation::read_annotations(record_name, annotator).map_err(|error| {
+        io::Error::new(
+            error.kind(),
+            format!("{}.{}: {}", record_name, annotator, error),
+        )
+    })?;
+    let stdout = io::stdout();
+    let mut writer = BufWriter::new(stdout.lock());
+    if headings {
+        writeln!(writer, "{}", HEADINGS)?;
+    }
+    for annotation in annotations.iter().filter(|a| {
+        (start..end).contains(&a.sample)
+            && types.as_ref().is_none_or(|types| types.contains(&a.code))
+            && chan.is_none_or(|chan| a.chan == chan)
+            && num.is_none_or(|num| a.num == num)
+            && subtype.is_none_or(|subtype| a.subtype == subtype)
+    }) {
+        writeln!(writer, "{}", format_line(annotation, sampling_frequency))?;
+    }
+    writer.flush()
+}
+
+/// The type of an annotation as printed: its mnemonic, or its code in brackets for codes without
+/// one.
+pub fn format_type(code: u8) -> String {
+    match annotation::code_mnemonic(code) {
+        Some(mnemonic) => String::from(mnemonic),
+        None => format!("[{}]", code),
+    }
+}
+
+/// The code of an annotation type as printed by [`format_type`].
+pub fn parse_type(annotation_type: &str) -> Option<u8> {
+    annotation::mnemonic_code(annotation_type).or_else(|| {
+        annotation_type
+            .strip_prefix('[')
+            .and_then(|code| code.strip_suffix(']'))
+            .and_then(|code| code.parse().ok())
+    })
+}
+
+/// An elapsed time as printed by the WFDB tools: `M:SS.mmm`, or `H:MM:SS.mmm` from an hour on.
+fn format_elapsed_time(seconds: f64) -> String {
+    let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+    let (hours, minutes) = (millis / 3_600_000, millis / 60_000 % 60);
+    let seconds = format!("{:02}.{:03}", millis / 1000 % 60, millis % 1000);
+    if hours > 0 {
+        format!("{}:{:02}:{}", hours, minutes, seconds)
+    } else {
+        format!("{}:{}", minutes, seconds)
+    }
+}
+
+/// An annotation as a line of `rdann` output.
+pub fn format_line(annotation: &Annotation, sampling_frequency: f64) -> String {
+    let mut line = format!(
+        "{:>12}  {:>7}{:>6}{:>5}{:>5}{:>5}",
+        format_elapsed_time(annotation.sample as f64 / sampling_frequency),
+        annotation.sample,
+        format_type(annotation.code),
+        annotation.subtype,
+        annotation.chan,
+        annotation.num
+    );
+    if let Some(aux) = &annotation.aux {
+        line.push('\t');
+        line.push_str(&escape_aux(aux));
+    }
+    line
+}
+
+/// Auxiliary information with the characters that would break an `rdann` line escaped.
+fn escape_aux(aux: &str) -> String {
+    let mut escaped = String::with_capacity(aux.len());
+    for c in aux.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Auxiliary information as printed by `rdann`, with its escapes undone, or `None` if it holds an
+/// unknown escape.
+pub fn unescape_aux(escaped: &str) -> Option<String> {
+    let mut aux = String::with_capacity(escaped.len());
+    let mut chars = escaped.chars();
+    while let Some(c) = chars.next() {
+        aux.push(match c {
+            '\\' => match chars.next()? {
+                '\\' => '\\',
+                't' => '\t',
+                'n' => '\n',
+                'r' => '\r',
+                _ => return None,
+            },
+            _ => c,
+        });
+    }
+    Some(aux)
+}
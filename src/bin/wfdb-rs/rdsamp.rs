@@ -0,0 +1,278 @@
+//! `rdsamp`: print the samples of selected signals of a record over a time range.
+use std::io::{self, BufWriter, Write};
+use std::ops::Range;
+
+use wfdb_rust::csv::{self, CsvOptions, TimeFormat};
+use wfdb_rust::header::{Header, RecordLine};
+use wfdb_rust::record::{Record, Units};
+use wfdb_rust::{signal, time};
+
+use crate::args::Args;
+use crate::json;
+
+const USAGE: &str = "usage: wfdb-rs rdsamp -r RECORD [OPTIONS ...]
+
+Options:
+  -r RECORD      record to read (required)
+  -f TIME        start at TIME (default: the beginning of the record)
+  -t TIME        stop at TIME (default: the end of the record)
+  -l DURATION    stop DURATION after the start (instead of -t)
+  -s SIGNAL ...  print only these signals, given by number or name, in this order
+  -p, -pe        print values in physical units and times in seconds
+  -pd            as -p, with times of day and dates
+  -pS            as -p, with sample numbers
+  -H             high-resolution mode: print every sample of signals with several
+                 samples per frame, instead of their average over each frame
+  -c             print comma-separated values (default: tab-separated)
+  --json         print JSON
+  -v             print column headings
+  -h             print this usage
+
+TIME is HH:MM:SS (elapsed time), sNNN (sample number), [HH:MM:SS DD/MM/YYYY]
+(time of day and date) or e (the end of the record).
+";
+
+enum Output {
+    Delimited(char),
+    Json,
+}
+
+pub fn run(args: &[String]) -> io::Result<()> {
+    let mut args = Args::new(args, USAGE);
+    let mut record_name = None;
+    let mut from = None;
+    let mut to = None;
+    let mut length = None;
+    let mut selection = None;
+    let mut units = Units::Adc;
+    let mut time_format = TimeFormat::SampleIndex;
+    let mut high_resolution = false;
+    let mut output = Output::Delimited('\t');
+    let mut headings = false;
+    while let Some(option) = args.next_option() {
+        match option {
+            "-r" => record_name = Some(args.value(option)?),
+            "-f" => from = Some(args.value(option)?),
+            "-t" => to = Some(args.value(option)?),
+            "-l" => length = Some(args.value(option)?),
+            "-s" => selection = Some(args.values(option)?),
+            "-p" | "-pe" | "-pd" | "-pS" => {
+                units = Units::Physical;
+                time_format = match option {
+                    "-pd" => TimeFormat::Absolute,
+                    "-pS" => TimeFormat::SampleIndex,
+                    _ => TimeFormat::Seconds,
+                };
+            }
+            "-H" => high_resolution = true,
+            "-c" => output = Output::Delimited(','),
+            "--json" => output = Output::Json,
+            "-v" => headings = true,
+            "-h" => {
+                print!("{}", USAGE);
+                return Ok(());
+            }
+            _ => return Err(args.error(&format!("unknown option {}", option))),
+        }
+    }
+    let record_name = record_name.ok_or_else(|| args.error("no record given (-r)"))?;
+
+    let mut record = Record::open(record_name)
+        .map_err(|error| io::Error::new(error.kind(), format!("{}: {}", record_name, error)))?;
+    if let Some(selection) = selection {
+        record = select_signals(record, &selection).map_err(|message| args.error(&message))?;
+    }
+    record = if high_resolution {
+        resample_frames(record)
+    } else {
+        average_frames(record)
+    };
+
+    let parse_time = |spec: &str| match spec {
+        "e" => Ok(record.duration()),
+        _ => time::parse_time_spec(spec, &record.header)
+            .ok_or_else(|| args.error(&format!("invalid time {}", spec))),
+    };
+    let start = from.map_or(Ok(0.0), parse_time)?;
+    let end = match (length, to) {
+        (Some(length), _) => start + parse_time(length)?,
+        (None, Some(to)) => parse_time(to)?,
+        (None, None) => record.duration(),
+    };
+    if end < start {
+        return Err(args.error("the end time is before the start time"));
+    }
+
+    let stdout = io::stdout();
+    match output {
+        Output::Delimited(separator) => {
+            let options = CsvOptions {
+                time_format,
+                units,
+                window: Some(start..end),
+                header_rows: headings,
+                separator,
+            };
+            csv::write_csv(&record, stdout.lock(), &options)
+        }
+        Output::Json => {
+            let num_samples = record.num_samples();
+            let first = record.sample_at(start).min(num_samples);
+            let rows = first..record.sample_at(end).clamp(first, num_samples);
+            write_json(&record, rows, units, time_format, stdout.lock())
+        }
+    }
+}
+
+/// A record holding only the selected signals, given by number or by name.
+fn select_signals(record: Record, selection: &[&str]) -> Result<Record, String> {
+    let num_signals = record.signals.len();
+    let indices = selection
+        .iter()
+        .map(|&signal| {
+            signal
+                .parse::<usize>()
+                .ok()
+                .filter(|&idx| idx < num_signals)
+                .or_else(|| record.signal_index(signal))
+                .ok_or_else(|| format!("record has no signal {}", signal))
+        })
+        .collect::<Result<Vec<usize>, String>>()?;
+    Ok(Record {
+        header: Header {
+            record: RecordLine {
+                number_of_signals: indices.len() as u32,
+                ..record.header.record.clone()
+            },
+            signal_specs: indices
+                .iter()
+                .map(|&idx| record.header.signal_specs[idx].clone())
+                .collect(),
+        },
+        signals: indices
+            .iter()
+            .map(|&idx| record.signals[idx].clone())
+            .collect(),
+    })
+}
+
+/// Average each frame's samples of a record whose signals have several samples per frame, as the
+/// WFDB library does outside high-resolution mode, leaving one sample per signal per frame.
+fn average_frames(mut record: Record) -> Record {
+    for (spec, samples) in record
+        .header
+        .signal_specs
+        .iter_mut()
+        .zip(&mut record.signals)
+    {
+        let spf = spec.spf();
+        if spf > 1 {
+            *samples = samples.chunks(spf).map(signal::frame_average).collect();
+        }
+        spec.samples_per_frame = None;
+    }
+    record
+}
+
+/// Resample a record whose signals have several samples per frame at the sampling frequency of
+/// its fastest signal, as the WFDB library does in high-resolution mode: samples of slower signals
+/// are repeated, and sample numbers count samples of the fastest signal.
+fn resample_frames(mut record: Record) -> Record {
+    let samples_per_frame = record
+        .header
+        .signal_specs
+        .iter()
+        .map(|spec| spec.spf())
+        .max()
+        .unwrap_or(1);
+    if samples_per_frame <= 1 {
+        return record;
+    }
+    for (spec, samples) in record
+        .header
+        .signal_specs
+        .iter_mut()
+        .zip(&mut record.signals)
+    {
+        let spf = spec.spf();
+        let len = (samples.len() * samples_per_frame).div_ceil(spf);
+        *samples = (0..len)
+            .map(|idx| samples[idx * spf / samples_per_frame])
+            .collect();
+        spec.samples_per_frame = None;
+    }
+    let frequency = record.sampling_frequency() * samples_per_frame as f64;
+    record.header.record.sampling_frequency = Some(frequency as f32);
+    record.header.record.samples_per_signal = record
+        .header
+        .record
+        .samples_per_signal
+        .map(|length| length * samples_per_frame as u32);
+    record
+}
+
+/// Write the samples in `rows` as a JSON object holding the record name, the sampling frequency,
+/// the time of each row and, for each signal, its name, units and samples (`null` for invalid
+/// samples).
+fn write_json<W: Write>(
+    record: &Record,
+    rows: Range<usize>,
+    units: Units,
+    time_format: TimeFormat,
+    writer: W,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(writer);
+    let list = |values: &mut dyn Iterator<Item = String>| values.collect::<Vec<_>>().join(", ");
+    writeln!(writer, "{{")?;
+    writeln!(
+        writer,
+        "  \"record\": {},",
+        json::string(&record.header.record.record_name)
+    )?;
+    writeln!(
+        writer,
+        "  \"sampling_frequency\": {},",
+        json::number(record.sampling_frequency())
+    )?;
+    let mut times = rows.clone().map(|sample| match time_format {
+        TimeFormat::SampleIndex => sample.to_string(),
+        TimeFormat::Seconds => json::number(record.time_of(sample)),
+        TimeFormat::Absolute => json::string(&time::absolute_time(
+            &record.header.record,
+            record.time_of(sample),
+        )),
+    });
+    writeln!(writer, "  \"time\": [{}],", list(&mut times))?;
+    writeln!(writer, "  \"signals\": [")?;
+    let names = record.signal_names();
+    let specs = &record.header.signal_specs;
+    for (idx, spec) in specs.iter().enumerate() {
+        let mut samples = rows.clone().map(|sample| {
+            let value = *record.signals[idx]
+                .get(sample)
+                .unwrap_or(&signal::INVALID_SAMPLE);
+            match units {
+                Units::Adc if signal::is_valid(value) => value.to_string(),
+                Units::Physical => spec
+                    .to_physical(value)
+                    .map_or_else(|| String::from("null"), json::number),
+                _ => String::from("null"),
+            }
+        });
+        let signal_units = match units {
+            Units::Adc => "adu",
+            Units::Physical => spec.units.as_deref().unwrap_or("mV"),
+        };
+        writeln!(
+            writer,
+            "    {{\"name\": {}, \"units\": {}, \"samples\": [{}]}}{}",
+            json::string(&names[idx]),
+            json::string(signal_units),
+            list(&mut samples),
+            if idx + 1 < specs.len() { "," } else { "" }
+        )?;
+    }
+    writeln!(writer, "  ]")?;
+    writeln!(writer, "}}")?;
+    writer.flush()
+}
@@ -0,0 +1,326 @@
+//! `wfdbdesc`: describe a record and its signals from its header, checking each signal's
+//! checksum against its samples.
+use std::io::{self, BufWriter, Write};
+
+use wfdb_rust::header::{self, Header, StorageFormat};
+use wfdb_rust::{signal, time, Record};
+
+use crate::args::Args;
+use crate::json;
+
+const USAGE: &str = "usage: wfdb-rs wfdbdesc RECORD [--json]
+
+Options:
+  --json    print JSON
+  -h        print this usage
+";
+
+/// What reading a signal's samples says about the checksum in its header.
+enum ChecksumStatus {
+    NotGiven,
+    Matches,
+    Differs(i16),
+    /// The signal files could not be read.
+    Unchecked,
+}
+
+impl ChecksumStatus {
+    fn name(&self) -> &'static str {
+        match self {
+            ChecksumStatus::NotGiven => "not given",
+            ChecksumStatus::Matches => "matches",
+            ChecksumStatus::Differs(_) => "differs",
+            ChecksumStatus::Unchecked => "not checked",
+        }
+    }
+}
+
+/// A record's header, with what reading its signal files (when they can be read) tells about it.
+struct Description {
+    header: Header,
+    /// Number of samples per signal, from the header or else from the signal files.
+    length: Option<usize>,
+    checksums: Vec<ChecksumStatus>,
+    /// Group of each signal: the index of its signal file among those of the record.
+    groups: Vec<usize>,
+}
+
+pub fn run(args: &[String]) -> io::Result<()> {
+    let mut args = Args::new(args, USAGE);
+    let mut record_name = None;
+    let mut as_json = false;
+    while let Some(option) = args.next_option() {
+        match option {
+            "--json" => as_json = true,
+            "-h" => {
+                print!("{}", USAGE);
+                return Ok(());
+            }
+            _ if !option.starts_with('-') && record_name.is_none() => record_name = Some(option),
+            _ => return Err(args.error(&format!("unknown option {}", option))),
+        }
+    }
+    let record_name = record_name.ok_or_else(|| args.error("no record given"))?;
+
+    let description = describe(record_name)
+        .map_err(|error| io::Error::new(error.kind(), format!("{}: {}", record_name, error)))?;
+    let stdout = io::stdout();
+    let mut writer = BufWriter::new(stdout.lock());
+    if as_json {
+        write_json(&description, &mut writer)?;
+    } else {
+        write_text(&description, &mut writer)?;
+    }
+    writer.flush()
+}
+
+fn describe(record_name: &str) -> io::Result<Description> {
+    let header = Record::read_header(record_name)?;
+    let record = Record::open(record_name).ok();
+    let length = header
+        .record
+        .samples_per_signal
+        .map(|length| length as usize)
+        .or_else(|| record.as_ref().map(Record::num_samples));
+    let checksums = header
+        .signal_specs
+        .iter()
+        .enumerate()
+        .map(|(idx, spec)| match (spec.checksum, &record) {
+            (None, _) => ChecksumStatus::NotGiven,
+            (Some(_), None) => ChecksumStatus::Unchecked,
+            (Some(expected), Some(record)) => {
+                let computed = signal::checksum(&record.signals[idx]);
+                if computed == expected {
+                    ChecksumStatus::Matches
+                } else {
+                    ChecksumStatus::Differs(computed)
+                }
+            }
+        })
+        .collect();
+    let mut files: Vec<&str> = vec![];
+    let groups = header
+        .signal_specs
+        .iter()
+        .map(
+            |spec| match files.iter().position(|&file| file == spec.filename) {
+                Some(group) => group,
+                None => {
+                    files.push(&spec.filename);
+                    files.len() - 1
+                }
+            },
+        )
+        .collect();
+    Ok(Description {
+        header,
+        length,
+        checksums,
+        groups,
+    })
+}
+
+fn gain(spec: &header::SignalSpecLine) -> Option<f32> {
+    spec.adc_gain.filter(|&gain| gain != 0.0)
+}
+
+fn units(spec: &header::SignalSpecLine) -> &str {
+    spec.units.as_deref().unwrap_or("mV")
+}
+
+/// The description in the layout of the WFDB `wfdbdesc`.
+fn write_text<W: Write>(description: &Description, writer: &mut W) -> io::Result<()> {
+    let header = &description.header;
+    let record = &header.record;
+    let sampling_frequency = header.sampling_frequency();
+    writeln!(writer, "Record {}", record.record_name)?;
+    writeln!(writer)?;
+    match (&record.base_time, &record.base_date) {
+        (Some(time), Some(date)) => writeln!(writer, "Starting time: [{} {}]", time, date)?,
+        (Some(time), None) => writeln!(writer, "Starting time: [{}]", time)?,
+        _ => writeln!(writer, "Starting time: not specified")?,
+    }
+    match description.length {
+        Some(length) => writeln!(
+            writer,
+            "Length: {} ({} sample intervals)",
+            time::format_time(length as f64 / sampling_frequency),
+            length
+        )?,
+        None => writeln!(writer, "Length: not specified")?,
+    }
+    writeln!(writer, "Sampling frequency: {} Hz", sampling_frequency)?;
+    if let Some(counter_frequency) = record
+        .counter_frequency
+        .filter(|&frequency| Some(frequency) != record.sampling_frequency)
+    {
+        writeln!(
+            writer,
+            "Counter frequency: {} ticks per second",
+            counter_frequency
+        )?;
+        writeln!(
+            writer,
+            "Base counter value: {}",
+            record.base_counter_value.unwrap_or(0.0)
+        )?;
+    }
+    match header.signal_specs.len() {
+        1 => writeln!(writer, "1 signal")?,
+        num_signals => writeln!(writer, "{} signals", num_signals)?,
+    }
+
+    for (idx, spec) in header.signal_specs.iter().enumerate() {
+        writeln!(writer, "Group {}, Signal {}:", description.groups[idx], idx)?;
+        writeln!(writer, " File: {}", spec.filename)?;
+        writeln!(
+            writer,
+            " Description: {}",
+            spec.description.as_deref().unwrap_or("")
+        )?;
+        match gain(spec) {
+            Some(gain) => writeln!(writer, " Gain: {} adu/{}", gain, units(spec))?,
+            None => writeln!(
+                writer,
+                " Gain: uncalibrated; assume {} adu/{}",
+                header::DEFGAIN,
+                units(spec)
+            )?,
+        }
+        writeln!(
+            writer,
+            " Initial value: {}",
+            spec.initial_value.unwrap_or(0)
+        )?;
+        writeln!(writer, " Storage format: {}", spec.format as u32)?;
+        if let Some(samples_per_frame) = spec.samples_per_frame.filter(|&spf| spf > 1) {
+            writeln!(writer, " Samples per frame: {}", samples_per_frame)?;
+        }
+        if spec.format != StorageFormat::_null {
+            writeln!(
+                writer,
+                " ADC resolution: {} bits",
+                spec.adc_resolution.unwrap_or(12)
+            )?;
+        }
+        writeln!(writer, " ADC zero: {}", spec.adc_zero.unwrap_or(0))?;
+        writeln!(writer, " Baseline: {}", spec.baseline.unwrap_or(0))?;
+        match (spec.checksum, &description.checksums[idx]) {
+            (Some(checksum), ChecksumStatus::Differs(computed)) => writeln!(
+                writer,
+                " Checksum: {} (the samples give {})",
+                checksum, computed
+            )?,
+            (Some(checksum), status) => {
+                writeln!(writer, " Checksum: {} ({})", checksum, status.name())?
+            }
+            (None, _) => writeln!(writer, " Checksum: not given")?,
+        }
+        writeln!(writer, " Block size: {}", spec.block_size.unwrap_or(0))?;
+    }
+    Ok(())
+}
+
+fn optional_string(value: Option<&str>) -> String {
+    value.map_or_else(|| String::from("null"), json::string)
+}
+
+fn optional_number<T: Into<f64>>(value: Option<T>) -> String {
+    value.map_or_else(|| String::from("null"), |value| json::number(value.into()))
+}
+
+/// The description as a JSON object, with `null` for fields missing from the header.
+fn write_json<W: Write>(description: &Description, writer: &mut W) -> io::Result<()> {
+    let header = &description.header;
+    let record = &header.record;
+    let sampling_frequency = header.sampling_frequency();
+    writeln!(writer, "{{")?;
+    writeln!(
+        writer,
+        "  \"record\": {},",
+        json::string(&record.record_name)
+    )?;
+    writeln!(
+        writer,
+        "  \"start_time\": {},",
+        optional_string(record.base_time.as_deref())
+    )?;
+    writeln!(
+        writer,
+        "  \"start_date\": {},",
+        optional_string(record.base_date.as_deref())
+    )?;
+    writeln!(
+        writer,
+        "  \"sampling_frequency\": {},",
+        json::number(sampling_frequency)
+    )?;
+    writeln!(
+        writer,
+        "  \"counter_frequency\": {},",
+        optional_number(record.counter_frequency)
+    )?;
+    writeln!(
+        writer,
+        "  \"base_counter_value\": {},",
+        optional_number(record.base_counter_value)
+    )?;
+    writeln!(
+        writer,
+        "  \"length\": {},",
+        optional_number(description.length.map(|length| length as f64))
+    )?;
+    writeln!(
+        writer,
+        "  \"duration\": {},",
+        optional_string(
+            description
+                .length
+                .map(|length| time::format_time(length as f64 / sampling_frequency))
+                .as_deref()
+        )
+    )?;
+    writeln!(writer, "  \"signals\": [")?;
+    let specs = &header.signal_specs;
+    for (idx, spec) in specs.iter().enumerate() {
+        let status = &description.checksums[idx];
+        let fields = [
+            ("group", description.groups[idx].to_string()),
+            ("file", json::string(&spec.filename)),
+            ("description", optional_string(spec.description.as_deref())),
+            ("format", (spec.format as u32).to_string()),
+            ("samples_per_frame", optional_number(spec.samples_per_frame)),
+            ("skew", optional_number(spec.skew)),
+            ("byte_offset", optional_number(spec.byte_offset)),
+            ("gain", optional_number(gain(spec))),
+            ("units", json::string(units(spec))),
+            ("adc_resolution", optional_number(spec.adc_resolution)),
+            ("adc_zero", optional_number(spec.adc_zero)),
+            ("baseline", optional_number(spec.baseline)),
+            ("initial_value", optional_number(spec.initial_value)),
+            ("checksum", optional_number(spec.checksum)),
+            ("checksum_status", json::string(status.name())),
+            (
+                "computed_checksum",
+                match status {
+                    ChecksumStatus::Differs(computed) => computed.to_string(),
+                    _ => String::from("null"),
+                },
+            ),
+            ("block_size", optional_number(spec.block_size)),
+        ];
+        let fields: Vec<String> = fields
+            .iter()
+            .map(|(key, value)| format!("{}: {}", json::string(key), value))
+            .collect();
+        writeln!(
+            writer,
+            "    {{{}}}{}",
+            fields.join(", "),
+            if idx + 1 < specs.len() { "," } else { "" }
+        )?;
+    }
+    writeln!(writer, "  ]")?;
+    writeln!(writer, "}}")
+}
@@ -0,0 +1,82 @@
+//! `wrann`: write an annotation file in MIT format from annotations in the text layout printed by
+//! `rdann`.
+use std::io::{self, Read};
+
+use wfdb_rust::annotation::{self, Annotation};
+
+use crate::args::Args;
+use crate::rdann;
+
+const USAGE: &str = "usage: wfdb-rs wrann -r RECORD -a ANNOTATOR [OPTIONS ...] < ANNOTATIONS
+
+Options:
+  -r RECORD      record the annotations belong to (required)
+  -a ANNOTATOR   annotator to write, such as atr (required): the annotations are
+                 written to RECORD.ANNOTATOR
+  -h             print this usage
+
+Annotations are read from the standard input, one per line, as printed by
+`wfdb-rs rdann`: the time (which is ignored), sample number, type, subtype,
+channel and number of the annotation, then optionally a tab and its auxiliary
+information, with \\\\, \\t, \\n and \\r standing for a backslash, tab, newline and
+carriage return. Blank lines and column headings are skipped.
+";
+
+pub fn run(args: &[String]) -> io::Result<()> {
+    let mut args = Args::new(args, USAGE);
+    let mut record_name = None;
+    let mut annotator = None;
+    while let Some(option) = args.next_option() {
+        match option {
+            "-r" => record_name = Some(args.value(option)?),
+            "-a" => annotator = Some(args.value(option)?),
+            "-h" => {
+                print!("{}", USAGE);
+                return Ok(());
+            }
+            _ => return Err(args.error(&format!("unknown option {}", option))),
+        }
+    }
+    let record_name = record_name.ok_or_else(|| args.error("no record given (-r)"))?;
+    let annotator = annotator.ok_or_else(|| args.error("no annotator given (-a)"))?;
+
+    let mut text = String::new();
+    io::stdin().read_to_string(&mut text)?;
+    let annotations = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && line.trim() != rdann::HEADINGS.trim())
+        .map(|(idx, line)| {
+            parse_line(line).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: invalid annotation: {}", idx + 1, line),
+                )
+            })
+        })
+        .collect::<io::Result<Vec<Annotation>>>()?;
+    annotation::write_annotations(record_name, annotator, &annotations)
+}
+
+/// Parse a line of `rdann` output. The time may hold spaces (as in `[12:00:00 01/01/2000]`), so
+/// the fields are taken from the end.
+fn parse_line(line: &str) -> Option<Annotation> {
+    let (fields, aux) = match line.split_once('\t') {
+        Some((fields, aux)) => (fields, Some(rdann::unescape_aux(aux)?)),
+        None => (line, None),
+    };
+    let fields: Vec<&str> = fields.split_whitespace().collect();
+    match fields[..] {
+        [.., sample, annotation_type, subtype, chan, num] if fields.len() >= 6 => {
+            Some(Annotation {
+                sample: sample.parse().ok()?,
+                code: rdann::parse_type(annotation_type)?,
+                subtype: subtype.parse().ok()?,
+                chan: chan.parse().ok()?,
+                num: num.parse().ok()?,
+                aux,
+            })
+        }
+        _ => None,
+    }
+}
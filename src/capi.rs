@@ -0,0 +1,475 @@
+//! A C interface modelled on a subset of the WFDB C library (`libwfdb`).
+//!
+//! The functions and structures keep the names, signatures and return codes of their
+//! counterparts declared in `<wfdb/wfdb.h>` (WFDB 10.6, where `WFDB_Time` is 64 bits wide), so
+//! programs using only this subset can be relinked against this crate. As in the WFDB library,
+//! one record and one set of annotators are open at a time, and strings returned to the caller
+//! stay valid until the next call that replaces them. The C declarations are in
+//! `include/wfdb_rust.h`, generated with `cbindgen --config cbindgen.toml --output
+//! include/wfdb_rust.h`.
+//!
+//! Records are read whole when opened. For signals with several samples per frame, [`getvec`]
+//! returns the average of each frame's samples and [`getframe`] returns all of them, as the WFDB
+//! library does outside high-resolution mode.
+#![allow(non_camel_case_types, clippy::missing_safety_doc)]
+
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::io;
+use std::os::raw::{c_char, c_double, c_int, c_uint};
+use std::panic;
+use std::ptr;
+use std::sync::{Mutex, MutexGuard};
+
+use crate::annotation::{self, Annotation};
+use crate::header::{self, DEFREQ};
+use crate::record::{self, Record};
+use crate::signal;
+use crate::storage::{self, FileStorage};
+use crate::time;
+
+pub type WFDB_Sample = c_int;
+pub type WFDB_Time = i64;
+pub type WFDB_Frequency = c_double;
+pub type WFDB_Gain = c_double;
+pub type WFDB_Group = c_uint;
+pub type WFDB_Annotator = c_uint;
+
+/// Value of invalid samples returned by [`getvec`], as in the WFDB library.
+pub const WFDB_INVALID_SAMPLE: WFDB_Sample = -32768;
+
+/// `stat` of an annotator opened for reading.
+pub const WFDB_READ: c_int = 0;
+
+/// Description of a signal, filled in by [`isigopen`].
+#[repr(C)]
+pub struct WFDB_Siginfo {
+    /// Name of the signal file.
+    pub fname: *mut c_char,
+    /// Signal description.
+    pub desc: *mut c_char,
+    /// Physical units.
+    pub units: *mut c_char,
+    /// ADC units per physical unit (0: uncalibrated).
+    pub gain: WFDB_Gain,
+    /// Value of sample number 0.
+    pub initval: WFDB_Sample,
+    /// Signal group number: signals in the same file share a group.
+    pub group: WFDB_Group,
+    /// Storage format (8, 16, 212, ...).
+    pub fmt: c_int,
+    /// Samples per frame.
+    pub spf: c_int,
+    /// Block size (for character special files only).
+    pub bsize: c_int,
+    /// ADC resolution in bits.
+    pub adcres: c_int,
+    /// ADC output given 0 VDC input.
+    pub adczero: c_int,
+    /// ADC output given 0 physical units input.
+    pub baseline: c_int,
+    /// Number of samples (0: unspecified).
+    pub nsamp: WFDB_Time,
+    /// 16-bit checksum of all samples.
+    pub cksum: c_int,
+}
+
+/// An annotator to open with [`annopen`].
+#[repr(C)]
+pub struct WFDB_Anninfo {
+    /// Annotator name, such as `atr`.
+    pub name: *mut c_char,
+    /// How to open the annotator; only [`WFDB_READ`] is supported.
+    pub stat: c_int,
+}
+
+/// An annotation, filled in by [`getann`].
+#[repr(C)]
+pub struct WFDB_Annotation {
+    /// Time of the annotation, in samples from the beginning of the record.
+    pub time: WFDB_Time,
+    /// Annotation code.
+    pub anntyp: c_char,
+    pub subtyp: i8,
+    pub chan: u8,
+    pub num: i8,
+    /// Auxiliary information: a length byte followed by that many bytes and a null byte, or null
+    /// when the annotation has none.
+    pub aux: *mut u8,
+}
+
+struct InputSignals {
+    record: Record,
+    /// Indexes of the signals opened by `isigopen`.
+    signals: Vec<usize>,
+    position: usize,
+}
+
+struct InputAnnotator {
+    annotations: Vec<Annotation>,
+    position: usize,
+    aux: Vec<u8>,
+}
+
+struct State {
+    input: Option<InputSignals>,
+    /// Strings pointed to by the `WFDB_Siginfo` structures filled in by the last `isigopen`.
+    siginfo_strings: Vec<CString>,
+    annotators: Vec<InputAnnotator>,
+    timstr: Option<CString>,
+}
+
+static STATE: Mutex<State> = Mutex::new(State {
+    input: None,
+    siginfo_strings: vec![],
+    annotators: vec![],
+    timstr: None,
+});
+
+fn state() -> MutexGuard<'static, State> {
+    STATE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+impl State {
+    fn sampling_frequency(&self) -> f64 {
+        self.input
+            .as_ref()
+            .map_or(DEFREQ as f64, |input| input.record.sampling_frequency())
+    }
+}
+
+unsafe fn to_str<'a>(string: *const c_char) -> Option<&'a str> {
+    if string.is_null() {
+        None
+    } else {
+        CStr::from_ptr(string).to_str().ok()
+    }
+}
+
+fn c_string(string: &str) -> CString {
+    CString::new(string.replace('\0', "")).expect("Null bytes were removed")
+}
+
+/// Open the signals of `record`, filling in `siarray` with the description of each signal.
+///
+/// Up to `nsig` signals are opened for reading with [`getvec`]. When `nsig` is negative, up to
+/// `-nsig` signals are described but none are opened. Returns the number of signals opened (or
+/// the number of signals in the record, for a negative `nsig`), -1 if the record cannot be read
+/// or -2 if its header is invalid.
+#[no_mangle]
+pub unsafe extern "C" fn isigopen(
+    record: *const c_char,
+    siarray: *mut WFDB_Siginfo,
+    nsig: c_int,
+) -> c_int {
+    let record_name = match to_str(record) {
+        Some(name) => name,
+        None => return -1,
+    };
+    let opened = match panic::catch_unwind(|| Record::open(record_name)) {
+        Ok(Ok(opened)) => opened,
+        Ok(Err(error)) if error.kind() == io::ErrorKind::InvalidData => return -2,
+        Ok(Err(_)) => return -1,
+        Err(_) => return -2,
+    };
+
+    let num_signals = opened.signals.len();
+    let count = (nsig.unsigned_abs() as usize).min(num_signals);
+    let mut strings = vec![];
+    let mut groups: HashMap<&str, WFDB_Group> = HashMap::new();
+    for (idx, spec) in opened.header.signal_specs.iter().enumerate() {
+        let next_group = groups.len() as WFDB_Group;
+        let group = *groups.entry(spec.filename.as_str()).or_insert(next_group);
+        if idx >= count || siarray.is_null() {
+            continue;
+        }
+        let mut string = |value: &str| {
+            strings.push(c_string(value));
+            strings.last().unwrap().as_ptr() as *mut c_char
+        };
+        *siarray.add(idx) = WFDB_Siginfo {
+            fname: string(&spec.filename),
+            desc: string(spec.description.as_deref().unwrap_or("")),
+            units: string(spec.units.as_deref().unwrap_or("mV")),
+            gain: spec.adc_gain.unwrap_or(0.0) as f64,
+            initval: spec.initial_value.unwrap_or(0),
+            group,
+            fmt: spec.format as c_int,
+            spf: spec.samples_per_frame.unwrap_or(1) as c_int,
+            bsize: spec.block_size.unwrap_or(0) as c_int,
+            adcres: spec.adc_resolution.unwrap_or(12) as c_int,
+            adczero: spec.adc_zero.unwrap_or(0),
+            baseline: spec.baseline.unwrap_or(0),
+            nsamp: opened.header.record.samples_per_signal.unwrap_or(0) as WFDB_Time,
+            cksum: spec.checksum.unwrap_or(0) as c_int,
+        };
+    }
+    let mut state = state();
+    state.siginfo_strings = strings;
+    if nsig < 0 {
+        return num_signals as c_int;
+    }
+    state.input = Some(InputSignals {
+        record: opened,
+        signals: (0..count).collect(),
+        position: 0,
+    });
+    count as c_int
+}
+
+/// Read the next sample of each open signal into `vector`, averaging the samples in the frame of
+/// signals with several samples per frame. Returns the number of samples read, or -1 at the end of
+/// the record (or when no record is open).
+#[no_mangle]
+pub unsafe extern "C" fn getvec(vector: *mut WFDB_Sample) -> c_int {
+    let mut state = state();
+    let input = match state.input.as_mut() {
+        Some(input) if input.position < input.record.num_samples() => input,
+        _ => return -1,
+    };
+    for (idx, &signal) in input.signals.iter().enumerate() {
+        *vector.add(idx) = match input.record.frame_sample(signal, input.position) {
+            sample if signal::is_valid(sample) => sample,
+            _ => WFDB_INVALID_SAMPLE,
+        };
+    }
+    input.position += 1;
+    input.signals.len() as c_int
+}
+
+/// Read the next frame of the open signals into `vector`: every sample of each signal in the
+/// frame, so that a signal with `spf` samples per frame fills `spf` entries. Returns the number of
+/// signals read, or -1 at the end of the record (or when no record is open).
+#[no_mangle]
+pub unsafe extern "C" fn getframe(vector: *mut WFDB_Sample) -> c_int {
+    let mut state = state();
+    let input = match state.input.as_mut() {
+        Some(input) if input.position < input.record.num_samples() => input,
+        _ => return -1,
+    };
+    let mut entry = 0;
+    for &signal in &input.signals {
+        let spf = input.record.header.signal_specs[signal].spf();
+        for idx in input.position * spf..(input.position + 1) * spf {
+            *vector.add(entry) = match input.record.signals[signal].get(idx) {
+                Some(&sample) if signal::is_valid(sample) => sample,
+                _ => WFDB_INVALID_SAMPLE,
+            };
+            entry += 1;
+        }
+    }
+    input.position += 1;
+    input.signals.len() as c_int
+}
+
+/// Move the input position to sample number `t` (or `-t`, for a negative time as returned by
+/// [`strtim`]). Returns 0, or -1 if no record is open or `t` is past its end.
+#[no_mangle]
+pub extern "C" fn isigsettime(t: WFDB_Time) -> c_int {
+    let mut state = state();
+    match state.input.as_mut() {
+        Some(input) if t.unsigned_abs() as usize <= input.record.num_samples() => {
+            input.position = t.unsigned_abs() as usize;
+            0
+        }
+        _ => -1,
+    }
+}
+
+/// Open the annotators listed in `aiarray` for `record`, closing any open annotators. Each
+/// annotator is then read with [`getann`], by its index in `aiarray`. Returns 0, -3 if an
+/// annotation file cannot be read or -4 for annotators not opened with [`WFDB_READ`].
+#[no_mangle]
+pub unsafe extern "C" fn annopen(
+    record: *const c_char,
+    aiarray: *const WFDB_Anninfo,
+    nann: c_uint,
+) -> c_int {
+    let mut state = state();
+    state.annotators.clear();
+    let record_name = match to_str(record) {
+        Some(name) => name,
+        None => return -3,
+    };
+    for idx in 0..nann as usize {
+        let info = &*aiarray.add(idx);
+        if info.stat != WFDB_READ {
+            return -4;
+        }
+        let annotations =
+            match to_str(info.name).map(|name| annotation::read_annotations(record_name, name)) {
+                Some(Ok(annotations)) => annotations,
+                _ => return -3,
+            };
+        state.annotators.push(InputAnnotator {
+            annotations,
+            position: 0,
+            aux: vec![],
+        });
+    }
+    0
+}
+
+/// Read the next annotation from annotator `an` into `annot`. Returns 0, -1 at the end of the
+/// annotations or -2 if the annotator is not open. The `aux` string stays valid until the next
+/// call for the same annotator.
+#[no_mangle]
+pub unsafe extern "C" fn getann(an: WFDB_Annotator, annot: *mut WFDB_Annotation) -> c_int {
+    let mut state = state();
+    let annotator = match state.annotators.get_mut(an as usize) {
+        Some(annotator) => annotator,
+        None => return -2,
+    };
+    let next = match annotator.annotations.get(annotator.position) {
+        Some(next) => next,
+        None => return -1,
+    };
+    annotator.aux.clear();
+    if let Some(aux) = &next.aux {
+        let bytes = &aux.as_bytes()[..aux.len().min(255)];
+        annotator.aux.push(bytes.len() as u8);
+        annotator.aux.extend(bytes);
+        annotator.aux.push(0);
+    }
+    *annot = WFDB_Annotation {
+        time: next.sample as WFDB_Time,
+        anntyp: next.code as c_char,
+        subtyp: next.subtype,
+        chan: next.chan,
+        num: next.num,
+        aux: if next.aux.is_some() {
+            annotator.aux.as_mut_ptr()
+        } else {
+            ptr::null_mut()
+        },
+    };
+    annotator.position += 1;
+    0
+}
+
+/// Convert a time string into a sample number, using the sampling frequency of the open record.
+///
+/// Elapsed times (`HH:MM:SS`, `MM:SS` or `SS`, with optional fractional seconds) are converted
+/// into a number of samples, and `sNNN` is sample number NNN. `e` is the end of the record and
+/// `i` the current input position. An absolute time in brackets (`[HH:MM:SS DD/MM/YYYY]`) gives
+/// the negated sample number at that time. Returns 0 for strings that cannot be parsed.
+#[no_mangle]
+pub unsafe extern "C" fn strtim(string: *const c_char) -> WFDB_Time {
+    let state = state();
+    let string = match to_str(string) {
+        Some(string) => string.trim(),
+        None => return 0,
+    };
+    let frequency = state.sampling_frequency();
+    let to_samples = |seconds: f64| (seconds * frequency).round() as WFDB_Time;
+    if let Some(sample) = string.strip_prefix('s') {
+        return sample.parse().unwrap_or(0);
+    }
+    match (string, state.input.as_ref()) {
+        ("e", Some(input)) => input.record.num_samples() as WFDB_Time,
+        ("i", Some(input)) => input.position as WFDB_Time,
+        ("e", None) | ("i", None) => 0,
+        _ => match string.strip_prefix('[') {
+            Some(absolute) => {
+                let absolute = absolute.trim_end_matches(']');
+                let record = match state.input.as_ref() {
+                    Some(input) => &input.record.header.record,
+                    None => return 0,
+                };
+                let (time, base) = match (
+                    time::parse_absolute_time(absolute),
+                    time::parse_absolute_time(&time::absolute_time(record, 0.0)),
+                ) {
+                    (Some(time), Some(base)) => (time, base),
+                    _ => return 0,
+                };
+                // Times of day are compared on their own (the string's time being on the day
+                // after the base time if earlier) unless both the string and the record have a
+                // date
+                let time_dated = absolute.contains('/');
+                let base_dated = record.base_date.is_some();
+                let time_of_day = |seconds: f64, dated: bool| match dated {
+                    true => time::split_timestamp(seconds).0,
+                    false => seconds,
+                };
+                let elapsed = if time_dated && base_dated {
+                    time - base
+                } else {
+                    (time_of_day(time, time_dated) - time_of_day(base, base_dated))
+                        .rem_euclid(86_400.0)
+                };
+                -to_samples(elapsed.max(0.0))
+            }
+            None => time::parse_time(string).map_or(0, to_samples),
+        },
+    }
+}
+
+/// Format a sample number as a time, using the sampling frequency of the open record.
+///
+/// Positive times are formatted as elapsed times (`H:MM:SS`, or `MM:SS` under an hour), and
+/// negative times (and 0, for records with a base time) as absolute times in brackets, as
+/// `[HH:MM:SS DD/MM/YYYY]`. The string stays valid until the next call.
+#[no_mangle]
+pub extern "C" fn timstr(t: WFDB_Time) -> *const c_char {
+    let mut state = state();
+    let frequency = state.sampling_frequency();
+    let seconds = t.unsigned_abs() as f64 / frequency;
+    let record = state
+        .input
+        .as_ref()
+        .map(|input| &input.record.header.record);
+    let absolute = record.filter(|record| {
+        t < 0 || (t == 0 && (record.base_time.is_some() || record.base_date.is_some()))
+    });
+    let formatted = match absolute {
+        Some(record) => {
+            let absolute = time::absolute_time(record, seconds);
+            let mut fields = absolute.split_whitespace();
+            let time_of_day = fields.next().unwrap_or("");
+            let time_of_day = time_of_day.split('.').next().unwrap_or(time_of_day);
+            match fields.next() {
+                Some(date) => format!("[{} {}]", time_of_day, date),
+                None => format!("[{}]", time_of_day),
+            }
+        }
+        None => {
+            let seconds = seconds as u64;
+            match seconds / 3600 {
+                0 => format!("{:2}:{:02}", seconds / 60, seconds % 60),
+                hours => format!("{}:{:02}:{:02}", hours, seconds / 60 % 60, seconds % 60),
+            }
+        }
+    };
+    state.timstr.insert(c_string(&formatted)).as_ptr()
+}
+
+/// Sampling frequency of the open record (or of `record`, if not null), in frames per second: the
+/// sampling frequency of signals with one sample per frame. Returns -1 if the record cannot be
+/// read.
+#[no_mangle]
+pub unsafe extern "C" fn sampfreq(record: *const c_char) -> WFDB_Frequency {
+    if record.is_null() {
+        return state().sampling_frequency();
+    }
+    let header = to_str(record).map(|name| {
+        panic::catch_unwind(|| {
+            let path = record::header_path(name);
+            header::read_header_from(storage::open_file(&FileStorage::new(""), &path)?)
+        })
+    });
+    match header {
+        Some(Ok(Ok(header))) => header.sampling_frequency(),
+        _ => -1.0,
+    }
+}
+
+/// Close the open record and annotators.
+#[no_mangle]
+pub extern "C" fn wfdbquit() {
+    let mut state = state();
+    state.input = None;
+    state.annotators.clear();
+}
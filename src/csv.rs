@@ -0,0 +1,590 @@
+//! Exporting records as CSV in the layout written by `rdsamp -c -v`, and importing such files back
+//! into records.
+//!
+//! An exported file has a row of column names and a row of units, each quoted with single quotes,
+//! followed by one row per sample: the time of the sample, then the value of each signal. Invalid
+//! samples are written as `-`.
+use std::io::{self, BufWriter, Read, Write};
+use std::ops::Range;
+
+use crate::header::{self, Header, RecordLine, SignalSpecLine, StorageFormat};
+use crate::record::{Record, Units};
+use crate::signal;
+use crate::time;
+
+/// How the time of each sample is written in the first column.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TimeFormat {
+    /// The sample number, counting from 0.
+    SampleIndex,
+    /// Seconds from the beginning of the record.
+    Seconds,
+    /// Time of day and date, from the record's base time and date (`[HH:MM:SS.mmm DD/MM/YYYY]`).
+    Absolute,
+}
+
+/// Options for [`write_csv`].
+#[derive(Clone, PartialEq, Debug)]
+pub struct CsvOptions {
+    pub time_format: TimeFormat,
+    pub units: Units,
+    /// Only write the samples between two times, in seconds from the beginning of the record.
+    pub window: Option<Range<f64>>,
+    /// Write the rows of column names and units before the samples.
+    pub header_rows: bool,
+    /// Character between fields: `,`, or a tab as in the default output of `rdsamp`.
+    pub separator: char,
+}
+
+impl Default for CsvOptions {
+    /// Elapsed seconds and physical units, with header rows, as written by `rdsamp -c -p -v`.
+    fn default() -> CsvOptions {
+        CsvOptions {
+            time_format: TimeFormat::Seconds,
+            units: Units::Physical,
+            window: None,
+            header_rows: true,
+            separator: ',',
+        }
+    }
+}
+
+/// Number of decimals needed to tell apart values one step of `1 / resolution` apart, and at
+/// least the 3 decimals written by `rdsamp`.
+fn decimals(resolution: f64) -> usize {
+    ((resolution.log10() - 1e-9).ceil() as i64).clamp(3, 12) as usize
+}
+
+/// Write a record (or a time window of it) as CSV.
+pub fn write_csv<W: Write>(record: &Record, writer: W, options: &CsvOptions) -> io::Result<()> {
+    let mut writer = BufWriter::new(writer);
+    let specs = &record.header.signal_specs;
+    if options.header_rows {
+        let (time_name, time_units) = match options.time_format {
+            TimeFormat::SampleIndex => ("sample #", ""),
+            TimeFormat::Seconds => ("Elapsed time", "seconds"),
+            TimeFormat::Absolute => ("Time and date", "hh:mm:ss.mmm dd/mm/yyyy"),
+        };
+        let names = record.signal_names();
+        let units = specs.iter().map(|spec| match options.units {
+            Units::Adc => "adu",
+            Units::Physical => spec.units.as_deref().unwrap_or("mV"),
+        });
+        let separator = options.separator;
+        write_row(
+            &mut writer,
+            separator,
+            time_name,
+            names.iter().map(String::as_str),
+        )?;
+        write_row(&mut writer, separator, time_units, units)?;
+    }
+
+    let num_samples = record.num_samples();
+    let rows = match &options.window {
+        Some(window) => {
+            let start = record.sample_at(window.start).min(num_samples);
+            start..record.sample_at(window.end).clamp(start, num_samples)
+        }
+        None => 0..num_samples,
+    };
+    let time_decimals = decimals(record.sampling_frequency());
+    let value_decimals: Vec<usize> = specs
+        .iter()
+        .map(|spec| {
+            decimals(
+                spec.adc_gain
+                    .filter(|&gain| gain != 0.0)
+                    .unwrap_or(header::DEFGAIN) as f64,
+            )
+        })
+        .collect();
+    for sample in rows {
+        match options.time_format {
+            TimeFormat::SampleIndex => write!(writer, "{}", sample)?,
+            TimeFormat::Seconds => write!(writer, "{:.*}", time_decimals, record.time_of(sample))?,
+            TimeFormat::Absolute => write!(
+                writer,
+                "'[{}]'",
+                time::absolute_time(&record.header.record, record.time_of(sample))
+            )?,
+        }
+        for (idx, spec) in specs.iter().enumerate() {
+            let value = record.frame_sample(idx, sample);
+            match options.units {
+                Units::Adc if signal::is_valid(value) => {
+                    write!(writer, "{}{}", options.separator, value)?
+                }
+                Units::Physical if signal::is_valid(value) => write!(
+                    writer,
+                    "{}{:.*}",
+                    options.separator,
+                    value_decimals[idx],
+                    spec.to_physical(value).unwrap()
+                )?,
+                _ => write!(writer, "{}-", options.separator)?,
+            }
+        }
+        writeln!(writer)?;
+    }
+    writer.flush()
+}
+
+fn write_row<'a, W: Write>(
+    writer: &mut W,
+    separator: char,
+    first: &str,
+    fields: impl Iterator<Item = &'a str>,
+) -> io::Result<()> {
+    write!(writer, "'{}'", first)?;
+    for field in fields {
+        write!(writer, "{}'{}'", separator, field)?;
+    }
+    writeln!(writer)
+}
+
+/// Strip the quotes (and, for absolute times, brackets) around a field.
+fn unquote(field: &str) -> &str {
+    let field = field.trim();
+    let field = field
+        .strip_prefix('\'')
+        .and_then(|f| f.strip_suffix('\''))
+        .unwrap_or(field);
+    let field = field
+        .strip_prefix('"')
+        .and_then(|f| f.strip_suffix('"'))
+        .unwrap_or(field);
+    field
+        .strip_prefix('[')
+        .and_then(|f| f.strip_suffix(']'))
+        .unwrap_or(field)
+}
+
+fn is_value(field: &str) -> bool {
+    field == "-"
+        || field.parse::<f64>().is_ok()
+        || (field.contains(':') && time::parse_absolute_time(field).is_some())
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Number of digits after the decimal point in a value.
+fn fraction_digits(field: &str) -> u32 {
+    field.split('.').nth(1).map_or(0, |fraction| {
+        fraction.chars().take_while(char::is_ascii_digit).count() as u32
+    })
+}
+
+/// Build a record from CSV in the layout written by [`write_csv`] or `rdsamp -c` (tab-separated
+/// files are read too). The header rows are optional; when present, a first column named
+/// `sample #`, `Elapsed time` or `Time and date` is taken as the time of each sample, and signals
+/// whose units are `adu` are taken to be in ADC units.
+///
+/// Signals are stored in format 16 in `<record_name>.dat` (format 32 for ADC values outside the
+/// 16-bit range), with a gain chosen so that physical values are kept to the precision they are
+/// written with. The sampling frequency is worked out from the time column when it is not given
+/// (and defaults to 250 Hz without one), and the base time and date are taken from absolute
+/// times. The record can then be saved with [`Record::write_in`].
+pub fn read_csv<R: Read>(
+    mut reader: R,
+    record_name: &str,
+    sampling_frequency: Option<f32>,
+) -> io::Result<Record> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+    let delimiter = if text.lines().next().is_some_and(|line| line.contains(',')) {
+        ','
+    } else {
+        '\t'
+    };
+    let mut rows = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.split(delimiter).map(unquote).collect::<Vec<&str>>())
+        .peekable();
+
+    let mut header_rows = vec![];
+    while header_rows.len() < 2
+        && rows
+            .peek()
+            .is_some_and(|row| !row.iter().all(|field| is_value(field)))
+    {
+        header_rows.push(rows.next().unwrap());
+    }
+    let rows: Vec<Vec<&str>> = rows.collect();
+    let names = header_rows.first();
+    let units = header_rows.get(1);
+
+    let time_format = match names.map(|names| names[0].to_lowercase()).as_deref() {
+        Some("sample #") | Some("sample") => Some(TimeFormat::SampleIndex),
+        Some("elapsed time") | Some("time") => Some(TimeFormat::Seconds),
+        Some("time and date") => Some(TimeFormat::Absolute),
+        Some(_) => None,
+        None if rows.first().is_some_and(|row| row[0].contains(':')) => Some(TimeFormat::Absolute),
+        None => None,
+    };
+    let first_signal = if time_format.is_some() { 1 } else { 0 };
+    let num_columns = names.or_else(|| rows.first()).map_or(0, Vec::len);
+    if num_columns <= first_signal {
+        return Err(invalid_data(String::from("CSV file has no signal columns")));
+    }
+    if let Some(row) = rows.iter().find(|row| row.len() != num_columns) {
+        return Err(invalid_data(format!(
+            "Expected {} columns, found {}: {}",
+            num_columns,
+            row.len(),
+            row.join(",")
+        )));
+    }
+
+    let times: Vec<f64> = match time_format {
+        Some(TimeFormat::Seconds) => rows
+            .iter()
+            .filter_map(|row| time::parse_time(row[0]))
+            .collect(),
+        Some(TimeFormat::Absolute) => rows
+            .iter()
+            .filter_map(|row| time::parse_absolute_time(row[0]))
+            .collect(),
+        _ => vec![],
+    };
+    let sampling_frequency = sampling_frequency
+        .or_else(|| {
+            infer_sampling_frequency(&times, rows.last().map_or(0, |row| fraction_digits(row[0])))
+        })
+        .unwrap_or(header::DEFREQ);
+    let (base_time, base_date) = match (time_format, rows.first()) {
+        (Some(TimeFormat::Absolute), Some(row)) => {
+            let start = times.first().copied().unwrap_or(0.0);
+            let (time_of_day, date) = time::split_timestamp(start);
+            let has_date = row[0].split_whitespace().nth(1).is_some();
+            (
+                Some(time::format_time(time_of_day)),
+                Some(time::format_date(date)).filter(|_| has_date),
+            )
+        }
+        _ => (None, None),
+    };
+
+    let mut signal_specs = vec![];
+    let mut signals = vec![];
+    for column in first_signal..num_columns {
+        let fields: Vec<&str> = rows.iter().map(|row| row[column]).collect();
+        let unit = units
+            .map(|units| units[column])
+            .filter(|unit| !unit.is_empty());
+        let (spec, samples) = if unit == Some("adu") {
+            adc_signal(&fields)?
+        } else {
+            physical_signal(&fields, unit)?
+        };
+        signal_specs.push(SignalSpecLine {
+            filename: format!("{}.dat", record_name),
+            initial_value: Some(samples.first().copied().unwrap_or(0)),
+            checksum: Some(signal::checksum(&samples)),
+            description: names.map(|names| String::from(names[column])),
+            ..spec
+        });
+        signals.push(samples);
+    }
+
+    let record = RecordLine {
+        record_name: String::from(record_name),
+        number_of_segments: None,
+        number_of_signals: signal_specs.len() as u32,
+        sampling_frequency: Some(sampling_frequency),
+        counter_frequency: Some(sampling_frequency),
+        base_counter_value: Some(0.0),
+        samples_per_signal: Some(rows.len() as u32),
+        base_time,
+        base_date,
+    };
+    Ok(Record {
+        header: Header {
+            record,
+            signal_specs,
+        },
+        signals,
+    })
+}
+
+/// Work out the sampling frequency from the time of each sample, given to `digits` decimals.
+/// The result is rounded to a whole number of hertz when that is within the precision of the times.
+fn infer_sampling_frequency(times: &[f64], digits: u32) -> Option<f32> {
+    let intervals = times.len().checked_sub(1).filter(|&n| n > 0)? as f64;
+    let span = times.last()? - times.first()?;
+    if span <= 0.0 {
+        return None;
+    }
+    let frequency = intervals / span;
+    let tolerance = frequency * frequency * 10_f64.powi(-(digits as i32)) / intervals;
+    if (frequency - frequency.round()).abs() <= tolerance {
+        Some(frequency.round() as f32)
+    } else {
+        Some(frequency as f32)
+    }
+}
+
+fn spec(
+    format: StorageFormat,
+    adc_gain: f32,
+    units: Option<&str>,
+    adc_resolution: u32,
+) -> SignalSpecLine {
+    SignalSpecLine {
+        filename: String::new(),
+        format,
+        samples_per_frame: None,
+        skew: None,
+        byte_offset: None,
+        adc_gain: Some(adc_gain),
+        baseline: Some(0),
+        units: Some(String::from(units.unwrap_or("mV"))),
+        adc_resolution: Some(adc_resolution),
+        adc_zero: Some(0),
+        initial_value: None,
+        checksum: None,
+        block_size: Some(0),
+        description: None,
+    }
+}
+
+/// A signal given in ADC units, stored uncalibrated (with a gain of 0).
+fn adc_signal(fields: &[&str]) -> io::Result<(SignalSpecLine, Vec<i32>)> {
+    let samples = fields
+        .iter()
+        .map(|&field| match field {
+            "-" => Ok(signal::INVALID_SAMPLE),
+            _ => field
+                .parse::<i32>()
+                .map_err(|_| invalid_data(format!("Invalid sample value: {}", field))),
+        })
+        .collect::<io::Result<Vec<i32>>>()?;
+    let fits_16bit = samples
+        .iter()
+        .all(|&s| !signal::is_valid(s) || s > i16::MIN as i32 && s <= i16::MAX as i32);
+    Ok(match fits_16bit {
+        true => (
+            spec(StorageFormat::_16bit_twos_complement, 0.0, None, 16),
+            samples,
+        ),
+        false => (
+            spec(StorageFormat::_32bit_twos_complement_lsb, 0.0, None, 32),
+            samples,
+        ),
+    })
+}
+
+/// A signal given in physical units, stored in format 16 with a gain of a power of 10 matching
+/// the precision the values are written with (reduced if needed to fit the 16-bit range).
+fn physical_signal(fields: &[&str], units: Option<&str>) -> io::Result<(SignalSpecLine, Vec<i32>)> {
+    let values = fields
+        .iter()
+        .map(|&field| match field {
+            "-" => Ok(None),
+            _ => field
+                .parse::<f64>()
+                .map(Some)
+                .map_err(|_| invalid_data(format!("Invalid sample value: {}", field))),
+        })
+        .collect::<io::Result<Vec<Option<f64>>>>()?;
+    let max_abs = values
+        .iter()
+        .flatten()
+        .fold(0_f64, |max, value| max.max(value.abs()));
+    let mut digits = fields
+        .iter()
+        .map(|field| fraction_digits(field))
+        .max()
+        .unwrap_or(0)
+        .min(9) as i32;
+    while digits > 0 && max_abs * 10_f64.powi(digits) > i16::MAX as f64 {
+        digits -= 1;
+    }
+    let mut gain = 10_f64.powi(digits);
+    if max_abs * gain > i16::MAX as f64 {
+        gain = i16::MAX as f64 / max_abs;
+    }
+    let samples = values
+        .iter()
+        .map(|value| match value {
+            Some(value) => (value * gain).round().max(-(i16::MAX as f64)) as i32,
+            None => signal::INVALID_SAMPLE,
+        })
+        .collect();
+    Ok((
+        spec(
+            StorageFormat::_16bit_twos_complement,
+            gain as f32,
+            units,
+            16,
+        ),
+        samples,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record() -> Record {
+        let header = "rec 2 250 4 12:00:00 31/12/1999\nrec.dat 16 200(10) 16 0 0 0 0 MLII\nrec.dat 16 10/mmHg 16 0 0 0 0 ABP\n";
+        let invalid = signal::INVALID_SAMPLE;
+        let samples = [110, 1000, 10, 995, invalid, 1001, 12, invalid];
+        Record::from_header_str(header, |_| Ok(signal::encode_16_format(&samples))).unwrap()
+    }
+
+    fn export(record: &Record, options: &CsvOptions) -> String {
+        let mut buf = vec![];
+        write_csv(record, &mut buf, options).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn physical_seconds() {
+        assert_eq!(
+            export(&record(), &CsvOptions::default()),
+            "'Elapsed time','MLII','ABP'\n'seconds','mV','mmHg'\n\
+             0.000,0.500,100.000\n0.004,0.000,99.500\n0.008,-,100.100\n0.012,0.010,-\n"
+        );
+    }
+
+    #[test]
+    fn raw_sample_index_window() {
+        let options = CsvOptions {
+            time_format: TimeFormat::SampleIndex,
+            units: Units::Adc,
+            window: Some(0.004..0.012),
+            header_rows: false,
+            separator: ',',
+        };
+        assert_eq!(export(&record(), &options), "1,10,995\n2,-,1001\n");
+    }
+
+    #[test]
+    fn absolute_time() {
+        let options = CsvOptions {
+            time_format: TimeFormat::Absolute,
+            window: Some(0.0..0.008),
+            ..CsvOptions::default()
+        };
+        let csv = export(&record(), &options);
+        assert_eq!(
+            csv.lines().nth(2),
+            Some("'[12:00:00.000 31/12/1999]',0.500,100.000")
+        );
+        assert_eq!(
+            csv.lines().nth(3),
+            Some("'[12:00:00.004 31/12/1999]',0.000,99.500")
+        );
+    }
+
+    #[test]
+    fn tab_separated() {
+        let options = CsvOptions {
+            separator: '\t',
+            ..CsvOptions::default()
+        };
+        let tsv = export(&record(), &options);
+        assert_eq!(tsv.lines().nth(2), Some("0.000\t0.500\t100.000"));
+        let imported = read_csv(tsv.as_bytes(), "copy", None).unwrap();
+        assert_eq!(export(&imported, &options), tsv);
+    }
+
+    #[test]
+    fn import_physical() {
+        let csv = export(&record(), &CsvOptions::default());
+        let imported = read_csv(csv.as_bytes(), "copy", None).unwrap();
+        assert_eq!(imported.header.record.sampling_frequency, Some(250.0));
+        assert_eq!(imported.header.record.samples_per_signal, Some(4));
+        assert_eq!(imported.signal_names(), vec!["MLII", "ABP"]);
+        assert_eq!(
+            imported.header.signal_specs[1].units.as_deref(),
+            Some("mmHg")
+        );
+        assert_eq!(imported.header.signal_specs[0].filename, "copy.dat");
+        let original = record();
+        for signal in 0..2 {
+            assert_eq!(
+                imported.physical_samples(signal),
+                original.physical_samples(signal)
+            );
+        }
+        // The re-exported file is identical
+        assert_eq!(export(&imported, &CsvOptions::default()), csv);
+    }
+
+    #[test]
+    fn import_raw_absolute() {
+        let options = CsvOptions {
+            time_format: TimeFormat::Absolute,
+            units: Units::Adc,
+            ..CsvOptions::default()
+        };
+        let csv = export(&record(), &options);
+        let imported = read_csv(csv.as_bytes(), "copy", None).unwrap();
+        assert_eq!(imported.signals, record().signals);
+        assert_eq!(
+            imported.header.record.base_time.as_deref(),
+            Some("12:00:00.000")
+        );
+        assert_eq!(
+            imported.header.record.base_date.as_deref(),
+            Some("31/12/1999")
+        );
+        assert_eq!(
+            imported.header.signal_specs[0].format,
+            StorageFormat::_16bit_twos_complement
+        );
+
+        let imported = read_csv(
+            "'sample #','big'\n'','adu'\n0,100000\n1,-\n".as_bytes(),
+            "big",
+            Some(1000.0),
+        )
+        .unwrap();
+        assert_eq!(
+            imported.header.signal_specs[0].format,
+            StorageFormat::_32bit_twos_complement_lsb
+        );
+        assert_eq!(
+            imported.signals,
+            vec![vec![100_000, signal::INVALID_SAMPLE]]
+        );
+        assert_eq!(imported.sampling_frequency(), 1000.0);
+    }
+
+    #[test]
+    fn import_without_header_rows() {
+        // rdsamp -p without -c writes tab-separated columns, with elapsed time as m:ss.mmm
+        let imported = read_csv(
+            "0:00.000\t-0.145\n0:00.003\t-0.150\n0:00.006\t-0.160\n".as_bytes(),
+            "x",
+            Some(360.0),
+        )
+        .unwrap();
+        assert_eq!(imported.header.signal_specs.len(), 1);
+        assert_eq!(imported.signals[0], vec![-145, -150, -160]);
+
+        let imported = read_csv("1.5,20\n2.25,30\n".as_bytes(), "x", None).unwrap();
+        assert_eq!(imported.header.signal_specs.len(), 2);
+        assert_eq!(imported.header.signal_specs[0].adc_gain, Some(100.0));
+        assert_eq!(imported.signals[0], vec![150, 225]);
+        assert_eq!(imported.sampling_frequency(), header::DEFREQ as f64);
+
+        let error = read_csv("1,2\n3\n".as_bytes(), "x", None).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn sampling_frequency_from_times() {
+        let times: Vec<f64> = (0..1000)
+            .map(|i| (i as f64 / 360.0 * 1000.0).round() / 1000.0)
+            .collect();
+        assert_eq!(infer_sampling_frequency(&times, 3), Some(360.0));
+        assert_eq!(infer_sampling_frequency(&[0.0, 0.4, 0.8], 1), Some(2.5));
+        assert_eq!(infer_sampling_frequency(&[1.0], 3), None);
+    }
+}
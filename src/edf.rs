@@ -0,0 +1,999 @@
+//! Reading and writing EDF and EDF+ files.
+//!
+//! An EDF file holds a header describing each signal, followed by data records of a fixed
+//! duration in which each signal has a fixed number of 16-bit samples. This is the layout of a
+//! WFDB signal file in format 16 whose frames are the data records, so an EDF header maps onto a
+//! [`Header`] with a frame frequency of one frame per data record, and each signal's samples per
+//! data record as its samples per frame. The gain and baseline of each signal are worked out
+//! from its physical and digital minimum and maximum. The signals are given the signal file
+//! `<record>.dat` in format 16 rather than the EDF file itself, so that a record read from an EDF
+//! file is converted to WFDB signal files, not written over the EDF file, by [`Record::write_in`].
+//!
+//! EDF+ files may also hold `EDF Annotations` signals, which are not signals but time-stamped
+//! annotation lists. These are left out of the header and read with [`read_annotations_from`].
+//! Records (and their annotations) are converted to EDF and EDF+ with [`write_edf`].
+use std::convert::TryFrom;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::annotation::Annotation;
+use crate::header::{self, Header, RecordLine, SignalSpecLine, StorageFormat};
+use crate::record::{Record, SignalFiles};
+use crate::signal;
+use crate::storage::{self, FileStorage, Storage};
+use crate::time;
+
+/// Label of the EDF+ signals holding annotations instead of samples.
+pub const ANNOTATIONS_LABEL: &str = "EDF Annotations";
+
+/// Annotation code given to EDF+ annotations, whose text is kept as the auxiliary information
+/// (the code of a comment annotation, `"`, in the WFDB library).
+pub const NOTE: u8 = 22;
+
+/// Width of each field of a signal's header, in the order the fields appear.
+const SIGNAL_FIELDS: [usize; 10] = [16, 80, 8, 8, 8, 8, 8, 80, 8, 32];
+
+/// An annotation read from an EDF+ time-stamped annotation list.
+#[derive(Clone, PartialEq, Debug)]
+pub struct EdfAnnotation {
+    /// Time of the annotation, in seconds from the beginning of the recording.
+    pub onset: f64,
+    /// Duration of the annotated event, in seconds.
+    pub duration: Option<f64>,
+    pub text: String,
+}
+
+impl EdfAnnotation {
+    /// Convert to a WFDB annotation at the sample closest to the onset, with the annotation's
+    /// text as auxiliary information.
+    pub fn to_annotation(&self, sampling_frequency: f64) -> Annotation {
+        Annotation {
+            sample: (self.onset * sampling_frequency).round().max(0.0) as u64,
+            code: NOTE,
+            subtype: 0,
+            chan: 0,
+            num: 0,
+            aux: Some(self.text.clone()),
+        }
+    }
+}
+
+/// Whether a record name refers to an EDF file, by its extension.
+pub fn is_edf(record_name: &str) -> bool {
+    record_name.to_ascii_lowercase().ends_with(".edf")
+}
+
+/// The name of a file without the directories leading to it.
+fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// The layout of an EDF file, as described by its header.
+struct EdfLayout {
+    header: Header,
+    /// Samples per data record of every signal in the file, annotation signals included.
+    samples_per_record: Vec<usize>,
+    /// Which of the signals in the file are annotation signals.
+    is_annotations: Vec<bool>,
+    header_bytes: usize,
+    num_records: Option<usize>,
+}
+
+impl EdfLayout {
+    fn record_bytes(&self) -> usize {
+        self.samples_per_record.iter().sum::<usize>() * 2
+    }
+
+    /// Number of complete data records in a file of `len` bytes.
+    fn num_records(&self, len: usize) -> usize {
+        let available = len.saturating_sub(self.header_bytes) / self.record_bytes().max(1);
+        self.num_records
+            .map_or(available, |num_records| num_records.min(available))
+    }
+
+    /// Iterate over the data records of a file, giving the bytes of each signal in turn.
+    fn records<'a>(&'a self, buf: &'a [u8]) -> impl Iterator<Item = Vec<&'a [u8]>> + 'a {
+        let data = &buf[self.header_bytes.min(buf.len())..];
+        data.chunks_exact(self.record_bytes().max(1))
+            .take(self.num_records(buf.len()))
+            .map(move |record| {
+                let mut offset = 0;
+                self.samples_per_record
+                    .iter()
+                    .map(|&samples| {
+                        offset += samples * 2;
+                        &record[offset - samples * 2..offset]
+                    })
+                    .collect()
+            })
+    }
+}
+
+/// Read an ASCII header field, without its padding.
+fn field(buf: &[u8], offset: usize, len: usize) -> String {
+    String::from_utf8_lossy(&buf[offset..offset + len])
+        .trim()
+        .to_string()
+}
+
+fn number<T: FromStr>(text: &str, name: &str) -> io::Result<T> {
+    text.parse::<T>().map_err(|_| {
+        invalid_data(format!(
+            "Invalid EDF header: {} is not a number: {:?}",
+            name, text
+        ))
+    })
+}
+
+/// Convert the `dd.mm.yy` start date of an EDF header to a WFDB date, taking two-digit years
+/// from 1985 to 2084 as the EDF specification does.
+fn start_date(date: &str) -> Option<String> {
+    let fields: Vec<u32> = date
+        .split('.')
+        .map(|field| field.parse().ok())
+        .collect::<Option<_>>()?;
+    match fields[..] {
+        [day, month, year] => {
+            let year = if year >= 85 { 1900 + year } else { 2000 + year };
+            Some(format!("{:02}/{:02}/{}", day, month, year))
+        }
+        _ => None,
+    }
+}
+
+fn parse_layout(buf: &[u8], filename: &str) -> io::Result<EdfLayout> {
+    if buf.len() < 256 {
+        return Err(invalid_data(String::from("EDF header is truncated")));
+    }
+    if field(buf, 0, 8) != "0" {
+        return Err(invalid_data(format!("{} is not an EDF file", filename)));
+    }
+    let start_time = field(buf, 176, 8).replace('.', ":");
+    let start_date = start_date(&field(buf, 168, 8));
+    let header_bytes: usize = number(&field(buf, 184, 8), "header size")?;
+    let num_records: i64 = number(&field(buf, 236, 8), "number of data records")?;
+    let record_duration: f64 = number(&field(buf, 244, 8), "data record duration")?;
+    let num_signals: usize = number(&field(buf, 252, 4), "number of signals")?;
+    if buf.len() < 256 * (num_signals + 1) {
+        return Err(invalid_data(String::from("EDF header is truncated")));
+    }
+
+    // Each field is given for every signal before the next field starts
+    let signal_field = |field_idx: usize, signal: usize| {
+        let start = 256 + SIGNAL_FIELDS[..field_idx].iter().sum::<usize>() * num_signals;
+        let len = SIGNAL_FIELDS[field_idx];
+        field(buf, start + signal * len, len)
+    };
+
+    let record_name = file_name(filename);
+    let record_name = if is_edf(record_name) {
+        &record_name[..record_name.len() - 4]
+    } else {
+        record_name
+    };
+    let mut signal_specs = vec![];
+    let mut samples_per_record = vec![];
+    let mut is_annotations = vec![];
+    for signal in 0..num_signals {
+        let label = signal_field(0, signal);
+        let samples: usize = number(&signal_field(8, signal), "samples per data record")?;
+        samples_per_record.push(samples);
+        is_annotations.push(label == ANNOTATIONS_LABEL);
+        if label == ANNOTATIONS_LABEL {
+            continue;
+        }
+
+        let physical_min: f64 = number(&signal_field(3, signal), "physical minimum")?;
+        let physical_max: f64 = number(&signal_field(4, signal), "physical maximum")?;
+        let digital_min: f64 = number(&signal_field(5, signal), "digital minimum")?;
+        let digital_max: f64 = number(&signal_field(6, signal), "digital maximum")?;
+        let gain = match physical_max - physical_min {
+            range if range != 0.0 => (digital_max - digital_min) / range,
+            _ => 1.0,
+        };
+        let units = signal_field(2, signal);
+        signal_specs.push(SignalSpecLine {
+            filename: format!("{}.dat", record_name),
+            format: StorageFormat::_16bit_twos_complement,
+            samples_per_frame: Some(samples as u32),
+            skew: None,
+            byte_offset: None,
+            adc_gain: Some(gain as f32),
+            baseline: Some((digital_max - physical_max * gain).round() as i32),
+            units: Some(units).filter(|units| !units.is_empty()),
+            adc_resolution: Some(16),
+            adc_zero: Some(((digital_max + digital_min) / 2.0).round() as i32),
+            initial_value: None,
+            checksum: None,
+            block_size: Some(0),
+            description: Some(label),
+        });
+    }
+
+    let frame_frequency = if record_duration > 0.0 {
+        (1.0 / record_duration) as f32
+    } else {
+        header::DEFREQ
+    };
+    let record = RecordLine {
+        record_name: String::from(record_name),
+        number_of_segments: None,
+        number_of_signals: signal_specs.len() as u32,
+        sampling_frequency: Some(frame_frequency),
+        counter_frequency: Some(frame_frequency),
+        base_counter_value: Some(0.0),
+        samples_per_signal: None,
+        base_time: Some(start_time),
+        base_date: start_date,
+    };
+    Ok(EdfLayout {
+        header: Header {
+            record,
+            signal_specs,
+        },
+        samples_per_record,
+        is_annotations,
+        header_bytes,
+        num_records: usize::try_from(num_records).ok(),
+    })
+}
+
+/// Map the header of an EDF file (given at least up to the end of its header) onto a WFDB
+/// header. `filename` is the name of the EDF file, whose name without its extension is the record
+/// name.
+pub fn parse_header(buf: &[u8], filename: &str) -> io::Result<Header> {
+    let mut layout = parse_layout(buf, filename)?;
+    layout.header.record.samples_per_signal =
+        layout.num_records.map(|num_records| num_records as u32);
+    Ok(layout.header)
+}
+
+/// Read the header of an EDF file held in `storage`, without reading its data records.
+pub fn read_header_from(storage: &dyn Storage, path: &str) -> io::Result<Header> {
+    let fixed_header = storage::read_file_range(storage, path, 0, 256)?;
+    let num_signals: u64 = number(&field(&fixed_header, 252, 4), "number of signals")?;
+    let buf = storage::read_file_range(storage, path, 0, 256 * (num_signals + 1))?;
+    parse_header(&buf, file_name(path))
+}
+
+/// Read an EDF file held in `storage`, returning the WFDB header it maps onto and its signals.
+pub(crate) fn open_signal_files(
+    storage: &dyn Storage,
+    path: &str,
+) -> io::Result<(Header, SignalFiles)> {
+    let buf = storage::read_file(storage, path)?;
+    let mut layout = parse_layout(&buf, file_name(path))?;
+    let mut signals = vec![vec![]; layout.header.signal_specs.len()];
+    for record in layout.records(&buf) {
+        let data_signals = record
+            .iter()
+            .zip(&layout.is_annotations)
+            .filter(|(_, &annotations)| !annotations);
+        for (samples, (bytes, _)) in signals.iter_mut().zip(data_signals) {
+            samples.extend(
+                bytes
+                    .chunks_exact(2)
+                    .map(|b| i16::from_le_bytes([b[0], b[1]]) as i32),
+            );
+        }
+    }
+    layout.header.record.samples_per_signal = Some(layout.num_records(buf.len()) as u32);
+    for (spec, samples) in layout.header.signal_specs.iter_mut().zip(&signals) {
+        spec.initial_value = samples.first().copied();
+    }
+    let files = SignalFiles::from_signals(&layout.header, signals);
+    Ok((layout.header, files))
+}
+
+/// Parse the time-stamped annotation lists in the bytes of an EDF+ annotation signal. Each list
+/// is `+onset[\x15duration]\x14text\x14[text\x14...]\0`; lists without any text only keep time
+/// and are skipped.
+fn parse_annotation_lists(bytes: &[u8], annotations: &mut Vec<EdfAnnotation>) {
+    for list in bytes.split(|&b| b == 0).filter(|list| !list.is_empty()) {
+        let mut parts = list.split(|&b| b == 0x14);
+        let timing = String::from_utf8_lossy(parts.next().unwrap_or_default()).into_owned();
+        let mut timing = timing.split('\u{15}');
+        let onset = match timing.next().and_then(|onset| onset.parse::<f64>().ok()) {
+            Some(onset) => onset,
+            None => continue,
+        };
+        let duration = timing
+            .next()
+            .and_then(|duration| duration.parse::<f64>().ok());
+        for text in parts.filter(|text| !text.is_empty()) {
+            annotations.push(EdfAnnotation {
+                onset,
+                duration,
+                text: String::from_utf8_lossy(text).into_owned(),
+            });
+        }
+    }
+}
+
+/// Read the annotations of an EDF+ file held in `storage`, with their durations.
+pub fn read_edf_annotations_from(
+    storage: &dyn Storage,
+    path: &str,
+) -> io::Result<Vec<EdfAnnotation>> {
+    let buf = storage::read_file(storage, path)?;
+    let layout = parse_layout(&buf, file_name(path))?;
+    let mut annotations = vec![];
+    for record in layout.records(&buf) {
+        for (bytes, _) in record
+            .iter()
+            .zip(&layout.is_annotations)
+            .filter(|(_, &annotations)| annotations)
+        {
+            parse_annotation_lists(bytes, &mut annotations);
+        }
+    }
+    Ok(annotations)
+}
+
+/// Read the annotations of an EDF+ file held in `storage` as WFDB annotations (see
+/// [`EdfAnnotation::to_annotation`]). As elsewhere, sample numbers count frames, here the file's
+/// data records, so that [`Record::time_of`] gives their time; onsets are rounded to the nearest
+/// data record, and are kept whole by [`read_edf_annotations_from`].
+pub fn read_annotations_from(storage: &dyn Storage, path: &str) -> io::Result<Vec<Annotation>> {
+    let header = read_header_from(storage, path)?;
+    let sampling_frequency = header.sampling_frequency();
+    let annotations = read_edf_annotations_from(storage, path)?;
+    Ok(annotations
+        .iter()
+        .map(|annotation| annotation.to_annotation(sampling_frequency))
+        .collect())
+}
+
+/// Read the annotations of an EDF+ file, relative to the current working directory.
+pub fn read_annotations<P: AsRef<Path>>(path: P) -> io::Result<Vec<Annotation>> {
+    read_annotations_from(&FileStorage::new(""), &path.as_ref().to_string_lossy())
+}
+
+/// Largest size of a data record recommended by the EDF specification, in bytes.
+const MAX_RECORD_BYTES: usize = 61_440;
+
+const MONTHS: [&str; 12] = [
+    "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+];
+
+/// Pad or cut an ASCII header field to its width.
+fn pad_field(text: &str, len: usize) -> Vec<u8> {
+    let text: String = text
+        .chars()
+        .map(|c| {
+            if c.is_ascii() && !c.is_ascii_control() {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{:<width$.width$}", text, width = len).into_bytes()
+}
+
+/// Format a number in at most `len` characters, with as many decimals as fit. Fails for numbers
+/// whose integer part does not fit, which would otherwise be truncated in their field.
+fn format_number(value: f64, len: usize) -> io::Result<String> {
+    (0..=len)
+        .rev()
+        .map(|decimals| {
+            let text = format!("{:.*}", decimals, value);
+            // Trailing zeros after the decimal point carry no information
+            if text.contains('.') {
+                text.trim_end_matches('0').trim_end_matches('.').to_string()
+            } else {
+                text
+            }
+        })
+        .find(|text| text.len() <= len)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{} does not fit in an EDF field of {} characters",
+                    value, len
+                ),
+            )
+        })
+}
+
+/// The digital range of a signal in an EDF file: the range of its ADC, given by the ADC
+/// resolution and zero, limited to 16 bits.
+fn digital_range(spec: &SignalSpecLine) -> (i32, i32) {
+    let resolution = spec
+        .adc_resolution
+        .filter(|&res| res > 0)
+        .unwrap_or(12)
+        .min(16);
+    let zero = spec.adc_zero.unwrap_or(0);
+    let min = (zero - (1 << (resolution - 1))).max(i16::MIN as i32);
+    let max = (zero + (1 << (resolution - 1)) - 1).min(i16::MAX as i32);
+    (min, max.max(min + 1))
+}
+
+/// Time-stamped annotation lists for the annotations in each data record, each list starting
+/// with the time-keeping annotation giving the start time of the data record.
+fn annotation_lists(
+    annotations: &[Annotation],
+    sampling_frequency: f64,
+    num_records: usize,
+    record_duration: f64,
+) -> io::Result<Vec<Vec<u8>>> {
+    let mut lists: Vec<Vec<u8>> = (0..num_records.max(1))
+        .map(|record| {
+            let start = format_number(record as f64 * record_duration, 20)?;
+            Ok(format!("+{}\x14\x14\0", start).into_bytes())
+        })
+        .collect::<io::Result<_>>()?;
+    for annotation in annotations {
+        let onset = annotation.sample as f64 / sampling_frequency;
+        let record = ((onset / record_duration) as usize).min(lists.len() - 1);
+        let list = &mut lists[record];
+        list.extend(format!("+{}\x14", format_number(onset, 20)?).bytes());
+        let code = annotation.code.to_string();
+        list.extend(annotation.mnemonic().unwrap_or(&code).bytes());
+        list.push(0x14);
+        if let Some(aux) = annotation.aux.as_deref().filter(|aux| !aux.is_empty()) {
+            list.extend(aux.bytes().filter(|&b| b != 0 && b != 0x14 && b != 0x15));
+            list.push(0x14);
+        }
+        list.push(0);
+    }
+    Ok(lists)
+}
+
+/// Write a record as an EDF file, or as an EDF+ file holding `annotations` when they are given.
+///
+/// Each signal's digital minimum and maximum are the range of its ADC (given by its ADC resolution
+/// and zero, limited to 16 bits), and its physical minimum and maximum are the digital ones
+/// converted with the signal's gain and baseline. Data records last one second when the sampling
+/// frequency is a whole number of hertz (and one frame otherwise), each signal keeping its samples
+/// per frame. Samples outside the digital range, invalid ones included, are written as the
+/// digital minimum or maximum, and the last data record is padded with the digital minimum.
+/// Annotations are written as EDF+ time-stamped annotation lists, at the time of the frame their
+/// sample number counts, with each annotation's mnemonic and auxiliary information as its texts.
+/// Fails with `InvalidInput` for signals whose physical minimum or maximum does not fit in the 8
+/// characters of its field.
+pub fn write_edf<W: Write>(
+    record: &Record,
+    annotations: Option<&[Annotation]>,
+    writer: W,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(writer);
+    let specs: Vec<&SignalSpecLine> = record
+        .header
+        .signal_specs
+        .iter()
+        .filter(|spec| spec.format != StorageFormat::_null)
+        .collect();
+    let signals: Vec<&Vec<i32>> = record
+        .header
+        .signal_specs
+        .iter()
+        .zip(&record.signals)
+        .filter(|(spec, _)| spec.format != StorageFormat::_null)
+        .map(|(_, samples)| samples)
+        .collect();
+    let samples_per_frame: Vec<usize> = specs
+        .iter()
+        .map(|spec| spec.samples_per_frame.unwrap_or(1).max(1) as usize)
+        .collect();
+    let frame_bytes: usize = samples_per_frame.iter().sum::<usize>() * 2;
+    let num_frames = signals
+        .iter()
+        .zip(&samples_per_frame)
+        .map(|(samples, &spf)| samples.len().div_ceil(spf))
+        .max()
+        .unwrap_or(0);
+
+    let frame_frequency = record
+        .header
+        .record
+        .sampling_frequency
+        .unwrap_or(header::DEFREQ) as f64;
+    let mut frames_per_record = match frame_frequency.round() {
+        frames if frames >= 1.0 && (frame_frequency - frames).abs() < 1e-6 => frames as usize,
+        _ => 1,
+    };
+    while frames_per_record % 2 == 0 && frames_per_record * frame_bytes > MAX_RECORD_BYTES {
+        frames_per_record /= 2;
+    }
+    let record_duration = frames_per_record as f64 / frame_frequency;
+    let mut num_records = num_frames.div_ceil(frames_per_record);
+    if annotations.is_some() {
+        // An EDF+ file needs a data record to hold its annotations, even without samples
+        num_records = num_records.max(1);
+    }
+
+    let annotation_lists = annotations
+        .map(|annotations| {
+            annotation_lists(annotations, frame_frequency, num_records, record_duration)
+        })
+        .transpose()?;
+    let annotation_samples = annotation_lists
+        .as_ref()
+        .map(|lists| lists.iter().map(Vec::len).max().unwrap_or(0).div_ceil(2));
+    let num_signals = specs.len() + annotation_samples.map_or(0, |_| 1);
+
+    // Fixed part of the header
+    let base_time = record
+        .header
+        .record
+        .base_time
+        .as_deref()
+        .and_then(time::parse_time)
+        .unwrap_or(0.0);
+    let base_date = record
+        .header
+        .record
+        .base_date
+        .as_deref()
+        .and_then(time::parse_date);
+    let (hours, minutes, seconds) = (
+        (base_time / 3600.0) as u32 % 24,
+        (base_time / 60.0) as u32 % 60,
+        base_time as u32 % 60,
+    );
+    let (startdate, recording) = match base_date {
+        Some((day, month, year)) if (1..=12).contains(&month) && (1985..=2084).contains(&year) => (
+            format!("{:02}.{:02}.{:02}", day, month, year % 100),
+            format!(
+                "Startdate {:02}-{}-{} X X X",
+                day,
+                MONTHS[month as usize - 1],
+                year
+            ),
+        ),
+        _ => (String::from("01.01.85"), String::from("Startdate X X X X")),
+    };
+    writer.write_all(&pad_field("0", 8))?;
+    writer.write_all(&pad_field("X X X X", 80))?;
+    writer.write_all(&pad_field(&recording, 80))?;
+    writer.write_all(&pad_field(&startdate, 8))?;
+    writer.write_all(&pad_field(
+        &format!("{:02}.{:02}.{:02}", hours, minutes, seconds),
+        8,
+    ))?;
+    writer.write_all(&pad_field(&(256 * (num_signals + 1)).to_string(), 8))?;
+    writer.write_all(&pad_field(
+        if annotation_lists.is_some() {
+            "EDF+C"
+        } else {
+            ""
+        },
+        44,
+    ))?;
+    writer.write_all(&pad_field(&num_records.to_string(), 8))?;
+    writer.write_all(&pad_field(&format_number(record_duration, 8)?, 8))?;
+    writer.write_all(&pad_field(&num_signals.to_string(), 4))?;
+
+    // Signal headers, one field at a time
+    let ranges: Vec<(i32, i32)> = specs.iter().map(|spec| digital_range(spec)).collect();
+    let names = record.signal_names();
+    let names: Vec<&String> = record
+        .header
+        .signal_specs
+        .iter()
+        .zip(&names)
+        .filter(|(spec, _)| spec.format != StorageFormat::_null)
+        .map(|(_, name)| name)
+        .collect();
+    let physical = |spec: &SignalSpecLine, digital: i32| {
+        let gain = spec
+            .adc_gain
+            .filter(|&gain| gain != 0.0)
+            .unwrap_or(header::DEFGAIN) as f64;
+        format_number((digital - spec.baseline.unwrap_or(0)) as f64 / gain, 8)
+    };
+    let mut fields: Vec<Vec<String>> = vec![vec![]; SIGNAL_FIELDS.len()];
+    for (idx, spec) in specs.iter().enumerate() {
+        let (min, max) = ranges[idx];
+        let values = [
+            names[idx].clone(),
+            String::new(),
+            spec.units.clone().unwrap_or_else(|| String::from("mV")),
+            physical(spec, min)?,
+            physical(spec, max)?,
+            min.to_string(),
+            max.to_string(),
+            String::new(),
+            (samples_per_frame[idx] * frames_per_record).to_string(),
+            String::new(),
+        ];
+        for (field, value) in fields.iter_mut().zip(values) {
+            field.push(value);
+        }
+    }
+    if let Some(samples) = annotation_samples {
+        let values = [
+            ANNOTATIONS_LABEL,
+            "",
+            "",
+            "-1",
+            "1",
+            "-32768",
+            "32767",
+            "",
+            &samples.to_string(),
+            "",
+        ];
+        for (field, value) in fields.iter_mut().zip(values) {
+            field.push(String::from(value));
+        }
+    }
+    for (values, &len) in fields.iter().zip(&SIGNAL_FIELDS) {
+        for value in values {
+            writer.write_all(&pad_field(value, len))?;
+        }
+    }
+
+    // Data records
+    for data_record in 0..num_records {
+        for (idx, samples) in signals.iter().enumerate() {
+            let (min, max) = ranges[idx];
+            let samples_per_record = samples_per_frame[idx] * frames_per_record;
+            let start = data_record * samples_per_record;
+            for sample in start..start + samples_per_record {
+                let value = match samples.get(sample) {
+                    Some(&value) if signal::is_valid(value) => value.clamp(min, max),
+                    _ => min,
+                };
+                writer.write_all(&(value as i16).to_le_bytes())?;
+            }
+        }
+        if let (Some(lists), Some(samples)) = (&annotation_lists, annotation_samples) {
+            let mut bytes = lists[data_record].clone();
+            bytes.resize(samples * 2, 0);
+            writer.write_all(&bytes)?;
+        }
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    fn pad(text: &str, len: usize) -> Vec<u8> {
+        format!("{:<width$}", text, width = len).into_bytes()
+    }
+
+    /// An EDF+ file with 2 data records of 0.5 seconds: an EEG signal with 4 samples per record,
+    /// a respiration signal with 1, and an annotation signal.
+    fn edf_file() -> Vec<u8> {
+        let signals = [
+            [
+                "Fpz-Cz",
+                "AgAgCl electrode",
+                "uV",
+                "-100",
+                "100",
+                "-2048",
+                "2047",
+                "HP:0.5Hz",
+                "4",
+                "",
+            ],
+            ["Resp", "", "", "0", "10", "0", "1000", "", "1", ""],
+            [
+                ANNOTATIONS_LABEL,
+                "",
+                "",
+                "-1",
+                "1",
+                "-32768",
+                "32767",
+                "",
+                "16",
+                "",
+            ],
+        ];
+        let mut buf = vec![];
+        for (text, len) in [
+            ("0", 8),
+            ("X X X X", 80),
+            ("Startdate 02-MAR-2002 X X X", 80),
+            ("02.03.02", 8),
+            ("22.15.30", 8),
+            ("1024", 8),
+            ("EDF+C", 44),
+            ("2", 8),
+            ("0.5", 8),
+            ("3", 4),
+        ] {
+            buf.extend(pad(text, len));
+        }
+        for (field, &len) in SIGNAL_FIELDS.iter().enumerate() {
+            for signal in &signals {
+                buf.extend(pad(signal[field], len));
+            }
+        }
+        let tals: [&[u8]; 2] = [
+            b"+0\x14\x14\0+0.5\x150.5\x14Apnea\x14\0",
+            b"+0.5\x14\x14\0+1\x14Arousal\x14Snore\x14\0",
+        ];
+        for (record, tal) in tals.iter().enumerate() {
+            for sample in 0..4 {
+                buf.extend(((record * 4 + sample) as i16 * 100 - 300).to_le_bytes());
+            }
+            buf.extend((record as i16 * 500).to_le_bytes());
+            let mut annotations = tal.to_vec();
+            annotations.resize(32, 0);
+            buf.extend(annotations);
+        }
+        buf
+    }
+
+    fn storage() -> MemoryStorage {
+        let mut storage = MemoryStorage::new();
+        storage.insert("sleep/SC4001.edf", edf_file());
+        storage
+    }
+
+    #[test]
+    fn header() {
+        let header = read_header_from(&storage(), "sleep/SC4001.edf").unwrap();
+        assert_eq!(header.record.record_name, "SC4001");
+        assert_eq!(header.record.number_of_signals, 2);
+        assert_eq!(header.record.sampling_frequency, Some(2.0));
+        assert_eq!(header.record.samples_per_signal, Some(2));
+        assert_eq!(header.record.base_time.as_deref(), Some("22:15:30"));
+        assert_eq!(header.record.base_date.as_deref(), Some("02/03/2002"));
+        assert_eq!(header.sampling_frequency(), 2.0);
+        assert_eq!(header.signal_frequency(0), 8.0);
+        assert_eq!(header.signal_frequency(1), 2.0);
+
+        let eeg = &header.signal_specs[0];
+        assert_eq!(eeg.description.as_deref(), Some("Fpz-Cz"));
+        assert_eq!(eeg.units.as_deref(), Some("uV"));
+        assert_eq!(eeg.samples_per_frame, Some(4));
+        assert_eq!(eeg.filename, "SC4001.dat");
+        assert_eq!(eeg.byte_offset, None);
+        assert_eq!(eeg.adc_gain, Some(20.475));
+        assert_eq!(eeg.baseline, Some(-1));
+        assert!((eeg.to_physical(2047).unwrap() - 100.0).abs() < 0.05);
+        let resp = &header.signal_specs[1];
+        assert_eq!(resp.units, None);
+        assert_eq!(resp.adc_gain, Some(100.0));
+        assert_eq!(resp.to_physical(500), Some(5.0));
+    }
+
+    #[test]
+    fn signals() {
+        let record = Record::open_from(&storage(), "sleep/SC4001.edf").unwrap();
+        assert_eq!(
+            record.signals[0],
+            vec![-300, -200, -100, 0, 100, 200, 300, 400]
+        );
+        assert_eq!(record.signals[1], vec![0, 500]);
+        assert_eq!(record.header.signal_specs[0].initial_value, Some(-300));
+        assert_eq!(record.sampling_frequency(), 2.0);
+        assert_eq!(record.num_samples(), 2);
+        assert_eq!(record.duration(), 1.0);
+        assert_eq!(record.frame_sample(0, 1), 250);
+    }
+
+    #[test]
+    fn annotations() {
+        let edf_annotations = read_edf_annotations_from(&storage(), "sleep/SC4001.edf").unwrap();
+        assert_eq!(
+            edf_annotations,
+            vec![
+                EdfAnnotation {
+                    onset: 0.5,
+                    duration: Some(0.5),
+                    text: String::from("Apnea")
+                },
+                EdfAnnotation {
+                    onset: 1.0,
+                    duration: None,
+                    text: String::from("Arousal")
+                },
+                EdfAnnotation {
+                    onset: 1.0,
+                    duration: None,
+                    text: String::from("Snore")
+                },
+            ]
+        );
+        let annotations = read_annotations_from(&storage(), "sleep/SC4001.edf").unwrap();
+        // Sample numbers count data records of 0.5 seconds, as times do
+        assert_eq!(annotations[0].sample, 1);
+        assert_eq!(annotations[1].sample, 2);
+        let record = Record::open_from(&storage(), "sleep/SC4001.edf").unwrap();
+        for (annotation, edf_annotation) in annotations.iter().zip(&edf_annotations) {
+            assert_eq!(
+                record.time_of(annotation.sample as usize),
+                edf_annotation.onset
+            );
+        }
+        assert_eq!(annotations[1].mnemonic(), Some("\""));
+        assert_eq!(annotations[2].aux.as_deref(), Some("Snore"));
+    }
+
+    #[test]
+    fn truncated_and_invalid_files() {
+        let mut storage = MemoryStorage::new();
+        let mut truncated = edf_file();
+        truncated.truncate(1024 + 50);
+        storage.insert("short.edf", truncated);
+        storage.insert("header.edf", edf_file()[..300].to_vec());
+        storage.insert("bdf.edf", [&[0xFF][..], &edf_file()[1..]].concat());
+
+        let record = Record::open_from(&storage, "short.edf").unwrap();
+        assert_eq!(record.signals[0].len(), 4);
+        assert_eq!(record.header.record.samples_per_signal, Some(1));
+        assert_eq!(
+            Record::open_from(&storage, "header.edf")
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(
+            Record::open_from(&storage, "bdf.edf").unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    fn wfdb_record() -> Record {
+        let header = "rec 2 4 6 10:20:30 15/06/2010\nrec.dat 16 200(10) 11 1024 0 0 0 MLII\nrec.dat 16 10/mmHg 16 0 0 0 0 ABP\n";
+        let invalid = signal::INVALID_SAMPLE;
+        let samples = [
+            1024, 0, 1034, 1000, 2047, invalid, 0, 5000, invalid, 7, 3000, 8,
+        ];
+        Record::from_header_str(header, |_| Ok(signal::encode_16_format(&samples))).unwrap()
+    }
+
+    fn write(record: &Record, annotations: Option<&[Annotation]>) -> MemoryStorage {
+        let mut buf = vec![];
+        write_edf(record, annotations, &mut buf).unwrap();
+        let mut storage = MemoryStorage::new();
+        storage.insert("out.edf", buf);
+        storage
+    }
+
+    #[test]
+    fn convert_to_wfdb_signal_files() {
+        let record = Record::open_from(&storage(), "sleep/SC4001.edf").unwrap();
+        let mut storage = MemoryStorage::new();
+        record
+            .write_with(|filename, contents| {
+                storage.insert(filename, contents.to_vec());
+                Ok(())
+            })
+            .unwrap();
+        let converted = Record::open_from(&storage, "SC4001").unwrap();
+        assert_eq!(converted.signals, record.signals);
+        assert!(storage.read("SC4001.edf").is_err());
+    }
+
+    #[test]
+    fn write_edf_header() {
+        let storage = write(&wfdb_record(), None);
+        let buf = &storage.read("out.edf").unwrap();
+        assert_eq!(field(buf, 88, 80), "Startdate 15-JUN-2010 X X X");
+        assert_eq!(field(buf, 168, 8), "15.06.10");
+        assert_eq!(field(buf, 176, 8), "10.20.30");
+        assert_eq!(field(buf, 192, 44), "");
+        // 6 frames at 4 Hz make 2 data records of 1 second
+        assert_eq!(field(buf, 236, 8), "2");
+        assert_eq!(field(buf, 244, 8), "1");
+
+        let header = read_header_from(&storage, "out.edf").unwrap();
+        assert_eq!(header.record.base_time.as_deref(), Some("10:20:30"));
+        assert_eq!(header.record.base_date.as_deref(), Some("15/06/2010"));
+        let ecg = &header.signal_specs[0];
+        assert_eq!(ecg.description.as_deref(), Some("MLII"));
+        assert_eq!(ecg.samples_per_frame, Some(4));
+        // An 11-bit ADC with its zero at 1024 covers 0 to 2047
+        assert_eq!(
+            (ecg.adc_zero, ecg.adc_gain, ecg.baseline),
+            (Some(1024), Some(200.0), Some(10))
+        );
+        assert_eq!(header.signal_specs[1].units.as_deref(), Some("mmHg"));
+    }
+
+    #[test]
+    fn write_and_read_back_samples() {
+        let record = wfdb_record();
+        let read_back = Record::open_from(&write(&record, None), "out.edf").unwrap();
+        // Invalid samples become the digital minimum, and the last data record is padded
+        assert_eq!(
+            read_back.signals[0],
+            vec![1024, 1034, 2047, 0, 0, 2047, 0, 0]
+        );
+        assert_eq!(
+            read_back.signals[1],
+            vec![0, 1000, -32768, 5000, 7, 8, -32768, -32768]
+        );
+        assert_eq!(
+            read_back.physical_samples(0)[1],
+            record.physical_samples(0)[1]
+        );
+    }
+
+    #[test]
+    fn samples_per_frame_and_annotations() {
+        let edf = Record::open_from(&storage(), "sleep/SC4001.edf").unwrap();
+        let annotations = vec![
+            Annotation {
+                sample: 0,
+                code: 1,
+                subtype: 0,
+                chan: 0,
+                num: 0,
+                aux: None,
+            },
+            Annotation {
+                sample: 2,
+                code: 28,
+                subtype: 0,
+                chan: 0,
+                num: 0,
+                aux: Some(String::from("(AFIB")),
+            },
+        ];
+        let storage = write(&edf, Some(&annotations));
+        let read_back = Record::open_from(&storage, "out.edf").unwrap();
+        assert_eq!(read_back.signals, edf.signals);
+        // Data records of 0.5 seconds are merged into data records of 1 second
+        assert_eq!(read_back.header.record.sampling_frequency, Some(1.0));
+        assert_eq!(read_back.header.signal_specs[0].samples_per_frame, Some(8));
+        assert_eq!(read_back.header.signal_specs[1].samples_per_frame, Some(2));
+        assert_eq!(read_back.sampling_frequency(), 1.0);
+        assert_eq!(read_back.header.signal_frequency(0), 8.0);
+
+        assert_eq!(field(&storage.read("out.edf").unwrap(), 192, 44), "EDF+C");
+        assert_eq!(
+            read_edf_annotations_from(&storage, "out.edf").unwrap(),
+            vec![
+                // Samples 0 and 2 of a record of 2 frames per second
+                EdfAnnotation {
+                    onset: 0.0,
+                    duration: None,
+                    text: String::from("N")
+                },
+                EdfAnnotation {
+                    onset: 1.0,
+                    duration: None,
+                    text: String::from("+")
+                },
+                EdfAnnotation {
+                    onset: 1.0,
+                    duration: None,
+                    text: String::from("(AFIB")
+                },
+            ]
+        );
+        let annotations = read_annotations_from(&storage, "out.edf").unwrap();
+        // Read back at 1 frame per second
+        assert_eq!(
+            annotations.iter().map(|a| a.sample).collect::<Vec<_>>(),
+            vec![0, 1, 1]
+        );
+        assert_eq!(read_back.time_of(1), edf.time_of(2));
+    }
+
+    #[test]
+    fn numbers_fit_their_fields() {
+        assert_eq!(format_number(0.5, 8).unwrap(), "0.5");
+        assert_eq!(format_number(1.0 / 3.0, 8).unwrap(), "0.333333");
+        assert_eq!(format_number(-10.24, 8).unwrap(), "-10.24");
+        assert_eq!(format_number(-163.84, 8).unwrap(), "-163.84");
+        assert_eq!(format_number(12_345_678.9, 8).unwrap(), "12345679");
+        assert!(format_number(123_456_789.0, 8).is_err());
+        assert!(format_number(-12_345_678.0, 8).is_err());
+
+        // A gain this small puts the physical maximum of a 16-bit ADC at 3276700000
+        let mut record = wfdb_record();
+        record.header.signal_specs[1].adc_gain = Some(0.00001);
+        let error = write_edf(&record, None, &mut vec![]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+}
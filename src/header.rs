@@ -1,10 +1,17 @@
-use regex::Regex;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+#[cfg(feature = "std")]
+use std::io::{self, Read};
 
-const DEFREQ: f32 = 250_f32;
+/// Sampling frequency used when the record line does not give one, as in the WFDB library.
+pub const DEFREQ: f32 = 250_f32;
 
 #[allow(non_camel_case_types)]
 #[derive(Copy, Clone, PartialEq, Debug)]
 pub enum StorageFormat {
+    _null = 0,
     _8bit_first_difference = 8,
     _16bit_twos_complement = 16,
     _24bit_twos_complement_lsb = 24,
@@ -15,9 +22,57 @@ pub enum StorageFormat {
     _12bit_twos_complement = 212,
     _10bit_twos_complement_sets_of_11 = 310,
     _10bit_twos_complement_sets_of_4 = 311,
+    _8bit_flac = 508,
+    _16bit_flac = 516,
+    _24bit_flac = 524,
 }
 
-#[derive(PartialEq, Debug)]
+impl StorageFormat {
+    /// The storage format with the given numeric code, as written in signal specification lines.
+    pub fn from_code(code: u16) -> Option<StorageFormat> {
+        Some(match code {
+            0 => StorageFormat::_null,
+            8 => StorageFormat::_8bit_first_difference,
+            16 => StorageFormat::_16bit_twos_complement,
+            24 => StorageFormat::_24bit_twos_complement_lsb,
+            32 => StorageFormat::_32bit_twos_complement_lsb,
+            61 => StorageFormat::_16bit_twos_complement_msb,
+            80 => StorageFormat::_8bit_offset_binary,
+            160 => StorageFormat::_16bit_offset_binary,
+            212 => StorageFormat::_12bit_twos_complement,
+            310 => StorageFormat::_10bit_twos_complement_sets_of_11,
+            311 => StorageFormat::_10bit_twos_complement_sets_of_4,
+            508 => StorageFormat::_8bit_flac,
+            516 => StorageFormat::_16bit_flac,
+            524 => StorageFormat::_24bit_flac,
+            _ => return None,
+        })
+    }
+}
+
+/// Storage formats are serialized as their numeric codes, as in header files.
+#[cfg(feature = "serde")]
+impl serde::Serialize for StorageFormat {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u16(*self as u16)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for StorageFormat {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let code = u16::deserialize(deserializer)?;
+        StorageFormat::from_code(code).ok_or_else(|| {
+            serde::de::Error::invalid_value(
+                serde::de::Unexpected::Unsigned(code as u64),
+                &"a WFDB storage format code",
+            )
+        })
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RecordLine {
     pub record_name: String,
     pub number_of_segments: Option<u32>,
@@ -30,7 +85,8 @@ pub struct RecordLine {
     pub base_date: Option<String>,
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SignalSpecLine {
     pub filename: String,
     pub format: StorageFormat,
@@ -38,24 +94,92 @@ pub struct SignalSpecLine {
     pub skew: Option<u32>,
     pub byte_offset: Option<u32>,
     pub adc_gain: Option<f32>,
-    pub baseline: Option<u32>,
+    pub baseline: Option<i32>,
     pub units: Option<String>,
     pub adc_resolution: Option<u32>,
-    pub adc_zero: Option<u32>,
-    pub initial_value: Option<u32>,
+    pub adc_zero: Option<i32>,
+    pub initial_value: Option<i32>,
     pub checksum: Option<i16>,
     pub block_size: Option<u32>,
     pub description: Option<String>,
 }
 
-#[derive(PartialEq, Debug)]
+/// Gain used when a signal's ADC gain is missing or zero (uncalibrated), as in the WFDB library.
+pub const DEFGAIN: f32 = 200_f32;
+
+impl SignalSpecLine {
+    /// Number of samples of the signal in each frame of the record (1 when not given).
+    pub fn spf(&self) -> usize {
+        self.samples_per_frame.unwrap_or(1).max(1) as usize
+    }
+
+    /// Convert a sample in ADC units to physical units, using the signal's gain and baseline.
+    /// Returns `None` for invalid samples (see [`crate::signal::INVALID_SAMPLE`]).
+    pub fn to_physical(&self, sample: i32) -> Option<f64> {
+        if !crate::signal::is_valid(sample) {
+            return None;
+        }
+        let gain = match self.adc_gain {
+            Some(gain) if gain != 0.0 => gain,
+            _ => DEFGAIN,
+        };
+        let baseline = self.baseline.unwrap_or(0) as f64;
+        Some((sample as f64 - baseline) / gain as f64)
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Header {
     pub record: RecordLine,
     pub signal_specs: Vec<SignalSpecLine>,
 }
 
-pub fn parse_record_line(record_line: &str) -> RecordLine {
+impl Header {
+    /// Sampling frequency of the record, in frames per second, as given in the record line (or
+    /// [`DEFREQ`]). Sample numbers and record lengths are counted in frames.
+    pub fn sampling_frequency(&self) -> f64 {
+        self.record.sampling_frequency.unwrap_or(DEFREQ) as f64
+    }
+
+    /// Sampling frequency of a signal, in samples per second: the frame frequency times the
+    /// signal's number of samples per frame.
+    pub fn signal_frequency(&self, signal: usize) -> f64 {
+        self.sampling_frequency() * self.signal_specs[signal].spf() as f64
+    }
+}
+
+/// Error returned when a header, or one of its lines, cannot be parsed, or when it describes
+/// signals that cannot be decoded.
+#[derive(Clone, PartialEq, Debug)]
+pub struct HeaderError(pub(crate) &'static str);
+
+impl fmt::Display for HeaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid header: {}", self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for HeaderError {}
+
+#[cfg(feature = "std")]
+impl From<HeaderError> for io::Error {
+    fn from(error: HeaderError) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, error)
+    }
+}
+
+/// Parse a header field, failing with `message` if it is malformed.
+fn parse_field<T: FromStr>(field: &str, message: &'static str) -> Result<T, HeaderError> {
+    field.parse::<T>().map_err(|_| HeaderError(message))
+}
+
+pub fn parse_record_line(record_line: &str) -> Result<RecordLine, HeaderError> {
     let tokens: Vec<&str> = record_line.split_whitespace().collect();
+    if tokens.len() < 2 {
+        return Err(HeaderError("record line missing required fields."));
+    }
     let record_name;
     let mut number_of_segments = None;
 
@@ -67,9 +191,7 @@ pub fn parse_record_line(record_line: &str) -> RecordLine {
         record_name = String::from(record_name_tokens[0]);
     }
 
-    let number_of_signals = tokens[1]
-        .parse::<u32>()
-        .expect("Invalid header: cannot parse number of signals.");
+    let number_of_signals = parse_field(tokens[1], "cannot parse number of signals.")?;
 
     let mut sampling_frequency = Some(DEFREQ);
     let mut counter_frequency = None;
@@ -77,115 +199,100 @@ pub fn parse_record_line(record_line: &str) -> RecordLine {
     if tokens.len() > 2 {
         let frequency_tokens: Vec<&str> = tokens[2].split('/').collect();
         if frequency_tokens.len() > 1 {
-            let counter_regex = Regex::new(r"(\d+)\((\d+)\)").unwrap();
-            if counter_regex.is_match(frequency_tokens[1]) {
-                let captures = counter_regex.captures(frequency_tokens[1]).unwrap();
-                counter_frequency = Some(
-                    captures[1]
-                        .parse::<f32>()
-                        .expect("Invalid header: counter frequency specified, but not parseable."),
-                );
-                base_counter_value = Some(
-                    captures[2]
-                        .parse::<f32>()
-                        .expect("Invalid header: base counter value specified, but not parseable"),
-                );
+            let counter = frequency_tokens[1]
+                .split_once('(')
+                .and_then(|(freq, base)| Some((freq, base.strip_suffix(')')?)));
+            if let Some((freq, base)) = counter {
+                counter_frequency = Some(parse_field(
+                    freq,
+                    "counter frequency specified, but not parseable.",
+                )?);
+                base_counter_value = Some(parse_field(
+                    base,
+                    "base counter value specified, but not parseable",
+                )?);
             } else {
-                counter_frequency = Some(
-                    frequency_tokens[0]
-                        .parse::<f32>()
-                        .expect("Invalid header: counter frequency specified, but not parseable."),
-                );
+                counter_frequency = Some(parse_field(
+                    frequency_tokens[0],
+                    "counter frequency specified, but not parseable.",
+                )?);
             }
         }
-        sampling_frequency = Some(
-            frequency_tokens[0]
-                .parse::<f32>()
-                .expect("Invalid header: Sampling frequency field present, but not parseable"),
-        );
+        sampling_frequency = Some(parse_field(
+            frequency_tokens[0],
+            "sampling frequency field present, but not parseable",
+        )?);
     }
 
     let mut samples_per_signal = None;
     if tokens.len() > 3 {
-        samples_per_signal = Some(
-            tokens[3]
-                .parse::<u32>()
-                .expect("Invalid header: samples per signal specified, but not parseable"),
-        );
+        samples_per_signal = Some(parse_field(
+            tokens[3],
+            "samples per signal specified, but not parseable",
+        )?);
     }
 
-    // TODO: parse date and time
+    let base_time = tokens.get(4).map(|&time| String::from(time));
+    let base_date = tokens.get(5).map(|&date| String::from(date));
 
-    RecordLine {
+    Ok(RecordLine {
         record_name,
         number_of_segments,
         number_of_signals,
         sampling_frequency,
-        counter_frequency: if counter_frequency == None {
+        counter_frequency: if counter_frequency.is_none() {
             sampling_frequency
         } else {
             counter_frequency
         },
-        base_counter_value: if base_counter_value == None {
+        base_counter_value: if base_counter_value.is_none() {
             Some(0_f32)
         } else {
             base_counter_value
         },
         samples_per_signal,
-        base_time: None,
-        base_date: None,
-    }
+        base_time,
+        base_date,
+    })
 }
 
-pub fn parse_signal_line(signal_line: &str) -> SignalSpecLine {
+pub fn parse_signal_line(signal_line: &str) -> Result<SignalSpecLine, HeaderError> {
     let tokens: Vec<&str> = signal_line.split_whitespace().collect();
     if tokens.len() < 2 {
-        panic!("Invalid header: signal specification line missing required fields.");
+        return Err(HeaderError(
+            "signal specification line missing required fields.",
+        ));
     }
 
     let filename = String::from(tokens[0]);
-    let format;
     let mut samples_per_frame = None;
     let mut skew = None;
     let mut byte_offset = None;
-    let format_regex = Regex::new(r"(\d+)(?:x(\d+)(?::(\d+)(?:\+(\d+)))?)?").unwrap();
-    if format_regex.is_match(tokens[1]) {
-        let format_captures = format_regex.captures(tokens[1]).unwrap();
-        format = match &format_captures[1] {
-            "8" => StorageFormat::_8bit_first_difference,
-            "16" => StorageFormat::_16bit_twos_complement,
-            "24" => StorageFormat::_24bit_twos_complement_lsb,
-            "32" => StorageFormat::_32bit_twos_complement_lsb,
-            "61" => StorageFormat::_16bit_twos_complement_msb,
-            "80" => StorageFormat::_8bit_offset_binary,
-            "160" => StorageFormat::_16bit_offset_binary,
-            "212" => StorageFormat::_12bit_twos_complement,
-            "310" => StorageFormat::_10bit_twos_complement_sets_of_11,
-            "311" => StorageFormat::_10bit_twos_complement_sets_of_4,
-            _ => panic!("Unknown storage format!"),
-        };
-        if format_captures.get(2) != None {
-            samples_per_frame =
-                Some(format_captures[2].parse::<u32>().expect(
-                    "Invalid header: samples per frame specified, but could not be parsed",
-                ));
-        }
-        if format_captures.get(3) != None {
-            skew = Some(
-                format_captures[3]
-                    .parse::<u32>()
-                    .expect("Invalid header: skew specified, but could not be parsed"),
-            );
-        }
-        if format_captures.get(4) != None {
-            byte_offset = Some(
-                format_captures[4]
-                    .parse::<u32>()
-                    .expect("Invalid header: byte offset specified, but could not be parsed"),
-            );
-        }
-    } else {
-        panic!("Invalid header: signal format not properly specified.");
+    let (format_code, mut format_suffix) = split_digits(tokens[1]);
+    let format_code = parse_field(format_code, "signal format not properly specified.")?;
+    let format =
+        StorageFormat::from_code(format_code).ok_or(HeaderError("unknown storage format."))?;
+    if let Some(rest) = format_suffix.strip_prefix('x') {
+        let (value, rest) = split_digits(rest);
+        samples_per_frame = Some(parse_field(
+            value,
+            "samples per frame specified, but could not be parsed",
+        )?);
+        format_suffix = rest;
+    }
+    if let Some(rest) = format_suffix.strip_prefix(':') {
+        let (value, rest) = split_digits(rest);
+        skew = Some(parse_field(
+            value,
+            "skew specified, but could not be parsed",
+        )?);
+        format_suffix = rest;
+    }
+    if let Some(rest) = format_suffix.strip_prefix('+') {
+        byte_offset = Some(parse_field(
+            split_digits(rest).0,
+            "byte offset specified, but could not be parsed",
+        )?);
     }
 
     let mut adc_gain = None;
@@ -200,84 +307,78 @@ pub fn parse_signal_line(signal_line: &str) -> SignalSpecLine {
     let mut block_size = None;
     let mut description = None;
     if tokens.len() > 2 {
-        let adc_regex = Regex::new(r"(\d+)(?:\((?P<baseline>\d+)\))?(?:/(?P<units>\S+))?").unwrap();
-        let adc_tokens = adc_regex.captures(tokens[2]).unwrap();
-        adc_gain = Some(
-            adc_tokens[1]
-                .parse::<f32>()
-                .expect("Invalid header: adc gain specified, but not parseable"),
-        );
-        if adc_tokens.name("baseline") != None {
-            baseline = Some(
-                adc_tokens
-                    .name("baseline")
-                    .unwrap()
-                    .as_str()
-                    .parse::<u32>()
-                    .expect("Invalid header: baseline specified, but could not be parsed"),
-            );
-        }
-        if adc_tokens.name("units") != None {
-            units = Some(String::from(adc_tokens.name("units").unwrap().as_str()));
+        // The ADC gain field is `gain[(baseline)][/units]`
+        let (gain_and_baseline, adc_units) = match tokens[2].split_once('/') {
+            Some((gain_and_baseline, adc_units)) => (gain_and_baseline, Some(adc_units)),
+            None => (tokens[2], None),
+        };
+        let gain = match gain_and_baseline.split_once('(') {
+            Some((gain, adc_baseline)) => {
+                baseline = Some(parse_field(
+                    adc_baseline.trim_end_matches(')'),
+                    "baseline specified, but could not be parsed",
+                )?);
+                gain
+            }
+            None => gain_and_baseline,
+        };
+        adc_gain = Some(parse_field(gain, "adc gain specified, but not parseable")?);
+        if let Some(adc_units) = adc_units.filter(|units| !units.is_empty()) {
+            units = Some(String::from(adc_units));
         }
 
         if tokens.len() > 3 {
-            adc_resolution = Some(
-                tokens[3]
-                    .parse::<u32>()
-                    .expect("Invalid header: ADC resolution specified but not parseable."),
-            );
+            adc_resolution = Some(parse_field(
+                tokens[3],
+                "ADC resolution specified but not parseable.",
+            )?);
         }
 
         if tokens.len() > 4 {
-            adc_zero = Some(
-                tokens[4]
-                    .parse::<u32>()
-                    .expect("Invalid header: ADC zero specified but not parseable."),
-            );
+            adc_zero = Some(parse_field(
+                tokens[4],
+                "ADC zero specified but not parseable.",
+            )?);
         }
 
         if tokens.len() > 5 {
-            initial_value = Some(
-                tokens[5]
-                    .parse::<u32>()
-                    .expect("Invalid header: initial value specified but not parseable."),
-            );
+            initial_value = Some(parse_field(
+                tokens[5],
+                "initial value specified but not parseable.",
+            )?);
         }
 
         if tokens.len() > 6 {
-            checksum = Some(
-                tokens[6]
-                    .parse::<i16>()
-                    .expect("Invalid header: checksum specified but not parseable."),
-            );
+            checksum = Some(parse_field(
+                tokens[6],
+                "checksum specified but not parseable.",
+            )?);
         }
 
         if tokens.len() > 7 {
-            block_size = Some(
-                tokens[7]
-                    .parse::<u32>()
-                    .expect("Invalid header: block size specified but not parseable."),
-            );
+            block_size = Some(parse_field(
+                tokens[7],
+                "block size specified but not parseable.",
+            )?);
         }
 
         if tokens.len() > 8 {
-            description = Some(String::from(tokens[8]));
+            description = Some(tokens[8..].join(" "));
         }
     }
 
-    SignalSpecLine {
+    Ok(SignalSpecLine {
         filename,
         format,
         samples_per_frame,
         skew,
         byte_offset,
         adc_gain,
-        baseline: if baseline == None { adc_zero } else { baseline },
+        baseline: if baseline.is_none() { adc_zero } else { baseline },
         units,
         adc_resolution,
         adc_zero,
-        initial_value: if initial_value == None {
+        initial_value: if initial_value.is_none() {
             adc_zero
         } else {
             initial_value
@@ -285,14 +386,132 @@ pub fn parse_signal_line(signal_line: &str) -> SignalSpecLine {
         checksum,
         block_size,
         description,
-    }
+    })
+}
+
+/// Split a string into its leading ASCII digits and the rest.
+fn split_digits(s: &str) -> (&str, &str) {
+    let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    s.split_at(end)
 }
 
-pub fn read_header(header_string: &str) -> Header {
+pub fn read_header(header_string: &str) -> Result<Header, HeaderError> {
     let mut header_lines = header_string.lines().filter(|&line| !line.starts_with("#"));
-    Header {
-        record: parse_record_line(header_lines.next().unwrap()),
-        signal_specs: header_lines.map(parse_signal_line).collect(),
+    let record_line = header_lines
+        .next()
+        .ok_or(HeaderError("record line missing."))?;
+    Ok(Header {
+        record: parse_record_line(record_line)?,
+        signal_specs: header_lines
+            .map(parse_signal_line)
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+/// Read and parse a header from any `Read` source, such as an open file or an in-memory buffer.
+#[cfg(feature = "std")]
+pub fn read_header_from<R: Read>(mut reader: R) -> io::Result<Header> {
+    let mut header_string = String::new();
+    reader.read_to_string(&mut header_string)?;
+    Ok(read_header(&header_string)?)
+}
+
+impl fmt::Display for RecordLine {
+    /// Write the record line as it appears in a header file.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.record_name)?;
+        if let Some(segments) = self.number_of_segments {
+            write!(f, "/{}", segments)?;
+        }
+        write!(f, " {}", self.number_of_signals)?;
+        let sampling_frequency = match self.sampling_frequency {
+            Some(frequency) => frequency,
+            None => return Ok(()),
+        };
+        write!(f, " {}", sampling_frequency)?;
+        if let Some(counter_frequency) = self
+            .counter_frequency
+            .filter(|&freq| freq != sampling_frequency)
+        {
+            write!(f, "/{}", counter_frequency)?;
+            if let Some(base) = self.base_counter_value.filter(|&base| base != 0.0) {
+                write!(f, "({})", base)?;
+            }
+        }
+        if let Some(samples) = self.samples_per_signal {
+            write!(f, " {}", samples)?;
+            if let Some(time) = &self.base_time {
+                write!(f, " {}", time)?;
+                if let Some(date) = &self.base_date {
+                    write!(f, " {}", date)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for SignalSpecLine {
+    /// Write the signal specification line as it appears in a header file. Fields are written up
+    /// to the last one present, with defaults filled in for any missing fields before it.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.filename, self.format as u32)?;
+        if let Some(samples_per_frame) = self.samples_per_frame.filter(|&spf| spf > 1) {
+            write!(f, "x{}", samples_per_frame)?;
+        }
+        if let Some(skew) = self.skew.filter(|&skew| skew > 0) {
+            write!(f, ":{}", skew)?;
+        }
+        if let Some(byte_offset) = self.byte_offset.filter(|&offset| offset > 0) {
+            write!(f, "+{}", byte_offset)?;
+        }
+
+        let fields = [
+            self.adc_resolution.map(|res| res.to_string()),
+            self.adc_zero.map(|zero| zero.to_string()),
+            self.initial_value.map(|value| value.to_string()),
+            self.checksum.map(|checksum| checksum.to_string()),
+            self.block_size.map(|size| size.to_string()),
+            self.description.clone(),
+        ];
+        let defaults = ["12", "0", "0", "0", "0"];
+        let last_field = fields.iter().rposition(Option::is_some);
+        if self.adc_gain.is_none() && self.baseline.is_none() && last_field.is_none() {
+            return Ok(());
+        }
+
+        write!(f, " {}", self.adc_gain.unwrap_or(0.0))?;
+        if let Some(baseline) = self
+            .baseline
+            .filter(|&baseline| Some(baseline) != self.adc_zero.or(Some(0)))
+        {
+            write!(f, "({})", baseline)?;
+        }
+        if let Some(units) = self.units.as_deref().filter(|&units| units != "mV") {
+            write!(f, "/{}", units)?;
+        }
+        for (idx, field) in fields
+            .iter()
+            .enumerate()
+            .take(last_field.map_or(0, |last| last + 1))
+        {
+            match field {
+                Some(field) => write!(f, " {}", field)?,
+                None => write!(f, " {}", defaults.get(idx).unwrap_or(&""))?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Header {
+    /// Write the header in the WFDB header file format, as read by [`read_header`].
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.record)?;
+        for signal in &self.signal_specs {
+            writeln!(f, "{}", signal)?;
+        }
+        Ok(())
     }
 }
 
@@ -304,7 +523,7 @@ mod tests {
         let basic_header = "100 2 360 650000
             100.dat 212 200 11 1024 995 -22131 0 MLII
             100.dat 212 200 11 1024 1011 20052 0 V5";
-        let parsed = read_header(basic_header);
+        let parsed = read_header(basic_header).unwrap();
         assert_eq!(
             parsed,
             Header {
@@ -360,7 +579,7 @@ mod tests {
     #[test]
     fn test_mit_record_line() {
         let basic_record_line = "100 2 360 650000 0:0:0 0/0/0";
-        let parsed = parse_record_line(basic_record_line);
+        let parsed = parse_record_line(basic_record_line).unwrap();
         assert_eq!(
             parsed,
             RecordLine {
@@ -371,8 +590,8 @@ mod tests {
                 counter_frequency: Some(360_f32),
                 base_counter_value: Some(0.0),
                 samples_per_signal: Some(650000),
-                base_time: None,
-                base_date: None,
+                base_time: Some(String::from("0:0:0")),
+                base_date: Some(String::from("0/0/0")),
             }
         )
     }
@@ -380,7 +599,7 @@ mod tests {
     #[test]
     fn test_custom_mit_record_line() {
         let basic_record_line = "100/4 2 360/24(5) 650000 0:0:0 0/0/0";
-        let parsed = parse_record_line(basic_record_line);
+        let parsed = parse_record_line(basic_record_line).unwrap();
         assert_eq!(
             parsed,
             RecordLine {
@@ -391,8 +610,8 @@ mod tests {
                 counter_frequency: Some(24.0),
                 base_counter_value: Some(5.0),
                 samples_per_signal: Some(650000),
-                base_time: None,
-                base_date: None,
+                base_time: Some(String::from("0:0:0")),
+                base_date: Some(String::from("0/0/0")),
             }
         )
     }
@@ -400,7 +619,7 @@ mod tests {
     #[test]
     fn test_aha_record_line() {
         let basic_record_line = "7001 2 250 525000";
-        let parsed = parse_record_line(basic_record_line);
+        let parsed = parse_record_line(basic_record_line).unwrap();
         assert_eq!(
             parsed,
             RecordLine {
@@ -421,7 +640,7 @@ mod tests {
     fn test_mit_signal_spec_line() {
         let signal_line = "100.dat 212 200 11 1024 995 -22131 0 MLII";
         assert_eq!(
-            parse_signal_line(signal_line),
+            parse_signal_line(signal_line).unwrap(),
             SignalSpecLine {
                 filename: String::from("100.dat"),
                 format: StorageFormat::_12bit_twos_complement,
@@ -441,11 +660,65 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_signal_spec_line_byte_offset() {
+        let parsed = parse_signal_line("100.dat 212+512 200 11 1024 995 -22131 0 MLII").unwrap();
+        assert_eq!(
+            (parsed.samples_per_frame, parsed.skew, parsed.byte_offset),
+            (None, None, Some(512))
+        );
+        let parsed = parse_signal_line("100.dat 16:3 200 11 1024 995 -22131 0 MLII").unwrap();
+        assert_eq!(
+            (parsed.samples_per_frame, parsed.skew, parsed.byte_offset),
+            (None, Some(3), None)
+        );
+    }
+
+    #[test]
+    fn test_negative_signal_spec_fields() {
+        let parsed = parse_signal_line("e0103.dat 8 200(-12)/mV 12 -8 -53 -3191 0 MLIII").unwrap();
+        assert_eq!(parsed.format, StorageFormat::_8bit_first_difference);
+        assert_eq!(parsed.baseline, Some(-12));
+        assert_eq!(parsed.adc_zero, Some(-8));
+        assert_eq!(parsed.initial_value, Some(-53));
+    }
+
+    #[test]
+    fn test_to_physical() {
+        let signal = parse_signal_line("100.dat 212 200 11 1024 995 -22131 0 MLII").unwrap();
+        assert_eq!(signal.to_physical(1124), Some(0.5));
+        assert_eq!(signal.to_physical(crate::signal::INVALID_SAMPLE), None);
+        let uncalibrated = parse_signal_line("100.dat 212 0(0) 11 0 0 0 0 MLII").unwrap();
+        assert_eq!(uncalibrated.to_physical(100), Some(0.5));
+    }
+
+    #[test]
+    fn test_null_signal_spec_line() {
+        let parsed = parse_signal_line("~ 0 200 11 1024 0 0 0 ABP").unwrap();
+        assert_eq!(parsed.filename, "~");
+        assert_eq!(parsed.format, StorageFormat::_null);
+    }
+
+    #[test]
+    fn test_flac_signal_spec_line() {
+        let signal_line = "s0010_re.dat 516 2000 16 0 489 -8337 0 i";
+        let parsed = parse_signal_line(signal_line).unwrap();
+        assert_eq!(parsed.format, StorageFormat::_16bit_flac);
+        assert_eq!(
+            parse_signal_line("a.dat 508").unwrap().format,
+            StorageFormat::_8bit_flac
+        );
+        assert_eq!(
+            parse_signal_line("a.dat 524").unwrap().format,
+            StorageFormat::_24bit_flac
+        );
+    }
+
     #[test]
     fn test_custom_mit_signal_spec_line() {
         let signal_line = "100.dat 212x3:2+53 200(2)/cm 11 1024 995 -22131 0 MLII";
         assert_eq!(
-            parse_signal_line(signal_line),
+            parse_signal_line(signal_line).unwrap(),
             SignalSpecLine {
                 filename: String::from("100.dat"),
                 format: StorageFormat::_12bit_twos_complement,
@@ -464,4 +737,89 @@ mod tests {
             }
         )
     }
+
+    #[test]
+    fn frame_and_signal_frequencies() {
+        let header =
+            read_header("mf 2 125 10\nmf.dat 16x4 200 16 0 0 0 0 ECG\nmf.dat 16 200 16 0 0 0 0 BP")
+                .unwrap();
+        assert_eq!(header.sampling_frequency(), 125.0);
+        assert_eq!(header.signal_frequency(0), 500.0);
+        assert_eq!(header.signal_frequency(1), 125.0);
+        assert_eq!(header.signal_specs[0].spf(), 4);
+        assert_eq!(
+            read_header("rec 1").unwrap().sampling_frequency(),
+            DEFREQ as f64
+        );
+    }
+
+    #[test]
+    fn fractional_gain_and_long_description() {
+        let spec = parse_signal_line("rec.dat 16 0.5(-3)/uV 16 0 0 0 0 ECG lead II").unwrap();
+        assert_eq!(spec.adc_gain, Some(0.5));
+        assert_eq!(spec.baseline, Some(-3));
+        assert_eq!(spec.units, Some(String::from("uV")));
+        assert_eq!(spec.description, Some(String::from("ECG lead II")));
+    }
+
+    #[test]
+    fn malformed_headers() {
+        assert_eq!(read_header(""), Err(HeaderError("record line missing.")));
+        assert!(parse_record_line("100").is_err());
+        assert!(parse_record_line("100 two 360").is_err());
+        assert!(parse_record_line("100 2 fast").is_err());
+        assert!(parse_signal_line("100.dat").is_err());
+        assert!(parse_signal_line("100.dat 17").is_err());
+        assert!(parse_signal_line("100.dat x16").is_err());
+        assert!(parse_signal_line("100.dat 16 200(a)").is_err());
+        assert!(read_header("100 1 360\n100.dat 212 200 eleven").is_err());
+        assert_eq!(
+            parse_signal_line("100.dat 16 200 11 1024 995 -99999")
+                .unwrap_err()
+                .to_string(),
+            "Invalid header: checksum specified but not parseable."
+        );
+    }
+
+    #[test]
+    fn write_header() {
+        let text = "100/2 2 360/24(5) 650000 10:30:00 01/02/2003
+100.dat 212 200 11 1024 995 -22131 0 MLII
+100.dat 212x2:3+512 0.5(-10)/uV 16 0 -3 12 0 ECG lead II
+";
+        let header = read_header(text).unwrap();
+        assert_eq!(header.to_string(), text);
+        assert_eq!(read_header(&header.to_string()).unwrap(), header);
+
+        let sparse = read_header("rec 1\nrec.dat 16\n").unwrap();
+        assert_eq!(sparse.to_string(), "rec 1 250\nrec.dat 16 0 12 0 0\n");
+        assert_eq!(
+            read_header("rec 1 360\nrec.dat 16 100\n")
+                .unwrap()
+                .to_string(),
+            "rec 1 360\nrec.dat 16 100 12 0 0\n"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_round_trip() {
+        let header =
+            read_header("100 1 360/24(5) 650000 10:30:00\n100.dat 212x2 200(10)/uV 11 1024")
+                .unwrap();
+        let json = serde_json::to_value(&header).unwrap();
+        assert_eq!(json["record"]["record_name"], "100");
+        assert_eq!(json["record"]["base_counter_value"], 5.0);
+        assert_eq!(json["record"]["base_date"], serde_json::Value::Null);
+        assert_eq!(json["signal_specs"][0]["format"], 212);
+        assert_eq!(json["signal_specs"][0]["samples_per_frame"], 2);
+        assert_eq!(json["signal_specs"][0]["units"], "uV");
+        assert_eq!(serde_json::from_value::<Header>(json).unwrap(), header);
+
+        let spec: SignalSpecLine =
+            serde_json::from_str(r#"{"filename": "rec.dat", "format": 16}"#).unwrap();
+        assert_eq!(spec.format, StorageFormat::_16bit_twos_complement);
+        assert_eq!(spec.adc_gain, None);
+        assert!(serde_json::from_str::<StorageFormat>("17").is_err());
+    }
 }
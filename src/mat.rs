@@ -0,0 +1,295 @@
+//! Exporting records as MATLAB Level 5 MAT-files, as `wfdb2mat` does.
+//!
+//! A record `100` is written as `100m.mat`, holding a single matrix `val` with one row per
+//! signal and one column per sample in ADC units, and `100m.hea`, a header whose signal lines
+//! point into the `.mat` file. MATLAB stores matrices column by column, so the samples of each
+//! frame lie next to each other just as in a format 16 or 32 signal file. The header's byte
+//! offset skips the MAT-file prolog, which makes `100m` a WFDB record in its own right.
+use std::convert::TryFrom;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+
+use crate::header::{Header, RecordLine, SignalSpecLine, StorageFormat};
+use crate::record::Record;
+use crate::signal;
+use crate::time;
+
+const MI_INT8: u32 = 1;
+const MI_INT16: u32 = 3;
+const MI_INT32: u32 = 5;
+const MI_UINT32: u32 = 6;
+const MI_MATRIX: u32 = 14;
+const MX_INT16_CLASS: u32 = 10;
+const MX_INT32_CLASS: u32 = 12;
+
+/// Size of the 128-byte file header and the `val` matrix's tags, ahead of the samples.
+const PROLOG_LEN: usize = 128 + 8 + 16 + 16 + 8 + 8;
+
+/// Name of the record written for `record_name` by [`write_mat_in`]: `100` becomes `100m`.
+pub fn mat_record_name(record_name: &str) -> String {
+    format!("{}m", record_name)
+}
+
+/// Write a record (or a time window of it) as a `.mat` file and its companion header into
+/// `directory`, as described by [`write_mat_with`].
+pub fn write_mat_in(
+    record: &Record,
+    window: Option<Range<f64>>,
+    directory: &Path,
+) -> io::Result<()> {
+    write_mat_with(record, window, |filename, contents| {
+        fs::write(directory.join(filename), contents)
+    })
+}
+
+/// Encode a record (or a time window of it) as a MAT-file and a header for it, handing each
+/// file's name and contents to `save_file`.
+///
+/// Samples are stored as 16-bit integers (format 16), or as 32-bit integers (format 32) when a
+/// signal's ADC resolution or samples do not fit in 16 bits. Null signals (format 0) are left
+/// out. When the window starts after the beginning of the record, the header's base time and
+/// date are moved to the start of the window.
+pub fn write_mat_with<F>(
+    record: &Record,
+    window: Option<Range<f64>>,
+    mut save_file: F,
+) -> io::Result<()>
+where
+    F: FnMut(&str, &[u8]) -> io::Result<()>,
+{
+    let record_name = mat_record_name(&record.header.record.record_name);
+    let mat_name = format!("{}.mat", record_name);
+    let signals: Vec<usize> = (0..record.signals.len())
+        .filter(|&idx| record.header.signal_specs[idx].format != StorageFormat::_null)
+        .collect();
+
+    let num_samples = record.num_samples();
+    let rows = match &window {
+        Some(window) => {
+            let start = record.sample_at(window.start).min(num_samples);
+            start..record.sample_at(window.end).clamp(start, num_samples)
+        }
+        None => 0..num_samples,
+    };
+    let columns: Vec<Vec<i32>> = signals
+        .iter()
+        .map(|&idx| {
+            rows.clone()
+                .map(|row| record.frame_sample(idx, row))
+                .collect()
+        })
+        .collect();
+
+    let wide = signals.iter().zip(&columns).any(|(&idx, samples)| {
+        record.header.signal_specs[idx].adc_resolution.unwrap_or(12) > 16
+            || samples
+                .iter()
+                .any(|&sample| signal::is_valid(sample) && i16::try_from(sample).is_err())
+    });
+    let (format, data_type, class) = if wide {
+        (
+            StorageFormat::_32bit_twos_complement_lsb,
+            MI_INT32,
+            MX_INT32_CLASS,
+        )
+    } else {
+        (
+            StorageFormat::_16bit_twos_complement,
+            MI_INT16,
+            MX_INT16_CLASS,
+        )
+    };
+
+    let interleaved: Vec<i32> = (0..rows.len())
+        .flat_map(|row| columns.iter().map(move |samples| samples[row]))
+        .collect();
+    let data = match format {
+        StorageFormat::_16bit_twos_complement => signal::encode_16_format(&interleaved),
+        _ => signal::encode_32_format(&interleaved),
+    };
+    let matrix_len = PROLOG_LEN - 128 - 8 + data.len().div_ceil(8) * 8;
+    let too_large = || {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Record is too large for a Level 5 MAT-file",
+        )
+    };
+    let matrix_len = u32::try_from(matrix_len).map_err(|_| too_large())?;
+    let num_columns = i32::try_from(rows.len()).map_err(|_| too_large())?;
+
+    let mut mat = Vec::with_capacity(PROLOG_LEN + data.len() + 8);
+    let mut text = format!(
+        "MATLAB 5.0 MAT-file, written by wfdb-rust from record {}",
+        record.header.record.record_name
+    )
+    .into_bytes();
+    text.resize(116, b' ');
+    mat.extend(&text);
+    mat.extend([0; 8]);
+    mat.extend(0x0100_u16.to_le_bytes());
+    mat.extend(b"IM");
+
+    // Every element of the matrix starts with a pair of words (a tag, flags or dimensions)
+    let mut words = |first: u32, second: u32| {
+        mat.extend(first.to_le_bytes());
+        mat.extend(second.to_le_bytes());
+    };
+    words(MI_MATRIX, matrix_len);
+    words(MI_UINT32, 8);
+    words(class, 0);
+    words(MI_INT32, 8);
+    words(signals.len() as u32, num_columns as u32);
+    // The array name is a small data element: its length and type share one word
+    words(3 << 16 | MI_INT8, u32::from_le_bytes(*b"val\0"));
+    words(data_type, data.len() as u32);
+    mat.extend(&data);
+    mat.resize(mat.len().div_ceil(8) * 8, 0);
+
+    let base = time::absolute_time(&record.header.record, record.time_of(rows.start));
+    let mut base = base.split_whitespace().map(String::from);
+    let (base_time, base_date) = match (&record.header.record.base_time, rows.start) {
+        (_, 0) => (
+            record.header.record.base_time.clone(),
+            record.header.record.base_date.clone(),
+        ),
+        (Some(_), _) => (base.next(), base.next()),
+        (None, _) => (None, None),
+    };
+    let header = Header {
+        record: RecordLine {
+            record_name: record_name.clone(),
+            number_of_segments: None,
+            number_of_signals: signals.len() as u32,
+            sampling_frequency: record.header.record.sampling_frequency,
+            counter_frequency: record.header.record.counter_frequency,
+            base_counter_value: record.header.record.base_counter_value,
+            samples_per_signal: Some(rows.len() as u32),
+            base_time,
+            base_date,
+        },
+        signal_specs: signals
+            .iter()
+            .zip(&columns)
+            .map(|(&idx, samples)| {
+                let spec = &record.header.signal_specs[idx];
+                SignalSpecLine {
+                    filename: mat_name.clone(),
+                    format,
+                    samples_per_frame: None,
+                    skew: None,
+                    byte_offset: Some(PROLOG_LEN as u32),
+                    adc_gain: spec.adc_gain,
+                    baseline: spec.baseline,
+                    units: spec.units.clone(),
+                    adc_resolution: spec.adc_resolution,
+                    adc_zero: spec.adc_zero,
+                    initial_value: Some(samples.first().copied().unwrap_or(0)),
+                    checksum: Some(signal::checksum(samples)),
+                    block_size: Some(0),
+                    description: spec.description.clone(),
+                }
+            })
+            .collect(),
+    };
+
+    save_file(&mat_name, &mat)?;
+    save_file(
+        &format!("{}.hea", record_name),
+        header.to_string().as_bytes(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn record(samples: &[i32], adc_resolution: u32) -> Record {
+        let header = format!(
+            "rec 2 100 {} 10:00:00 01/01/2000\nrec.dat 32 200(10)/mV {res} 0 0 0 0 ECG\n\
+             rec.dat 32 100/mmHg {res} 0 0 0 0 BP\n",
+            samples.len() / 2,
+            res = adc_resolution
+        );
+        Record::from_header_str(&header, |_| Ok(signal::encode_32_format(samples))).unwrap()
+    }
+
+    fn export(record: &Record, window: Option<Range<f64>>) -> HashMap<String, Vec<u8>> {
+        let mut files = HashMap::new();
+        write_mat_with(record, window, |filename, contents| {
+            files.insert(String::from(filename), contents.to_vec());
+            Ok(())
+        })
+        .unwrap();
+        files
+    }
+
+    #[test]
+    fn mat_file_layout() {
+        let files = export(&record(&[1, -1, 2, -2, 3, -3], 12), None);
+        let mat = &files["recm.mat"];
+        assert!(mat.starts_with(b"MATLAB 5.0 MAT-file"));
+        assert_eq!(&mat[124..128], &[0, 1, b'I', b'M']);
+        // An int16 matrix of 2 rows and 3 columns named val
+        let words: Vec<u32> = mat[128..PROLOG_LEN]
+            .chunks_exact(4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        assert_eq!(
+            words,
+            vec![
+                14,
+                64,
+                6,
+                8,
+                10,
+                0,
+                5,
+                8,
+                2,
+                3,
+                0x0003_0001,
+                0x006c_6176,
+                3,
+                12
+            ]
+        );
+        assert_eq!(mat.len(), PROLOG_LEN + 16);
+        assert_eq!(
+            &mat[PROLOG_LEN..PROLOG_LEN + 12],
+            &signal::encode_16_format(&[1, -1, 2, -2, 3, -3])[..]
+        );
+
+        let header = String::from_utf8(files["recm.hea"].clone()).unwrap();
+        let lines: Vec<&str> = header.lines().collect();
+        assert_eq!(lines[0], "recm 2 100 3 10:00:00 01/01/2000");
+        assert_eq!(lines[1], "recm.mat 16+184 200(10) 12 0 1 6 0 ECG");
+        assert_eq!(lines[2], "recm.mat 16+184 100/mmHg 12 0 -1 -6 0 BP");
+    }
+
+    #[test]
+    fn read_back_as_record() {
+        let original = record(&[100, 7, -40_000, 8, signal::INVALID_SAMPLE, 9], 12);
+        let files = export(&original, None);
+        assert_eq!(files["recm.mat"][128 + 16], MX_INT32_CLASS as u8);
+        let header = String::from_utf8(files["recm.hea"].clone()).unwrap();
+        assert!(header.contains("recm.mat 32+184"));
+        let copy =
+            Record::from_header_str(&header, |filename| Ok(files[filename].clone())).unwrap();
+        assert_eq!(copy.signals, original.signals);
+        assert_eq!(copy.header.signal_specs[1].units.as_deref(), Some("mmHg"));
+    }
+
+    #[test]
+    fn window() {
+        let original = record(&[1, -1, 2, -2, 3, -3, 4, -4], 16);
+        let files = export(&original, Some(0.01..0.03));
+        let header = String::from_utf8(files["recm.hea"].clone()).unwrap();
+        assert!(header.starts_with("recm 2 100 2 10:00:00.010 01/01/2000\n"));
+        let copy =
+            Record::from_header_str(&header, |filename| Ok(files[filename].clone())).unwrap();
+        assert_eq!(copy.signals, vec![vec![2, 3], vec![-2, -3]]);
+    }
+}
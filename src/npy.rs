@@ -0,0 +1,367 @@
+//! Exporting records and annotations as NumPy `.npy` files and `.npz` archives.
+//!
+//! Files are written in version 1.0 of the `.npy` format, which `numpy.load` reads without
+//! pickling. A record's signals become a matrix of shape (samples, channels), as in
+//! [`crate::array`]. With the `zip` feature, [`write_npz`] bundles the signals with their names,
+//! units and calibration, the sampling frequency and the record's annotations in one archive.
+#[cfg(feature = "zip")]
+use std::io::Seek;
+use std::io::{self, Write};
+
+#[cfg(feature = "zip")]
+use crate::annotation::Annotation;
+use crate::record::{ArraySample, Record, Units};
+
+const MAGIC: &[u8] = b"\x93NUMPY\x01\x00";
+
+/// Element types that can be written to `.npy` files.
+pub trait NpyElement: Copy {
+    /// The array protocol type string of the element type, such as `<f8`.
+    const DESCR: &'static str;
+    fn write_le(self, buf: &mut Vec<u8>);
+}
+
+macro_rules! npy_element {
+    ($($t:ty => $descr:expr),*) => {
+        $(impl NpyElement for $t {
+            const DESCR: &'static str = $descr;
+            fn write_le(self, buf: &mut Vec<u8>) {
+                buf.extend(self.to_le_bytes());
+            }
+        })*
+    };
+}
+
+npy_element!(
+    f64 => "<f8", f32 => "<f4", i64 => "<i8", i32 => "<i4", i16 => "<i2", i8 => "|i1",
+    u64 => "<u8", u32 => "<u4", u16 => "<u2", u8 => "|u1"
+);
+
+/// The `.npy` header for an array: the magic string, the format version and a dictionary
+/// describing the array, padded with spaces so that the data starts on a 64-byte boundary.
+fn header(descr: &str, shape: &[usize]) -> Vec<u8> {
+    let shape = match shape {
+        [length] => format!("({},)", length),
+        _ => format!(
+            "({})",
+            shape
+                .iter()
+                .map(usize::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+    let mut dict = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}",
+        descr, shape
+    );
+    let len = MAGIC.len() + 2 + dict.len() + 1;
+    dict.extend(std::iter::repeat_n(' ', len.next_multiple_of(64) - len));
+    dict.push('\n');
+
+    let mut buf = MAGIC.to_vec();
+    buf.extend((dict.len() as u16).to_le_bytes());
+    buf.extend(dict.as_bytes());
+    buf
+}
+
+/// Encode `data`, in row-major order, as an array of the given shape in `.npy` format. Fails with
+/// `InvalidInput` if the shape does not hold as many elements as `data`.
+pub fn encode_npy<T: NpyElement>(shape: &[usize], data: &[T]) -> io::Result<Vec<u8>> {
+    let num_elements = shape
+        .iter()
+        .try_fold(1_usize, |len, &dim| len.checked_mul(dim));
+    if num_elements != Some(data.len()) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "An array of shape {:?} cannot hold {} elements",
+                shape,
+                data.len()
+            ),
+        ));
+    }
+    let mut buf = header(T::DESCR, shape);
+    buf.reserve(std::mem::size_of_val(data));
+    for &value in data {
+        value.write_le(&mut buf);
+    }
+    Ok(buf)
+}
+
+/// Encode strings as a one-dimensional array of fixed-width unicode strings (`<U` type) in
+/// `.npy` format, wide enough for the longest string.
+pub fn encode_npy_strings<S: AsRef<str>>(strings: &[S]) -> Vec<u8> {
+    let width = strings
+        .iter()
+        .map(|s| s.as_ref().chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let mut buf = header(&format!("<U{}", width), &[strings.len()]);
+    for s in strings {
+        let chars: Vec<char> = s.as_ref().chars().collect();
+        for idx in 0..width {
+            buf.extend((chars.get(idx).copied().map_or(0, u32::from)).to_le_bytes());
+        }
+    }
+    buf
+}
+
+/// The samples of a record as a row-major matrix of shape (samples, channels), with one row per
+/// frame (see [`Record::frame_sample`]). Signals shorter than the record are padded with invalid
+/// samples.
+fn samples<T: ArraySample>(record: &Record, units: Units) -> Vec<T> {
+    let specs = &record.header.signal_specs;
+    (0..record.num_samples())
+        .flat_map(|row| {
+            specs
+                .iter()
+                .enumerate()
+                .map(move |(idx, spec)| T::from_sample(record.frame_sample(idx, row), spec, units))
+        })
+        .collect()
+}
+
+/// Write a record's signals to `writer` as a `.npy` matrix of shape (samples, channels), in ADC
+/// or physical units. Invalid samples are written as described for [`ArraySample`].
+pub fn write_npy<T, W>(record: &Record, units: Units, mut writer: W) -> io::Result<()>
+where
+    T: ArraySample + NpyElement,
+    W: Write,
+{
+    let shape = [record.num_samples(), record.signals.len()];
+    writer.write_all(&encode_npy(&shape, &samples::<T>(record, units))?)?;
+    writer.flush()
+}
+
+/// Write a record to `writer` as a `.npz` archive, holding the arrays:
+///
+/// - `signals`: the samples, as written by [`write_npy`]
+/// - `signal_names`, `units`, `adc_gain` and `baseline`: one entry per signal
+/// - `fs`: the sampling frequency, as a scalar
+///
+/// and, when `annotations` are given, `ann_sample`, `ann_code`, `ann_symbol`, `ann_subtype`,
+/// `ann_chan`, `ann_num` and `ann_aux` (an empty string for annotations without auxiliary
+/// information).
+#[cfg(feature = "zip")]
+pub fn write_npz<T, W>(
+    record: &Record,
+    units: Units,
+    annotations: Option<&[Annotation]>,
+    writer: W,
+) -> io::Result<()>
+where
+    T: ArraySample + NpyElement,
+    W: Write + Seek,
+{
+    let specs = &record.header.signal_specs;
+    let signal_units: Vec<&str> = specs
+        .iter()
+        .map(|spec| spec.units.as_deref().unwrap_or("mV"))
+        .collect();
+    let adc_gain: Vec<f64> = specs
+        .iter()
+        .map(|spec| {
+            spec.adc_gain
+                .filter(|&gain| gain != 0.0)
+                .unwrap_or(crate::header::DEFGAIN) as f64
+        })
+        .collect();
+    let baseline: Vec<i32> = specs
+        .iter()
+        .map(|spec| spec.baseline.unwrap_or(0))
+        .collect();
+
+    let mut arrays = vec![
+        (
+            "signals",
+            encode_npy(
+                &[record.num_samples(), record.signals.len()],
+                &samples::<T>(record, units),
+            )?,
+        ),
+        ("signal_names", encode_npy_strings(&record.signal_names())),
+        ("units", encode_npy_strings(&signal_units)),
+        ("adc_gain", encode_npy(&[adc_gain.len()], &adc_gain)?),
+        ("baseline", encode_npy(&[baseline.len()], &baseline)?),
+        ("fs", encode_npy(&[], &[record.sampling_frequency()])?),
+    ];
+    if let Some(annotations) = annotations {
+        let column =
+            |f: fn(&Annotation) -> i64| -> Vec<i64> { annotations.iter().map(f).collect() };
+        let shape = [annotations.len()];
+        let symbols: Vec<&str> = annotations
+            .iter()
+            .map(|a| a.mnemonic().unwrap_or(""))
+            .collect();
+        let aux: Vec<&str> = annotations
+            .iter()
+            .map(|a| a.aux.as_deref().unwrap_or(""))
+            .collect();
+        arrays.extend(vec![
+            (
+                "ann_sample",
+                encode_npy(&shape, &column(|a| a.sample as i64))?,
+            ),
+            ("ann_code", encode_npy(&shape, &column(|a| a.code as i64))?),
+            ("ann_symbol", encode_npy_strings(&symbols)),
+            (
+                "ann_subtype",
+                encode_npy(&shape, &column(|a| a.subtype as i64))?,
+            ),
+            ("ann_chan", encode_npy(&shape, &column(|a| a.chan as i64))?),
+            ("ann_num", encode_npy(&shape, &column(|a| a.num as i64))?),
+            ("ann_aux", encode_npy_strings(&aux)),
+        ]);
+    }
+
+    let mut archive = zip::ZipWriter::new(writer);
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .large_file(true);
+    for (name, contents) in arrays {
+        archive
+            .start_file(format!("{}.npy", name), options)
+            .map_err(io::Error::other)?;
+        archive.write_all(&contents)?;
+    }
+    archive.finish().map_err(io::Error::other)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signal;
+
+    fn record() -> Record {
+        let header =
+            "rec 2 10 3\nrec.dat 16 100(10) 16 0 0 0 0 ECG\nrec.dat 16 50/mmHg 16 0 0 0 0 BP\n";
+        let samples = [100, 50, 200, signal::INVALID_SAMPLE, -100, 25];
+        Record::from_header_str(header, |_| Ok(signal::encode_16_format(&samples))).unwrap()
+    }
+
+    /// Split a `.npy` file into its header dictionary and its data.
+    fn parse(npy: &[u8]) -> (&str, &[u8]) {
+        assert!(npy.starts_with(MAGIC));
+        let len = u16::from_le_bytes([npy[8], npy[9]]) as usize;
+        assert_eq!((10 + len) % 64, 0);
+        let dict = std::str::from_utf8(&npy[10..10 + len]).unwrap();
+        assert!(dict.ends_with('\n'));
+        (dict.trim_end(), &npy[10 + len..])
+    }
+
+    #[test]
+    fn npy_headers() {
+        let npy = encode_npy(&[2, 3], &[1_i16, 2, 3, 4, 5, 6]).unwrap();
+        let (dict, data) = parse(&npy);
+        assert_eq!(
+            dict,
+            "{'descr': '<i2', 'fortran_order': False, 'shape': (2, 3), }"
+        );
+        assert_eq!(data, &[1, 0, 2, 0, 3, 0, 4, 0, 5, 0, 6, 0]);
+
+        let npy = encode_npy(&[1], &[255_u8]).unwrap();
+        let (dict, data) = parse(&npy);
+        assert!(dict.contains("'descr': '|u1'") && dict.contains("'shape': (1,)"));
+        assert_eq!(data, &[255]);
+        let npy = encode_npy(&[], &[2.5_f64]).unwrap();
+        let (dict, data) = parse(&npy);
+        assert!(dict.contains("'shape': ()"));
+        assert_eq!(data, &2.5_f64.to_le_bytes());
+        for shape in [&[2, 2][..], &[usize::MAX, 2]] {
+            assert_eq!(
+                encode_npy(shape, &[1_i16, 2, 3]).unwrap_err().kind(),
+                io::ErrorKind::InvalidInput
+            );
+        }
+
+        let npy = encode_npy_strings(&["ECG", "µV"]);
+        let (dict, data) = parse(&npy);
+        assert!(dict.contains("'descr': '<U3'"));
+        assert_eq!(data.len(), 2 * 3 * 4);
+        assert_eq!(&data[12..20], &[0xb5, 0, 0, 0, b'V', 0, 0, 0]);
+    }
+
+    #[test]
+    fn record_npy() {
+        use std::convert::TryInto;
+
+        let mut npy = vec![];
+        write_npy::<f64, _>(&record(), Units::Physical, &mut npy).unwrap();
+        let (dict, data) = parse(&npy);
+        assert!(dict.contains("'descr': '<f8'") && dict.contains("'shape': (3, 2)"));
+        let values: Vec<f64> = data
+            .chunks_exact(8)
+            .map(|b| f64::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+        assert_eq!(values[..3], [0.9, 1.0, 1.9]);
+        assert!(values[3].is_nan());
+
+        let mut npy = vec![];
+        write_npy::<i32, _>(&record(), Units::Adc, &mut npy).unwrap();
+        let (dict, data) = parse(&npy);
+        assert!(dict.contains("'descr': '<i4'"));
+        assert_eq!(&data[12..16], &signal::INVALID_SAMPLE.to_le_bytes());
+    }
+
+    #[cfg(feature = "zip")]
+    #[test]
+    fn record_npz() {
+        use std::io::{Cursor, Read};
+
+        let annotations = vec![Annotation {
+            sample: 2,
+            code: 1,
+            subtype: 0,
+            chan: 1,
+            num: 0,
+            aux: Some(String::from("(N")),
+        }];
+        let mut buf = Cursor::new(vec![]);
+        write_npz::<f32, _>(&record(), Units::Physical, Some(&annotations), &mut buf).unwrap();
+
+        let mut archive = zip::ZipArchive::new(buf).unwrap();
+        let mut names: Vec<&str> = archive.file_names().collect();
+        names.sort_unstable();
+        assert_eq!(
+            names,
+            vec![
+                "adc_gain.npy",
+                "ann_aux.npy",
+                "ann_chan.npy",
+                "ann_code.npy",
+                "ann_num.npy",
+                "ann_sample.npy",
+                "ann_subtype.npy",
+                "ann_symbol.npy",
+                "baseline.npy",
+                "fs.npy",
+                "signal_names.npy",
+                "signals.npy",
+                "units.npy"
+            ]
+        );
+        let mut read = |name: &str| {
+            let mut contents = vec![];
+            archive
+                .by_name(name)
+                .unwrap()
+                .read_to_end(&mut contents)
+                .unwrap();
+            contents
+        };
+        let signals = read("signals.npy");
+        let (dict, data) = parse(&signals);
+        assert!(dict.contains("'descr': '<f4'") && dict.contains("'shape': (3, 2)"));
+        assert_eq!(&data[..4], &0.9_f32.to_le_bytes());
+        assert_eq!(read("units.npy"), encode_npy_strings(&["mV", "mmHg"]));
+        let fs = read("fs.npy");
+        assert_eq!(parse(&fs).1, &10.0_f64.to_le_bytes());
+        let symbols = read("ann_symbol.npy");
+        assert_eq!(parse(&symbols).1, &[b'N', 0, 0, 0]);
+        let samples = read("ann_sample.npy");
+        assert_eq!(parse(&samples).1, &2_i64.to_le_bytes());
+    }
+}
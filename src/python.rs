@@ -0,0 +1,250 @@
+//! Python bindings, built as the `wfdb_rust` extension module.
+//!
+//! The module is built with maturin (`maturin build --release`, configured in `pyproject.toml`)
+//! and exposes header parsing, record and annotation reading and record writing. Headers are
+//! returned as dictionaries keyed like the attributes of `wfdb.Record` in the `wfdb` package,
+//! and samples and annotations as NumPy arrays.
+use std::path::Path;
+
+use numpy::{IntoPyArray, PyReadonlyArray2};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::annotation;
+use crate::array::{self, Units};
+use crate::header::{self, Header, RecordLine, SignalSpecLine, StorageFormat};
+use crate::record::Record;
+use crate::signal;
+
+/// A header as a dictionary, with a list holding one entry per signal for each signal field.
+fn header_dict<'py>(py: Python<'py>, header: &Header) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    let record = &header.record;
+    dict.set_item("record_name", &record.record_name)?;
+    dict.set_item("n_seg", record.number_of_segments)?;
+    dict.set_item("n_sig", record.number_of_signals)?;
+    dict.set_item("fs", record.sampling_frequency)?;
+    dict.set_item("counter_freq", record.counter_frequency)?;
+    dict.set_item("base_counter", record.base_counter_value)?;
+    dict.set_item("sig_len", record.samples_per_signal)?;
+    dict.set_item("base_time", &record.base_time)?;
+    dict.set_item("base_date", &record.base_date)?;
+
+    let specs = &header.signal_specs;
+    macro_rules! signal_field {
+        ($key:expr, $spec:ident => $value:expr) => {
+            dict.set_item($key, specs.iter().map(|$spec| $value).collect::<Vec<_>>())?
+        };
+    }
+    signal_field!("file_name", spec => spec.filename.clone());
+    signal_field!("fmt", spec => (spec.format as u32).to_string());
+    signal_field!("samps_per_frame", spec => spec.samples_per_frame.unwrap_or(1));
+    signal_field!("skew", spec => spec.skew);
+    signal_field!("byte_offset", spec => spec.byte_offset);
+    signal_field!("adc_gain", spec => spec.adc_gain);
+    signal_field!("baseline", spec => spec.baseline);
+    signal_field!("units", spec => spec.units.clone());
+    signal_field!("adc_res", spec => spec.adc_resolution);
+    signal_field!("adc_zero", spec => spec.adc_zero);
+    signal_field!("init_value", spec => spec.initial_value);
+    signal_field!("checksum", spec => spec.checksum);
+    signal_field!("block_size", spec => spec.block_size);
+    signal_field!("sig_name", spec => spec.description.clone());
+    Ok(dict)
+}
+
+/// Parse the text of a header file into a dictionary.
+#[pyfunction]
+fn parse_header<'py>(py: Python<'py>, text: &str) -> PyResult<Bound<'py, PyDict>> {
+    let header =
+        header::read_header(text).map_err(|error| PyValueError::new_err(error.to_string()))?;
+    header_dict(py, &header)
+}
+
+/// Read a record into a header dictionary and an array of shape (samples, channels).
+///
+/// Samples are `float64` in physical units, with NaN for invalid samples, or `int32` in ADC
+/// units when `physical` is false, with invalid samples as their storage format's invalid value
+/// (-32768 in format 16) as in the `wfdb` package. As in `wfdb.rdrecord`, the array starts at
+/// sample number `sampfrom` and ends before sample number `sampto` (the end of the record by
+/// default).
+#[pyfunction]
+#[pyo3(signature = (record_name, physical = true, sampfrom = 0, sampto = None))]
+fn read_record<'py>(
+    py: Python<'py>,
+    record_name: &str,
+    physical: bool,
+    sampfrom: usize,
+    sampto: Option<usize>,
+) -> PyResult<(Bound<'py, PyDict>, Bound<'py, PyAny>)> {
+    let rows = sampfrom..sampto.unwrap_or(usize::MAX);
+    if physical {
+        let (header, samples) =
+            py.detach(|| array::read_array_rows::<f64, _>(record_name, Units::Physical, rows))?;
+        Ok((
+            header_dict(py, &header)?,
+            samples.into_pyarray(py).into_any(),
+        ))
+    } else {
+        let (header, mut samples) =
+            py.detach(|| array::read_array_rows::<i32, _>(record_name, Units::Adc, rows))?;
+        for (mut column, spec) in samples.columns_mut().into_iter().zip(&header.signal_specs) {
+            if let Some(invalid_value) = signal::invalid_value(spec.format) {
+                column.mapv_inplace(|sample| {
+                    if signal::is_valid(sample) {
+                        sample
+                    } else {
+                        invalid_value
+                    }
+                });
+            }
+        }
+        Ok((
+            header_dict(py, &header)?,
+            samples.into_pyarray(py).into_any(),
+        ))
+    }
+}
+
+/// Read the annotations made by `annotator` for a record into a dictionary of arrays: `sample`,
+/// `code`, `subtype`, `chan` and `num` as integer arrays, and `symbol` and `aux_note` as lists of
+/// strings (empty for annotations without one).
+#[pyfunction]
+fn read_annotations<'py>(
+    py: Python<'py>,
+    record_name: &str,
+    annotator: &str,
+) -> PyResult<Bound<'py, PyDict>> {
+    let annotations = py.detach(|| annotation::read_annotations(record_name, annotator))?;
+    let dict = PyDict::new(py);
+    macro_rules! column {
+        ($key:expr, $a:ident => $value:expr) => {
+            dict.set_item(
+                $key,
+                annotations
+                    .iter()
+                    .map(|$a| $value)
+                    .collect::<Vec<_>>()
+                    .into_pyarray(py),
+            )?
+        };
+    }
+    column!("sample", a => a.sample);
+    column!("code", a => a.code);
+    column!("subtype", a => a.subtype);
+    column!("chan", a => a.chan);
+    column!("num", a => a.num);
+    let symbols: Vec<&str> = annotations
+        .iter()
+        .map(|a| a.mnemonic().unwrap_or(""))
+        .collect();
+    dict.set_item("symbol", symbols)?;
+    let aux: Vec<&str> = annotations
+        .iter()
+        .map(|a| a.aux.as_deref().unwrap_or(""))
+        .collect();
+    dict.set_item("aux_note", aux)?;
+    Ok(dict)
+}
+
+/// Write a record from an `int32` array of ADC samples of shape (samples, channels), as
+/// `<record_name>.hea` and `<record_name>.dat` in `write_dir`.
+///
+/// `fmt` is 16 or 32. Signals without an entry in `sig_name`, `units`, `adc_gain` or `baseline`
+/// get no description, millivolts, a gain of 200 and a baseline of 0. Invalid samples are
+/// -32768 in format 16 and -2147483648 in format 32.
+#[pyfunction]
+#[pyo3(signature = (
+    record_name, d_signal, fs, sig_name = None, units = None, adc_gain = None, baseline = None,
+    fmt = 16, write_dir = "."
+))]
+#[allow(clippy::too_many_arguments)]
+fn write_record(
+    py: Python<'_>,
+    record_name: &str,
+    d_signal: PyReadonlyArray2<'_, i32>,
+    fs: f32,
+    sig_name: Option<Vec<String>>,
+    units: Option<Vec<String>>,
+    adc_gain: Option<Vec<f32>>,
+    baseline: Option<Vec<i32>>,
+    fmt: u32,
+    write_dir: &str,
+) -> PyResult<()> {
+    let (format, adc_resolution) = match fmt {
+        16 => (StorageFormat::_16bit_twos_complement, 16),
+        32 => (StorageFormat::_32bit_twos_complement_lsb, 32),
+        _ => return Err(PyValueError::new_err("fmt must be 16 or 32")),
+    };
+    let d_signal = d_signal.as_array();
+    let invalid_value = signal::invalid_value(format).unwrap_or(signal::INVALID_SAMPLE);
+    let signals: Vec<Vec<i32>> = d_signal
+        .columns()
+        .into_iter()
+        .map(|column| {
+            column
+                .iter()
+                .map(|&sample| signal::mark_invalid(sample, invalid_value))
+                .collect()
+        })
+        .collect();
+    let signal_specs = signals
+        .iter()
+        .enumerate()
+        .map(|(idx, samples)| SignalSpecLine {
+            filename: format!("{}.dat", record_name),
+            format,
+            samples_per_frame: None,
+            skew: None,
+            byte_offset: None,
+            adc_gain: Some(
+                adc_gain
+                    .as_ref()
+                    .and_then(|gains| gains.get(idx).copied())
+                    .unwrap_or(header::DEFGAIN),
+            ),
+            baseline: Some(
+                baseline
+                    .as_ref()
+                    .and_then(|b| b.get(idx).copied())
+                    .unwrap_or(0),
+            ),
+            units: units.as_ref().and_then(|units| units.get(idx).cloned()),
+            adc_resolution: Some(adc_resolution),
+            adc_zero: Some(0),
+            initial_value: Some(samples.first().copied().unwrap_or(0)),
+            checksum: Some(signal::checksum(samples)),
+            block_size: Some(0),
+            description: sig_name.as_ref().and_then(|names| names.get(idx).cloned()),
+        })
+        .collect();
+    let record = Record {
+        header: Header {
+            record: RecordLine {
+                record_name: String::from(record_name),
+                number_of_segments: None,
+                number_of_signals: signals.len() as u32,
+                sampling_frequency: Some(fs),
+                counter_frequency: None,
+                base_counter_value: None,
+                samples_per_signal: Some(d_signal.nrows() as u32),
+                base_time: None,
+                base_date: None,
+            },
+            signal_specs,
+        },
+        signals,
+    };
+    py.detach(|| record.write_in(Path::new(write_dir)))?;
+    Ok(())
+}
+
+#[pymodule]
+fn wfdb_rust(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(parse_header, m)?)?;
+    m.add_function(wrap_pyfunction!(read_record, m)?)?;
+    m.add_function(wrap_pyfunction!(read_annotations, m)?)?;
+    m.add_function(wrap_pyfunction!(write_record, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,1102 @@
+//! Opening WFDB records by record name.
+//!
+//! A WFDB record is identified by its name (e.g. `100`, or `mitdb/100` when the record lives in a
+//! sub-directory). The record's header is found by appending `.hea` to the record name, and the
+//! signal files listed in the header are resolved relative to the directory containing the
+//! header. Records can be read from the filesystem or from any other [`Storage`] backend, and
+//! gzip-compressed headers and signal files (`100.hea.gz`, `100.dat.gz`) are read transparently.
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read};
+use std::ops::Range;
+use std::path::Path;
+
+use crate::edf;
+use crate::header::{self, Header, SignalSpecLine, StorageFormat};
+use crate::signal;
+use crate::storage::{self, FileStorage, Storage};
+
+/// The units samples are exported in.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Units {
+    /// Raw ADC units, as stored in the signal files.
+    Adc,
+    /// Physical units (as given by each signal's `units`), using the signal's gain and baseline.
+    Physical,
+}
+
+/// Element types a record's samples can be exported as, such as into `ndarray` matrices or
+/// `.npy` files.
+///
+/// Invalid samples become `NaN` in floating-point matrices and [`signal::INVALID_SAMPLE`] in
+/// `i32` matrices. Physical values are rounded to the nearest integer in `i32` matrices.
+pub trait ArraySample: Copy {
+    fn from_sample(sample: i32, signal: &SignalSpecLine, units: Units) -> Self;
+}
+
+impl ArraySample for f64 {
+    fn from_sample(sample: i32, signal: &SignalSpecLine, units: Units) -> f64 {
+        match units {
+            Units::Adc if signal::is_valid(sample) => sample as f64,
+            Units::Adc => f64::NAN,
+            Units::Physical => signal.to_physical(sample).unwrap_or(f64::NAN),
+        }
+    }
+}
+
+impl ArraySample for f32 {
+    fn from_sample(sample: i32, signal: &SignalSpecLine, units: Units) -> f32 {
+        f64::from_sample(sample, signal, units) as f32
+    }
+}
+
+impl ArraySample for i32 {
+    fn from_sample(sample: i32, signal: &SignalSpecLine, units: Units) -> i32 {
+        match units {
+            Units::Adc => sample,
+            Units::Physical => signal
+                .to_physical(sample)
+                .map_or(signal::INVALID_SAMPLE, |value| value.round() as i32),
+        }
+    }
+}
+
+/// A WFDB record: the parsed header and the samples of every signal it describes.
+///
+/// Samples are held in ADC units as `i32`, wide enough for every storage format.
+#[derive(PartialEq, Debug)]
+pub struct Record {
+    pub header: Header,
+    pub signals: Vec<Vec<i32>>,
+}
+
+impl Record {
+    /// Open a record by name, relative to the current working directory.
+    ///
+    /// `Record::open("mitdb/100")` reads `mitdb/100.hea` and resolves the signal files listed in
+    /// it relative to `mitdb/`. A path to the header file itself (`mitdb/100.hea`) is also
+    /// accepted, and EDF files are opened directly by their file name (`chb01/chb01_01.edf`, see
+    /// [`crate::edf`]).
+    pub fn open<P: AsRef<Path>>(record_name: P) -> io::Result<Record> {
+        Record::open_in(Path::new(""), record_name)
+    }
+
+    /// Read only the header of a record, named as for [`Record::open`], without decoding its
+    /// signal files.
+    pub fn read_header<P: AsRef<Path>>(record_name: P) -> io::Result<Header> {
+        read_header_in(
+            &FileStorage::new(""),
+            &record_name.as_ref().to_string_lossy(),
+        )
+    }
+
+    /// Open a record by name, relative to the database directory `root`.
+    pub fn open_in<P: AsRef<Path>>(root: &Path, record_name: P) -> io::Result<Record> {
+        Record::open_from(
+            &FileStorage::new(root),
+            &record_name.as_ref().to_string_lossy(),
+        )
+    }
+
+    /// Open a record by name from any [`Storage`] backend, such as a zip archive. The record name
+    /// is resolved the same way as by [`Record::open`].
+    pub fn open_from(storage: &dyn Storage, record_name: &str) -> io::Result<Record> {
+        let (header, files) = open_signal_files(storage, record_name)?;
+        Ok(Record::from_signal_files(header, &files))
+    }
+
+    /// Build a record from header text held in memory, fetching each signal file's contents with
+    /// `load_signal_file`.
+    pub fn from_header_str<F>(header: &str, load_signal_file: F) -> io::Result<Record>
+    where
+        F: FnMut(&str) -> io::Result<Vec<u8>>,
+    {
+        Record::from_header(header::read_header(header)?, load_signal_file)
+    }
+
+    /// Build a record from header text read from any `Read` source (an archive entry, a byte
+    /// buffer, a test fixture...), fetching each signal file's contents with `load_signal_file`.
+    pub fn from_reader<R, F>(header: R, load_signal_file: F) -> io::Result<Record>
+    where
+        R: Read,
+        F: FnMut(&str) -> io::Result<Vec<u8>>,
+    {
+        Record::from_header(header::read_header_from(header)?, load_signal_file)
+    }
+
+    /// Build a record from an already-parsed header. `load_signal_file` is called once for each
+    /// distinct signal file name listed in the header, except for signals in format 0, which have
+    /// no signal file and whose samples are all [`signal::INVALID_SAMPLE`].
+    pub fn from_header<F>(header: Header, load_signal_file: F) -> io::Result<Record>
+    where
+        F: FnMut(&str) -> io::Result<Vec<u8>>,
+    {
+        let files = SignalFiles::decode(&header, load_signal_file)?;
+        Ok(Record::from_signal_files(header, &files))
+    }
+
+    fn from_signal_files(header: Header, files: &SignalFiles) -> Record {
+        let signals = (0..header.signal_specs.len())
+            .map(|signal| {
+                (0..files.signal_len(signal))
+                    .map(|idx| files.sample(signal, idx))
+                    .collect()
+            })
+            .collect();
+        Record { header, signals }
+    }
+
+    /// Write the record's header and signal files into `directory`, as described by
+    /// [`Record::write_with`].
+    pub fn write_in(&self, directory: &Path) -> io::Result<()> {
+        self.write_with(|filename, contents| fs::write(directory.join(filename), contents))
+    }
+
+    /// Encode the record's header and signal files, handing each file's name and contents to
+    /// `save_file`. The header is named after the record (`100.hea`) and each signal file is
+    /// named as in the header, with each frame holding each signal's samples per frame and skewed
+    /// signals stored as many frames late as their skew. Signals can be written in formats 16 and
+    /// 32, and in the FLAC formats 508, 516 and 524 with the `flac` feature, which hold up to 8
+    /// signals per file and fail with an error of kind `InvalidInput` for samples that do not fit.
+    pub fn write_with<F>(&self, mut save_file: F) -> io::Result<()>
+    where
+        F: FnMut(&str, &[u8]) -> io::Result<()>,
+    {
+        save_file(
+            &format!("{}.hea", self.header.record.record_name),
+            self.header.to_string().as_bytes(),
+        )?;
+
+        let mut file_names: Vec<&String> = vec![];
+        let mut signals_in_file: HashMap<&String, Vec<usize>> = HashMap::new();
+        let specs = self.header.signal_specs.iter().enumerate();
+        for (idx, spec) in specs.filter(|(_, spec)| spec.format != StorageFormat::_null) {
+            if !signals_in_file.contains_key(&spec.filename) {
+                file_names.push(&spec.filename);
+            }
+            signals_in_file.entry(&spec.filename).or_default().push(idx);
+        }
+
+        for filename in file_names {
+            let signals = &signals_in_file[filename];
+            let format = self.header.signal_specs[signals[0]].format;
+            if signals
+                .iter()
+                .any(|&idx| self.header.signal_specs[idx].format != format)
+            {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Signals in {} are not all in the same format", filename),
+                ));
+            }
+            let specs: Vec<&SignalSpecLine> = signals
+                .iter()
+                .map(|&idx| &self.header.signal_specs[idx])
+                .collect();
+            // Each signal's samples for a frame are stored `skew` frames later in the file
+            let length = signals
+                .iter()
+                .zip(&specs)
+                .map(|(&idx, spec)| {
+                    self.signals[idx].len().div_ceil(spec.spf()) + spec.skew.unwrap_or(0) as usize
+                })
+                .max()
+                .unwrap_or(0);
+            let padded = |idx: usize| -> Vec<i32> {
+                let spec = &self.header.signal_specs[idx];
+                let skew = spec.skew.unwrap_or(0) as usize * spec.spf();
+                let mut samples = vec![signal::INVALID_SAMPLE; skew];
+                samples.extend(&self.signals[idx]);
+                samples.resize(length * spec.spf(), signal::INVALID_SAMPLE);
+                samples
+            };
+            let interleaved = || -> Vec<i32> {
+                let columns: Vec<Vec<i32>> = signals.iter().map(|&idx| padded(idx)).collect();
+                (0..length)
+                    .flat_map(|frame| {
+                        columns.iter().zip(&specs).flat_map(move |(column, spec)| {
+                            column[frame * spec.spf()..(frame + 1) * spec.spf()]
+                                .iter()
+                                .copied()
+                        })
+                    })
+                    .collect()
+            };
+            let contents = match format {
+                StorageFormat::_16bit_twos_complement => signal::encode_16_format(&interleaved()),
+                StorageFormat::_32bit_twos_complement_lsb => {
+                    signal::encode_32_format(&interleaved())
+                }
+                #[cfg(feature = "flac")]
+                StorageFormat::_8bit_flac
+                | StorageFormat::_16bit_flac
+                | StorageFormat::_24bit_flac => {
+                    if specs.iter().any(|spec| spec.spf() > 1) {
+                        return Err(flac_frames_unsupported());
+                    }
+                    let bits_per_sample = signal::flac::bits_per_sample(format)
+                        .expect("Formats 508, 516 and 524 are FLAC formats");
+                    let invalid_value = -(1 << (bits_per_sample - 1));
+                    let columns: Vec<Vec<i32>> = signals
+                        .iter()
+                        .map(|&idx| {
+                            let samples = padded(idx).into_iter();
+                            samples
+                                .map(|s| {
+                                    if signal::is_valid(s) {
+                                        s
+                                    } else {
+                                        invalid_value
+                                    }
+                                })
+                                .collect()
+                        })
+                        .collect();
+                    signal::flac::encode(&columns, format, self.sampling_frequency() as f32)?
+                }
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::Unsupported,
+                        format!(
+                            "Writing signals in format {} is not supported",
+                            format as u32
+                        ),
+                    ))
+                }
+            };
+            save_file(filename, &contents)?;
+        }
+        Ok(())
+    }
+
+    /// The samples of a signal, with invalid samples (see [`signal::INVALID_SAMPLE`]) as `None`.
+    pub fn samples(&self, signal: usize) -> impl Iterator<Item = Option<i32>> + '_ {
+        self.signals[signal]
+            .iter()
+            .map(|&sample| Some(sample).filter(|&sample| signal::is_valid(sample)))
+    }
+
+    /// The samples of a signal converted to physical units, with invalid samples as `None`.
+    pub fn physical_samples(&self, signal: usize) -> Vec<Option<f64>> {
+        let spec = &self.header.signal_specs[signal];
+        self.signals[signal]
+            .iter()
+            .map(|&sample| spec.to_physical(sample))
+            .collect()
+    }
+
+    /// Summary statistics of a signal's valid samples.
+    pub fn statistics(&self, signal: usize) -> signal::Statistics {
+        signal::statistics(&self.signals[signal])
+    }
+
+    /// Index of the signal with the given description in the header, such as `MLII` or `V5`.
+    pub fn signal_index(&self, name: &str) -> Option<usize> {
+        self.header
+            .signal_specs
+            .iter()
+            .position(|spec| spec.description.as_deref() == Some(name))
+    }
+
+    /// The samples of the signal with the given description in the header.
+    pub fn signal(&self, name: &str) -> Option<&[i32]> {
+        self.signal_index(name).map(|idx| &self.signals[idx][..])
+    }
+
+    /// Names of the signals: each signal's description in the header, or `signal <n>` for signals
+    /// without one.
+    pub fn signal_names(&self) -> Vec<String> {
+        let specs = self.header.signal_specs.iter().enumerate();
+        specs
+            .map(|(idx, spec)| {
+                spec.description
+                    .clone()
+                    .unwrap_or_else(|| format!("signal {}", idx))
+            })
+            .collect()
+    }
+
+    /// Sampling frequency of the record, in frames per second (see
+    /// [`Header::sampling_frequency`]). Signals with several samples per frame are sampled faster
+    /// (see [`Header::signal_frequency`]).
+    pub fn sampling_frequency(&self) -> f64 {
+        self.header.sampling_frequency()
+    }
+
+    /// Number of frames in the record: the number of samples in each signal with one sample per
+    /// frame. Sample numbers given to and returned by the time-axis helpers below count frames.
+    pub fn num_samples(&self) -> usize {
+        let specs = self.header.signal_specs.iter();
+        specs
+            .zip(&self.signals)
+            .map(|(spec, samples)| samples.len().div_ceil(spec.spf()))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// A signal's sample in a frame, or [`signal::INVALID_SAMPLE`] past the end of the signal.
+    /// Signals with several samples per frame give the average of their samples in the frame (see
+    /// [`signal::frame_average`]), as the WFDB library does outside high-resolution mode.
+    pub fn frame_sample(&self, signal: usize, frame: usize) -> i32 {
+        let samples = &self.signals[signal];
+        match self.header.signal_specs[signal].spf() {
+            1 => *samples.get(frame).unwrap_or(&signal::INVALID_SAMPLE),
+            spf => {
+                let start = frame.saturating_mul(spf).min(samples.len());
+                let end = start.saturating_add(spf).min(samples.len());
+                signal::frame_average(&samples[start..end])
+            }
+        }
+    }
+
+    /// Duration of the record, in seconds.
+    pub fn duration(&self) -> f64 {
+        self.time_of(self.num_samples())
+    }
+
+    /// Time of a sample, in seconds from the beginning of the record.
+    pub fn time_of(&self, sample: usize) -> f64 {
+        sample as f64 / self.sampling_frequency()
+    }
+
+    /// Index of the sample closest to a time in seconds from the beginning of the record.
+    pub fn sample_at(&self, seconds: f64) -> usize {
+        (seconds * self.sampling_frequency()).round().max(0.0) as usize
+    }
+
+    /// The time axis of the record: the time of each sample, in seconds.
+    pub fn times(&self) -> impl Iterator<Item = f64> + '_ {
+        (0..self.num_samples()).map(move |sample| self.time_of(sample))
+    }
+}
+
+/// Read the header of a record held in `storage` and decode its signal files.
+pub(crate) fn open_signal_files(
+    storage: &dyn Storage,
+    record_name: &str,
+) -> io::Result<(Header, SignalFiles)> {
+    if edf::is_edf(record_name) {
+        return edf::open_signal_files(storage, record_name);
+    }
+    let header = read_header_in(storage, record_name)?;
+    let files = read_signal_files(storage, record_name, &header, 0..usize::MAX)?;
+    Ok((header, files))
+}
+
+/// Read the header of a record held in `storage`, without reading its signal files.
+pub(crate) fn read_header_in(storage: &dyn Storage, record_name: &str) -> io::Result<Header> {
+    if edf::is_edf(record_name) {
+        return edf::read_header_from(storage, record_name);
+    }
+    header::read_header_from(storage::open_file(storage, &header_path(record_name))?)
+}
+
+/// Decode the samples in `frames` from the signal files of a record held in `storage`, reading
+/// only the bytes holding them where the storage format allows it (see [`SignalFiles::decode_range`]).
+pub(crate) fn read_signal_files(
+    storage: &dyn Storage,
+    record_name: &str,
+    header: &Header,
+    frames: Range<usize>,
+) -> io::Result<SignalFiles> {
+    if edf::is_edf(record_name) {
+        return edf::open_signal_files(storage, record_name).map(|(_, files)| files);
+    }
+    let header_path = header_path(record_name);
+    let data_directory = header_path.rfind('/').map_or("", |idx| &header_path[..idx]);
+    // Signal files with a nonzero block size are special files (such as block devices) that must
+    // be read in whole blocks
+    let block_sizes: HashMap<&str, u32> = header
+        .signal_specs
+        .iter()
+        .filter_map(|signal| {
+            signal
+                .block_size
+                .filter(|&size| size > 0)
+                .map(|size| (signal.filename.as_str(), size))
+        })
+        .collect();
+    SignalFiles::decode_range(header, frames, |filename, offset, len| {
+        let path = storage::join(data_directory, filename);
+        match block_sizes.get(filename) {
+            Some(&block_size) => {
+                let buf = storage::read_file_in_blocks(storage, &path, block_size as usize)?;
+                Ok(byte_range(buf, offset, len))
+            }
+            // Files read to the end are read whole, so that storage backends can cache them
+            None if len == u64::MAX => {
+                Ok(byte_range(storage::read_file(storage, &path)?, offset, len))
+            }
+            None => storage::read_file_range(storage, &path, offset, len),
+        }
+    })
+}
+
+/// The bytes of `buf` in the range read by [`Storage::read_range`].
+fn byte_range(mut buf: Vec<u8>, offset: u64, len: u64) -> Vec<u8> {
+    let start = (offset.min(buf.len() as u64)) as usize;
+    let end = (offset.saturating_add(len).min(buf.len() as u64)) as usize;
+    buf.truncate(end);
+    buf.drain(..start);
+    buf
+}
+
+/// The decoded signal files of a record, with the samples of the signals in each file still
+/// interleaved as they are stored. Samples can be copied from here straight into whatever
+/// container holds them.
+pub(crate) struct SignalFiles {
+    /// The decoded samples of each file, starting at frame `start` of the file.
+    files: Vec<Vec<i32>>,
+    start: usize,
+    layout: Vec<SignalLayout>,
+    /// Number of frames in the record.
+    length: usize,
+}
+
+/// Where the samples of a signal are held among the decoded samples of its file.
+#[derive(Copy, Clone)]
+struct SignalLayout {
+    /// Index of the signal's file, or `None` for signals in format 0.
+    file: Option<usize>,
+    /// Position of the signal's first sample in each frame of the file.
+    offset: usize,
+    /// Number of samples of the signal in each frame.
+    spf: usize,
+    /// Number of samples of all the signals in the file in each frame.
+    frame_size: usize,
+    /// Number of frames the signal's samples are stored after the frames they belong to.
+    skew: usize,
+}
+
+impl SignalFiles {
+    /// Decode the signal files listed in `header`. `load_signal_file` is called once for each
+    /// distinct signal file name, except for signals in format 0, which have no signal file.
+    pub(crate) fn decode<F>(header: &Header, mut load_signal_file: F) -> io::Result<SignalFiles>
+    where
+        F: FnMut(&str) -> io::Result<Vec<u8>>,
+    {
+        SignalFiles::decode_range(header, 0..usize::MAX, |filename, offset, len| {
+            Ok(byte_range(load_signal_file(filename)?, offset, len))
+        })
+    }
+
+    /// Decode the samples in `frames` from the signal files listed in `header`. `read_range` is
+    /// called once for each distinct signal file name with a byte offset and length, as for
+    /// [`Storage::read_range`]: only the bytes holding the frames are requested, except for
+    /// formats that can only be decoded from the beginning of the file (first differences and
+    /// FLAC), whose files are read whole.
+    pub(crate) fn decode_range<F>(
+        header: &Header,
+        frames: Range<usize>,
+        mut read_range: F,
+    ) -> io::Result<SignalFiles>
+    where
+        F: FnMut(&str, u64, u64) -> io::Result<Vec<u8>>,
+    {
+        let mut file_names: Vec<&String> = vec![];
+        let mut signals_in_file: HashMap<&String, Vec<&SignalSpecLine>> = HashMap::new();
+        for signal in header
+            .signal_specs
+            .iter()
+            .filter(|signal| signal.format != StorageFormat::_null)
+        {
+            if !signals_in_file.contains_key(&signal.filename) {
+                file_names.push(&signal.filename);
+            }
+            signals_in_file
+                .entry(&signal.filename)
+                .or_default()
+                .push(signal);
+        }
+
+        let start = frames.start.min(frames.end);
+        let mut files = vec![];
+        for filename in &file_names {
+            let signals = &signals_in_file[filename];
+            let frame_size: usize = signals.iter().map(|signal| signal.spf()).sum();
+            let max_skew = signals
+                .iter()
+                .map(|signal| signal.skew.unwrap_or(0) as usize)
+                .max()
+                .unwrap_or(0);
+            let byte_offset = signals[0].byte_offset.unwrap_or(0) as u64;
+            // Skewed signals hold the samples of the last frames further into the file
+            let first_sample = start.saturating_mul(frame_size);
+            let end_sample = frames
+                .end
+                .saturating_add(max_skew)
+                .saturating_mul(frame_size);
+            let mut samples = match sample_group(signals[0].format) {
+                Some((group_samples, group_bytes)) => {
+                    // Read from the start of the group holding the first sample
+                    let first_group = first_sample / group_samples;
+                    let end_group = end_sample.div_ceil(group_samples);
+                    let group_bytes = group_bytes as u64;
+                    let buf = read_range(
+                        filename,
+                        byte_offset
+                            .saturating_add((first_group as u64).saturating_mul(group_bytes)),
+                        ((end_group - first_group) as u64).saturating_mul(group_bytes),
+                    )?;
+                    let mut samples = parse_signal_file(signals, &buf)?;
+                    samples
+                        .drain(..(first_sample - first_group * group_samples).min(samples.len()));
+                    samples
+                }
+                None => {
+                    let buf = read_range(filename, byte_offset, u64::MAX)?;
+                    let mut samples = parse_signal_file(signals, &buf)?;
+                    samples.drain(..first_sample.min(samples.len()));
+                    samples
+                }
+            };
+            samples.truncate(end_sample - first_sample);
+            files.push(samples);
+        }
+
+        let mut offset_in_file: HashMap<&String, usize> = HashMap::new();
+        let layout = header
+            .signal_specs
+            .iter()
+            .map(|signal| {
+                let mut layout = SignalLayout {
+                    file: None,
+                    offset: 0,
+                    spf: signal.spf(),
+                    frame_size: signal.spf(),
+                    skew: signal.skew.unwrap_or(0) as usize,
+                };
+                if signal.format != StorageFormat::_null {
+                    let offset = offset_in_file.entry(&signal.filename).or_insert(0);
+                    layout.file = file_names
+                        .iter()
+                        .position(|&filename| filename == &signal.filename);
+                    layout.offset = *offset;
+                    layout.frame_size = signals_in_file[&signal.filename]
+                        .iter()
+                        .map(|signal| signal.spf())
+                        .sum();
+                    *offset += layout.spf;
+                }
+                layout
+            })
+            .collect();
+
+        let mut signal_files = SignalFiles {
+            files,
+            start,
+            layout,
+            length: 0,
+        };
+        signal_files.length = match header.record.samples_per_signal {
+            Some(length) => length as usize,
+            None => signal_files
+                .layout
+                .iter()
+                .enumerate()
+                .map(|(signal, layout)| signal_files.held_len(signal).div_ceil(layout.spf))
+                .max()
+                .unwrap_or(0),
+        };
+        Ok(signal_files)
+    }
+
+    /// Signals decoded by other means (such as from an EDF file), each held on its own with the
+    /// number of samples per frame given in `header`.
+    pub(crate) fn from_signals(header: &Header, signals: Vec<Vec<i32>>) -> SignalFiles {
+        let layout: Vec<SignalLayout> = header
+            .signal_specs
+            .iter()
+            .enumerate()
+            .map(|(signal, spec)| SignalLayout {
+                file: Some(signal),
+                offset: 0,
+                spf: spec.spf(),
+                frame_size: spec.spf(),
+                skew: 0,
+            })
+            .collect();
+        let length = signals
+            .iter()
+            .zip(&layout)
+            .map(|(samples, layout)| samples.len().div_ceil(layout.spf))
+            .max()
+            .unwrap_or(0);
+        SignalFiles {
+            files: signals,
+            start: 0,
+            layout,
+            length,
+        }
+    }
+
+    /// Number of frames in the record.
+    #[cfg_attr(not(feature = "ndarray"), allow(dead_code))]
+    pub(crate) fn len(&self) -> usize {
+        self.length
+    }
+
+    /// Number of samples of a signal held in its file, up to the end of the record's last frame,
+    /// or the record's length for signals in format 0.
+    pub(crate) fn signal_len(&self, signal: usize) -> usize {
+        let spf = self.layout[signal].spf;
+        match self.layout[signal].file {
+            Some(_) => self.held_len(signal).min(self.length.saturating_mul(spf)),
+            None => self.length * spf,
+        }
+    }
+
+    /// Number of samples of a signal held in its file, which may hold frames past the end of the
+    /// record.
+    fn held_len(&self, signal: usize) -> usize {
+        let layout = self.layout[signal];
+        let file_idx = match layout.file {
+            Some(file_idx) => file_idx,
+            None => return 0,
+        };
+        let file_len = self.files[file_idx].len();
+        let in_last_frame = (file_len % layout.frame_size)
+            .saturating_sub(layout.offset)
+            .min(layout.spf);
+        let held = (file_len / layout.frame_size) * layout.spf + in_last_frame;
+        // The first frames held in the file belong to frames before `start` of skewed signals
+        (self.start * layout.spf) + held.saturating_sub(layout.skew * layout.spf)
+    }
+
+    /// A sample of a signal, or [`signal::INVALID_SAMPLE`] outside the samples decoded from the
+    /// signal's file.
+    pub(crate) fn sample(&self, signal: usize, idx: usize) -> i32 {
+        let layout = self.layout[signal];
+        let frame = (idx / layout.spf + layout.skew).checked_sub(self.start);
+        match (layout.file, frame) {
+            (Some(file_idx), Some(frame)) => *self.files[file_idx]
+                .get(frame * layout.frame_size + layout.offset + idx % layout.spf)
+                .unwrap_or(&signal::INVALID_SAMPLE),
+            _ => signal::INVALID_SAMPLE,
+        }
+    }
+
+    /// A signal's sample in a frame: the average of its samples in the frame for signals with
+    /// several samples per frame (see [`signal::frame_average`]).
+    #[cfg_attr(not(feature = "ndarray"), allow(dead_code))]
+    pub(crate) fn frame_sample(&self, signal: usize, frame: usize) -> i32 {
+        let spf = self.layout[signal].spf;
+        if spf == 1 {
+            return self.sample(signal, frame);
+        }
+        let samples: Vec<i32> = (frame * spf..(frame + 1) * spf)
+            .map(|idx| self.sample(signal, idx))
+            .collect();
+        signal::frame_average(&samples)
+    }
+}
+
+/// The smallest number of samples in a storage format that starts on a byte boundary and the
+/// number of bytes they take, or `None` for formats that can only be decoded from the beginning
+/// of the file.
+fn sample_group(format: StorageFormat) -> Option<(usize, usize)> {
+    match format {
+        StorageFormat::_8bit_offset_binary => Some((1, 1)),
+        StorageFormat::_16bit_twos_complement
+        | StorageFormat::_16bit_twos_complement_msb
+        | StorageFormat::_16bit_offset_binary => Some((1, 2)),
+        StorageFormat::_24bit_twos_complement_lsb => Some((1, 3)),
+        StorageFormat::_32bit_twos_complement_lsb => Some((1, 4)),
+        StorageFormat::_12bit_twos_complement => Some((2, 3)),
+        StorageFormat::_10bit_twos_complement_sets_of_11
+        | StorageFormat::_10bit_twos_complement_sets_of_4 => Some((3, 4)),
+        StorageFormat::_null
+        | StorageFormat::_8bit_first_difference
+        | StorageFormat::_8bit_flac
+        | StorageFormat::_16bit_flac
+        | StorageFormat::_24bit_flac => None,
+    }
+}
+
+/// Decode the contents of the signal file holding `signals`, returning the samples of those
+/// signals interleaved.
+fn parse_signal_file(signals: &[&SignalSpecLine], buf: &[u8]) -> io::Result<Vec<i32>> {
+    let format = signals[0].format;
+    match format {
+        StorageFormat::_null => Ok(vec![]),
+        StorageFormat::_8bit_first_difference => {
+            let initial_values: Vec<i32> = signals
+                .iter()
+                .map(|signal| signal.initial_value.unwrap_or(0))
+                .collect();
+            let samples_per_frame: Vec<usize> = signals.iter().map(|signal| signal.spf()).collect();
+            let mut decoder = signal::FirstDifferenceDecoder::with_samples_per_frame(
+                &initial_values,
+                &samples_per_frame,
+            )?;
+            Ok(decoder.decode(buf))
+        }
+        StorageFormat::_16bit_twos_complement => Ok(signal::parse_16_format(buf)),
+        StorageFormat::_24bit_twos_complement_lsb => Ok(signal::parse_24_format(buf)),
+        StorageFormat::_32bit_twos_complement_lsb => Ok(signal::parse_32_format(buf)),
+        StorageFormat::_16bit_twos_complement_msb => Ok(signal::parse_61_format(buf)),
+        StorageFormat::_8bit_offset_binary => Ok(signal::parse_80_format(buf)),
+        StorageFormat::_16bit_offset_binary => Ok(signal::parse_160_format(buf)),
+        StorageFormat::_12bit_twos_complement => Ok(signal::parse_212_format(buf)),
+        StorageFormat::_10bit_twos_complement_sets_of_11 => Ok(signal::parse_310_format(buf)),
+        StorageFormat::_10bit_twos_complement_sets_of_4 => Ok(signal::parse_311_format(buf)),
+        #[cfg(feature = "flac")]
+        StorageFormat::_8bit_flac | StorageFormat::_16bit_flac | StorageFormat::_24bit_flac => {
+            if signals.iter().any(|signal| signal.spf() > 1) {
+                return Err(flac_frames_unsupported());
+            }
+            let (samples, channels) = signal::flac::decode(buf)?;
+            if channels != signals.len() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "FLAC signal file has {} channels but the header lists {} signals",
+                        channels,
+                        signals.len()
+                    ),
+                ));
+            }
+            let bits_per_sample = signal::flac::bits_per_sample(format)
+                .expect("Formats 508, 516 and 524 are FLAC formats");
+            let invalid_value = -(1 << (bits_per_sample - 1));
+            Ok(samples
+                .into_iter()
+                .map(|sample| signal::mark_invalid(sample, invalid_value))
+                .collect())
+        }
+        #[cfg(not(feature = "flac"))]
+        StorageFormat::_8bit_flac | StorageFormat::_16bit_flac | StorageFormat::_24bit_flac => {
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "FLAC-compressed signal files require the `flac` feature",
+            ))
+        }
+    }
+}
+
+/// Error for FLAC signal files holding signals with several samples per frame, which would need
+/// one FLAC channel per sample of a frame.
+#[cfg(feature = "flac")]
+fn flac_frames_unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "FLAC signal files with several samples per frame are not supported",
+    )
+}
+
+/// Resolve the header file for `record_name`, appending `.hea` unless the name already refers to a
+/// header file.
+pub(crate) fn header_path(record_name: &str) -> String {
+    if record_name.ends_with(".hea") {
+        String::from(record_name)
+    } else {
+        format!("{}.hea", record_name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStorage;
+
+    #[test]
+    fn header_path_appends_extension() {
+        assert_eq!(header_path("100"), "100.hea");
+        assert_eq!(header_path("mitdb/100"), "mitdb/100.hea");
+        assert_eq!(header_path("mitdb/100.hea"), "mitdb/100.hea");
+    }
+
+    #[test]
+    fn record_from_memory() {
+        let header = "100 2 360 2
+            100.dat 212 200 11 1024 995 -22131 0 MLII
+            100.dat 212 200 11 1024 1011 20052 0 V5";
+        let record = Record::from_reader(header.as_bytes(), |filename| {
+            assert_eq!(filename, "100.dat");
+            Ok(vec![0xF0, 0x68, 0x80, 0xFF, 0x8F, 0x80])
+        })
+        .unwrap();
+        assert_eq!(record.header.record.record_name, "100");
+        assert_eq!(record.signals, vec![vec![-1808, -1], vec![1664, -1920]]);
+    }
+
+    #[test]
+    fn record_from_storage_subdirectory() {
+        let mut storage = MemoryStorage::new();
+        storage.insert(
+            "mitdb/100.hea",
+            b"100 1 360 2\n100.dat 212 200 11 1024 995 -22131 0 MLII\n".to_vec(),
+        );
+        storage.insert("mitdb/100.dat", vec![0xF0, 0x68, 0x80]);
+        let record = Record::open_from(&storage, "mitdb/100").unwrap();
+        assert_eq!(record.signals, vec![vec![-1808, 1664]]);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn record_from_compressed_files() {
+        use std::io::Write;
+        let gzip = |buf: &[u8]| {
+            let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+            encoder.write_all(buf).unwrap();
+            encoder.finish().unwrap()
+        };
+        let mut storage = MemoryStorage::new();
+        storage.insert(
+            "100.hea.gz",
+            gzip(b"100 1 360 2\n100.dat 212 200 11 1024 995 -22131 0 MLII\n"),
+        );
+        storage.insert("100.dat.gz", gzip(&[0xF0, 0x68, 0x80]));
+        let record = Record::open_from(&storage, "100").unwrap();
+        assert_eq!(record.signals, vec![vec![-1808, 1664]]);
+    }
+
+    #[cfg(feature = "flac")]
+    #[test]
+    fn record_from_flac_file() {
+        let header = "s0010 3 1000 3
+            s0010.dat 516 2000 16 0 0 0 0 i
+            s0010.dat 516 2000 16 0 0 0 0 ii
+            s0010_vcg.dat 508 2000 8 0 0 0 0 vx";
+        let record = Record::from_header_str(header, |filename| {
+            Ok(match filename {
+                "s0010.dat" => signal::flac::encode(
+                    &[vec![1, 2, 3], vec![-1, -2, -32768]],
+                    StorageFormat::_16bit_flac,
+                    1000.0,
+                )?,
+                _ => signal::flac::encode(
+                    &[vec![127, -128, -127]],
+                    StorageFormat::_8bit_flac,
+                    1000.0,
+                )?,
+            })
+        })
+        .unwrap();
+        // -32768 and -128 are the invalid values of formats 516 and 508
+        assert_eq!(
+            record.signals,
+            vec![
+                vec![1, 2, 3],
+                vec![-1, -2, signal::INVALID_SAMPLE],
+                vec![127, signal::INVALID_SAMPLE, -127]
+            ]
+        );
+
+        let header = "s0010 1 1000 3
+            s0010.dat 516 2000 16 0 0 0 0 i";
+        let result = Record::from_header_str(header, |_| {
+            signal::flac::encode(
+                &[vec![1, 2, 3], vec![-1, -2, -3]],
+                StorageFormat::_16bit_flac,
+                1000.0,
+            )
+        });
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn record_with_null_signal() {
+        let header = "100 3 360 2
+            ~ 0 200 11 1024 0 0 0 ABP
+            100.dat 212 200 11 1024 995 -22131 0 MLII
+            100.dat 212 200 11 1024 1011 20052 0 V5";
+        let record = Record::from_header_str(header, |filename| {
+            assert_eq!(filename, "100.dat");
+            Ok(vec![0xF0, 0x68, 0x80, 0xFF, 0x8F, 0x80])
+        })
+        .unwrap();
+        assert_eq!(
+            record.signals,
+            vec![
+                vec![signal::INVALID_SAMPLE; 2],
+                vec![-1808, -1],
+                vec![1664, -1920]
+            ]
+        );
+    }
+
+    #[test]
+    fn record_samples_skip_invalid() {
+        let header = "100 1 360 3
+            100.dat 16 200(10)/mV 16 0 0 0 0 ECG";
+        let record =
+            Record::from_header_str(header, |_| Ok(vec![0x0A, 0x00, 0x00, 0x80, 0x32, 0x00]))
+                .unwrap();
+        assert_eq!(
+            record.samples(0).collect::<Vec<_>>(),
+            vec![Some(10), None, Some(50)]
+        );
+        assert_eq!(record.physical_samples(0), vec![Some(0.0), None, Some(0.2)]);
+        assert_eq!(record.statistics(0).mean, Some(30.0));
+        assert_eq!(record.statistics(0).invalid, 1);
+    }
+
+    #[test]
+    fn record_in_first_difference_format() {
+        let header = "e0103 2 250 3
+            e0103.dat 8 200 12 0 -53 0 0 MLIII
+            e0103.dat 8 200 12 0 100 0 0 V4";
+        let record =
+            Record::from_header_str(header, |_| Ok(vec![0x03, 0xFF, 0x03, 0xFF, 0x80, 0x7F]))
+                .unwrap();
+        assert_eq!(
+            record.signals,
+            vec![vec![-50, -47, -175], vec![99, 98, 225]]
+        );
+    }
+
+    #[test]
+    fn record_helpers() {
+        let header = "100 2 4 3
+            100.dat 24 200 24 0 0 0 0 MLII
+            100.dat 24 200 24 0 0 0 0 V5";
+        let buf = vec![
+            0xFF, 0xFF, 0x7F, 0x01, 0x00, 0x00, //
+            0x00, 0x00, 0x00, 0x02, 0x00, 0x00, //
+            0x00, 0x00, 0x80, 0x03, 0x00, 0x00,
+        ];
+        let record = Record::from_header_str(header, |_| Ok(buf.clone())).unwrap();
+        assert_eq!(
+            record.signal("MLII"),
+            Some(&[8_388_607, 0, signal::INVALID_SAMPLE][..])
+        );
+        assert_eq!(record.signal("V5"), Some(&[1, 2, 3][..]));
+        assert_eq!(record.signal("V1"), None);
+        assert_eq!(record.signal_index("V5"), Some(1));
+        assert_eq!(record.num_samples(), 3);
+        assert_eq!(record.duration(), 0.75);
+        assert_eq!(record.time_of(2), 0.5);
+        assert_eq!(record.sample_at(0.5), 2);
+        assert_eq!(record.times().collect::<Vec<_>>(), vec![0.0, 0.25, 0.5]);
+    }
+
+    #[test]
+    fn record_with_byte_offset() {
+        let header = "100 1 360 2
+            100.dat 212+4 200 11 1024 995 -22131 0 MLII";
+        let record =
+            Record::from_header_str(header, |_| Ok(vec![0, 0, 0, 0, 0xF0, 0x68, 0x80])).unwrap();
+        assert_eq!(record.signals, vec![vec![-1808, 1664]]);
+    }
+
+    #[test]
+    fn record_from_block_device() {
+        let mut storage = MemoryStorage::new();
+        storage.insert(
+            "100.hea",
+            b"100 1 360 4\n100.dat 212 200 11 1024 995 -22131 4 MLII\n".to_vec(),
+        );
+        storage.insert("100.dat", vec![0xF0, 0x68, 0x80, 0xFF, 0x8F, 0x80]);
+        let record = Record::open_from(&storage, "100").unwrap();
+        assert_eq!(record.signals, vec![vec![-1808, 1664, -1, -1920]]);
+    }
+
+    #[test]
+    fn decode_frame_range() {
+        let header = header::read_header(
+            "rec 4 360 4
+rec.dat 212 200 11 0 0 0 0 A
+rec.dat 212 200 11 0 0 0 0 B
+mono.dat 16+2 200 16 0 0 0 0 C
+diff.dat 8 200 8 0 100 0 0 D",
+        )
+        .unwrap();
+        let mut requests = vec![];
+        let files = SignalFiles::decode_range(&header, 1..3, |filename, offset, len| {
+            requests.push((String::from(filename), offset, len));
+            Ok(match filename {
+                "rec.dat" => vec![1, 0, 2, 3, 0, 4, 5, 0, 6, 7, 0, 8][offset as usize..]
+                    [..len as usize]
+                    .to_vec(),
+                "mono.dat" => vec![0xAA, 0xAA, 10, 0, 20, 0, 30, 0, 40, 0][offset as usize..]
+                    [..len as usize]
+                    .to_vec(),
+                _ => byte_range(vec![1, 1, 1, 1], offset, len),
+            })
+        })
+        .unwrap();
+        assert_eq!(
+            requests,
+            vec![
+                (String::from("rec.dat"), 3, 6),
+                (String::from("mono.dat"), 4, 4),
+                (String::from("diff.dat"), 0, u64::MAX),
+            ]
+        );
+        let samples = |signal| {
+            (0..4)
+                .map(|idx| files.sample(signal, idx))
+                .collect::<Vec<_>>()
+        };
+        let invalid = signal::INVALID_SAMPLE;
+        assert_eq!(samples(0), vec![invalid, 3, 5, invalid]);
+        assert_eq!(samples(1), vec![invalid, 4, 6, invalid]);
+        assert_eq!(samples(2), vec![invalid, 20, 30, invalid]);
+        assert_eq!(samples(3), vec![invalid, 102, 103, invalid]);
+        assert_eq!(files.len(), 4);
+    }
+
+    #[test]
+    fn missing_signal_file() {
+        let header = "100 1 360
+            100.dat 212 200 11 1024 995 -22131 0 MLII";
+        let result =
+            Record::from_header_str(header, |_| Err(io::Error::from(io::ErrorKind::NotFound)));
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn write_and_read_back() {
+        let header = "rec 3 500 4\nrec.dat 16 200(5)/mV 16 0 0 0 0 ECG\nrec.dat 16 50 16 0 0 0 0 RESP\nbig.dat 32 1 32 0 0 0 0 COUNT\n";
+        let record = Record::from_header(header::read_header(header).unwrap(), |filename| {
+            Ok(match filename {
+                "rec.dat" => {
+                    signal::encode_16_format(&[1, 2, 3, signal::INVALID_SAMPLE, 5, 6, 7, 8])
+                }
+                _ => signal::encode_32_format(&[100_000, -100_000, 0, 1]),
+            })
+        })
+        .unwrap();
+
+        let mut storage = MemoryStorage::new();
+        let mut file_count = 0;
+        record
+            .write_with(|filename, contents| {
+                storage.insert(filename, contents.to_vec());
+                file_count += 1;
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(file_count, 3);
+        let read_back = Record::open_from(&storage, "rec").unwrap();
+        assert_eq!(read_back, record);
+
+        let mut record = read_back;
+        record.header.signal_specs[1].format = StorageFormat::_32bit_twos_complement_lsb;
+        let error = record.write_with(|_, _| Ok(())).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        record.header.signal_specs[0].format = StorageFormat::_12bit_twos_complement;
+        record.header.signal_specs[1].format = StorageFormat::_12bit_twos_complement;
+        let error = record.write_with(|_, _| Ok(())).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::Unsupported);
+    }
+
+    #[cfg(feature = "flac")]
+    #[test]
+    fn write_invalid_flac_signals() {
+        let header = format!("rec 9 250 3\n{}", "rec.dat 516 200 16 0 0 0 0\n".repeat(9));
+        let mut record = Record {
+            header: header::read_header(&header).unwrap(),
+            signals: vec![vec![1, 2, 3]; 9],
+        };
+        let error = record.write_with(|_, _| Ok(())).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+
+        record.header.signal_specs.truncate(1);
+        record.header.record.number_of_signals = 1;
+        record.signals = vec![vec![40_000, 1, 2]];
+        let error = record.write_with(|_, _| Ok(())).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -1,4 +1,67 @@
 //! Parsing logic for each signal format type
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::header::{HeaderError, StorageFormat};
+
+#[cfg(feature = "flac")]
+pub mod flac;
+
+/// Value used for samples that are missing or could not be recorded, and for every sample of a
+/// signal stored in format 0.
+///
+/// Each storage format reserves its own most negative value to mark invalid samples (see
+/// [`invalid_value`]); the parsers below map that value to `INVALID_SAMPLE` and the encoders map
+/// it back. `INVALID_SAMPLE` is format 32's reserved value, so it never collides with a valid
+/// sample of any format.
+pub const INVALID_SAMPLE: i32 = i32::MIN;
+
+/// The value a storage format reserves for invalid samples, or `None` for formats without one.
+pub fn invalid_value(format: StorageFormat) -> Option<i32> {
+    match format {
+        StorageFormat::_8bit_offset_binary | StorageFormat::_8bit_flac => Some(-(1 << 7)),
+        StorageFormat::_10bit_twos_complement_sets_of_11
+        | StorageFormat::_10bit_twos_complement_sets_of_4 => Some(-(1 << 9)),
+        StorageFormat::_12bit_twos_complement => Some(-(1 << 11)),
+        StorageFormat::_16bit_twos_complement
+        | StorageFormat::_16bit_twos_complement_msb
+        | StorageFormat::_16bit_offset_binary
+        | StorageFormat::_16bit_flac => Some(-(1 << 15)),
+        StorageFormat::_24bit_twos_complement_lsb | StorageFormat::_24bit_flac => Some(-(1 << 23)),
+        StorageFormat::_32bit_twos_complement_lsb => Some(i32::MIN),
+        StorageFormat::_null | StorageFormat::_8bit_first_difference => None,
+    }
+}
+
+/// Whether a sample holds a valid value, rather than [`INVALID_SAMPLE`].
+pub fn is_valid(sample: i32) -> bool {
+    sample != INVALID_SAMPLE
+}
+
+/// Map a format's reserved invalid value to [`INVALID_SAMPLE`].
+pub(crate) fn mark_invalid(sample: i32, invalid_value: i32) -> i32 {
+    if sample == invalid_value {
+        INVALID_SAMPLE
+    } else {
+        sample
+    }
+}
+
+/// The average of a signal's valid samples in one frame, rounded to the nearest integer, as
+/// returned for signals with several samples per frame outside high-resolution mode. Frames
+/// without valid samples average to [`INVALID_SAMPLE`].
+pub fn frame_average(samples: &[i32]) -> i32 {
+    let valid = samples.iter().filter(|&&sample| is_valid(sample));
+    let (sum, count) = valid.fold((0_i64, 0_i64), |(sum, count), &sample| {
+        (sum + sample as i64, count + 1)
+    });
+    // Round halves away from zero, as f64::round does
+    match count {
+        0 => INVALID_SAMPLE,
+        _ if sum < 0 => -((-2 * sum + count) / (2 * count)) as i32,
+        _ => ((2 * sum + count) / (2 * count)) as i32,
+    }
+}
 
 /// Parse a byte buffer of data in format 212, 12-bit two's complement amplitude.
 ///
@@ -8,7 +71,7 @@
 /// | 8 7 6 5 4 3 2 1 | 12 11 10 9 12 11 10 9 | 8 7 6 5 4 3 2 1 |
 /// ```
 ///
-pub fn parse_212_format(buf: &[u8]) -> Vec<i16> {
+pub fn parse_212_format(buf: &[u8]) -> Vec<i32> {
     let mut output_buf = vec![];
     for idx in (0..buf.len()).step_by(3) {
         if idx + 1 >= buf.len() { break }
@@ -18,7 +81,7 @@ pub fn parse_212_format(buf: &[u8]) -> Vec<i16> {
             // Extend two's complement sign bits if last bit is 1
             sample_1_upper |= 0xF000;
         }
-        output_buf.push((sample_1_lower | sample_1_upper) as i16);
+        output_buf.push(mark_invalid((sample_1_lower | sample_1_upper) as i16 as i32, -2048));
 
         if idx + 2 >= buf.len() { break }
         let sample_2_lower = buf[idx+2] as u16;
@@ -27,11 +90,265 @@ pub fn parse_212_format(buf: &[u8]) -> Vec<i16> {
             // Extend two's complement sign bits if last bit is 1
             sample_2_upper |= 0xF000;
         }
-        output_buf.push((sample_2_lower | sample_2_upper) as i16);
+        output_buf.push(mark_invalid((sample_2_lower | sample_2_upper) as i16 as i32, -2048));
+    }
+    output_buf
+}
+
+/// Stateful decoder for data in format 8, 8-bit first differences.
+///
+/// Each byte holds the difference between a sample and the previous sample of the same signal,
+/// starting from the signal's initial value given in the header. The decoder keeps the current
+/// value of every signal in the file, so a file can be decoded in pieces, or skipped over to seek
+/// forward, with the samples of its signals interleaved frame by frame. Values that would
+/// overflow 16 bits are clipped to the 16-bit range.
+#[derive(Clone, PartialEq, Debug)]
+pub struct FirstDifferenceDecoder {
+    values: Vec<i16>,
+    /// The signal of each sample in a frame.
+    frame: Vec<usize>,
+    next_sample: usize,
+}
+
+impl FirstDifferenceDecoder {
+    /// Create a decoder for a file holding one signal per initial value, in file order. Fails if
+    /// no initial values are given, as a file must hold at least one signal.
+    pub fn new(initial_values: &[i32]) -> Result<FirstDifferenceDecoder, HeaderError> {
+        FirstDifferenceDecoder::with_samples_per_frame(
+            initial_values,
+            &vec![1; initial_values.len()],
+        )
+    }
+
+    /// Create a decoder for a file whose frames hold `samples_per_frame[i]` consecutive samples of
+    /// the signal with initial value `initial_values[i]`, in file order.
+    pub fn with_samples_per_frame(
+        initial_values: &[i32],
+        samples_per_frame: &[usize],
+    ) -> Result<FirstDifferenceDecoder, HeaderError> {
+        let frame: Vec<usize> = samples_per_frame
+            .iter()
+            .enumerate()
+            .flat_map(|(signal, &spf)| core::iter::repeat_n(signal, spf))
+            .collect();
+        if frame.is_empty() {
+            return Err(HeaderError("format 8 signal file without signals."));
+        }
+        Ok(FirstDifferenceDecoder {
+            values: initial_values
+                .iter()
+                .map(|&value| value.clamp(i16::MIN as i32, i16::MAX as i32) as i16)
+                .collect(),
+            frame,
+            next_sample: 0,
+        })
+    }
+
+    /// The value of each signal after the last difference read.
+    pub fn values(&self) -> &[i16] {
+        &self.values
+    }
+
+    fn apply(&mut self, difference: u8) -> i16 {
+        let signal = self.frame[self.next_sample];
+        self.values[signal] = self.values[signal].saturating_add(difference as i8 as i16);
+        self.next_sample = (self.next_sample + 1) % self.frame.len();
+        self.values[signal]
+    }
+
+    /// Decode the next bytes of the file, returning the samples interleaved as they are stored.
+    pub fn decode(&mut self, buf: &[u8]) -> Vec<i32> {
+        buf.iter()
+            .map(|&difference| self.apply(difference) as i32)
+            .collect()
+    }
+
+    /// Read past the next bytes of the file without returning their samples, keeping the state of
+    /// every signal up to date.
+    pub fn skip(&mut self, buf: &[u8]) {
+        for &difference in buf {
+            self.apply(difference);
+        }
+    }
+}
+
+/// Parse a byte buffer of data in format 8, 8-bit first differences, holding one signal for each
+/// initial value.
+pub fn parse_8_format(buf: &[u8], initial_values: &[i32]) -> Result<Vec<i32>, HeaderError> {
+    Ok(FirstDifferenceDecoder::new(initial_values)?.decode(buf))
+}
+
+/// Parse a byte buffer of data in format 16, 16-bit two's complement amplitude stored least
+/// significant byte first.
+pub fn parse_16_format(buf: &[u8]) -> Vec<i32> {
+    buf.chunks_exact(2)
+        .map(|b| mark_invalid(i16::from_le_bytes([b[0], b[1]]) as i32, -32768))
+        .collect()
+}
+
+/// Parse a byte buffer of data in format 61, 16-bit two's complement amplitude stored most
+/// significant byte first.
+pub fn parse_61_format(buf: &[u8]) -> Vec<i32> {
+    buf.chunks_exact(2)
+        .map(|b| mark_invalid(i16::from_be_bytes([b[0], b[1]]) as i32, -32768))
+        .collect()
+}
+
+/// Parse a byte buffer of data in format 80, 8-bit offset binary amplitude (the stored value is
+/// the amplitude plus 128).
+pub fn parse_80_format(buf: &[u8]) -> Vec<i32> {
+    buf.iter()
+        .map(|&b| mark_invalid(b as i32 - 128, -128))
+        .collect()
+}
+
+/// Parse a byte buffer of data in format 160, 16-bit offset binary amplitude (the stored value is
+/// the amplitude plus 32768) stored least significant byte first.
+pub fn parse_160_format(buf: &[u8]) -> Vec<i32> {
+    buf.chunks_exact(2)
+        .map(|b| mark_invalid(u16::from_le_bytes([b[0], b[1]]) as i32 - 32768, -32768))
+        .collect()
+}
+
+/// Parse a byte buffer of data in format 24, 24-bit two's complement amplitude stored least
+/// significant byte first.
+pub fn parse_24_format(buf: &[u8]) -> Vec<i32> {
+    buf.chunks_exact(3)
+        .map(|b| mark_invalid(i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8, -(1 << 23)))
+        .collect()
+}
+
+/// Parse a byte buffer of data in format 32, 32-bit two's complement amplitude stored least
+/// significant byte first.
+pub fn parse_32_format(buf: &[u8]) -> Vec<i32> {
+    buf.chunks_exact(4)
+        .map(|b| mark_invalid(i32::from_le_bytes([b[0], b[1], b[2], b[3]]), i32::MIN))
+        .collect()
+}
+
+/// Encode samples in format 16, storing invalid samples as format 16's invalid value. Valid
+/// samples outside the 16-bit range are clipped to the range of valid values.
+pub fn encode_16_format(samples: &[i32]) -> Vec<u8> {
+    samples
+        .iter()
+        .flat_map(|&s| {
+            if is_valid(s) {
+                s.clamp(i16::MIN as i32 + 1, i16::MAX as i32) as i16
+            } else {
+                i16::MIN
+            }
+            .to_le_bytes()
+        })
+        .collect()
+}
+
+/// Encode samples in format 32, storing invalid samples as format 32's invalid value.
+pub fn encode_32_format(samples: &[i32]) -> Vec<u8> {
+    samples
+        .iter()
+        .flat_map(|&s| if is_valid(s) { s } else { i32::MIN }.to_le_bytes())
+        .collect()
+}
+
+/// Sign-extend a 10-bit two's complement value and mark it invalid if needed.
+fn from_10bit(value: u16) -> i32 {
+    mark_invalid(((value << 6) as i16 >> 6) as i32, -512)
+}
+
+/// Parse a byte buffer of data in format 310, 10-bit two's complement amplitude.
+///
+/// Three 10-bit samples in two 16-bit little-endian words. The first and second samples are in
+/// bits 1-10 of the first and second words; the third sample's low 5 bits are in bits 11-15 of
+/// the first word and its high 5 bits in bits 11-15 of the second word.
+pub fn parse_310_format(buf: &[u8]) -> Vec<i32> {
+    let mut output_buf = vec![];
+    for idx in (0..buf.len()).step_by(4) {
+        if idx + 1 >= buf.len() {
+            break;
+        }
+        let word_1 = u16::from_le_bytes([buf[idx], buf[idx + 1]]);
+        output_buf.push(from_10bit((word_1 >> 1) & 0x03FF));
+
+        if idx + 3 >= buf.len() {
+            break;
+        }
+        let word_2 = u16::from_le_bytes([buf[idx + 2], buf[idx + 3]]);
+        output_buf.push(from_10bit((word_2 >> 1) & 0x03FF));
+        output_buf.push(from_10bit((word_1 >> 11) | ((word_2 >> 11) << 5)));
     }
     output_buf
 }
 
+/// Parse a byte buffer of data in format 311, 10-bit two's complement amplitude.
+///
+/// Three 10-bit samples in one 32-bit little-endian word, in bits 0-9, 10-19 and 20-29.
+pub fn parse_311_format(buf: &[u8]) -> Vec<i32> {
+    let mut output_buf = vec![];
+    for idx in (0..buf.len()).step_by(4) {
+        let available = (buf.len() - idx).min(4);
+        let mut bytes = [0; 4];
+        bytes[..available].copy_from_slice(&buf[idx..idx + available]);
+        let word = u32::from_le_bytes(bytes);
+        // Each sample is complete once the byte holding its highest bits has been read
+        for (sample, last_byte) in [(0, 1), (1, 2), (2, 3)] {
+            if last_byte >= available {
+                break;
+            }
+            output_buf.push(from_10bit(((word >> (10 * sample)) & 0x03FF) as u16));
+        }
+    }
+    output_buf
+}
+
+/// The checksum of a signal as given in its header: the sum of its samples, modulo 2^16, with
+/// invalid samples counted as -32768 as in the WFDB library.
+pub fn checksum(samples: &[i32]) -> i16 {
+    samples.iter().fold(0_i16, |sum, &sample| {
+        sum.wrapping_add(if is_valid(sample) {
+            sample as i16
+        } else {
+            i16::MIN
+        })
+    })
+}
+
+/// Summary statistics of a signal, computed over its valid samples only.
+#[derive(PartialEq, Debug)]
+pub struct Statistics {
+    /// Number of valid samples.
+    pub count: usize,
+    /// Number of samples equal to [`INVALID_SAMPLE`].
+    pub invalid: usize,
+    pub min: Option<i32>,
+    pub max: Option<i32>,
+    pub mean: Option<f64>,
+}
+
+/// Compute summary statistics of a signal, skipping invalid samples.
+pub fn statistics(samples: &[i32]) -> Statistics {
+    let mut count = 0;
+    let mut sum = 0_i64;
+    let mut min = None;
+    let mut max = None;
+    for &sample in samples.iter().filter(|&&sample| is_valid(sample)) {
+        count += 1;
+        sum += sample as i64;
+        min = Some(min.map_or(sample, |min: i32| min.min(sample)));
+        max = Some(max.map_or(sample, |max: i32| max.max(sample)));
+    }
+    Statistics {
+        count,
+        invalid: samples.len() - count,
+        min,
+        max,
+        mean: if count > 0 {
+            Some(sum as f64 / count as f64)
+        } else {
+            None
+        },
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -52,9 +369,7 @@ mod test {
 
     #[test]
     fn incomplete_buffer() {
-        let byte_buf = [
-            0xF0, 0x86,
-        ];
+        let byte_buf = [0xF0, 0x86];
         assert_eq!(
             parse_212_format(&byte_buf),
             vec![
@@ -65,9 +380,7 @@ mod test {
 
     #[test]
     fn negative_values_buffer() {
-        let byte_buf = [
-            0xFF, 0x8F, 0x80
-        ];
+        let byte_buf = [0xFF, 0x8F, 0x80];
         assert_eq!(
             parse_212_format(&byte_buf),
             vec![
@@ -75,4 +388,175 @@ mod test {
             ]
         );
     }
+
+    #[test]
+    fn invalid_212_samples() {
+        let byte_buf = [0x00, 0x88, 0x00];
+        assert_eq!(
+            parse_212_format(&byte_buf),
+            vec![
+                INVALID_SAMPLE,
+                INVALID_SAMPLE // 0x800, 0x800
+            ]
+        );
+    }
+
+    #[test]
+    fn first_differences() {
+        let byte_buf = [
+            0x01, 0xFF, // 11, -6
+            0x7F, 0x80, // 138, -134
+            0xFE, 0x00, // 136, -134
+        ];
+        assert_eq!(
+            parse_8_format(&byte_buf, &[10, -5]),
+            Ok(vec![11, -6, 138, -134, 136, -134])
+        );
+        assert!(parse_8_format(&byte_buf, &[]).is_err());
+    }
+
+    #[test]
+    fn first_differences_clip() {
+        assert_eq!(
+            parse_8_format(&[0x7F, 0x7F, 0x80], &[32700]),
+            Ok(vec![32767, 32767, 32639])
+        );
+        assert_eq!(parse_8_format(&[0x80], &[-40000]), Ok(vec![-32768]));
+    }
+
+    #[test]
+    fn first_differences_across_reads_and_seeks() {
+        let byte_buf = [
+            0x01, 0x02, 0x03, // 1, 2, 3
+            0x01, 0x02, 0x03, // 2, 4, 6
+            0x01, 0x02, 0x03, // 3, 6, 9
+            0xFF, 0xFE, 0xFD, // 2, 4, 6
+        ];
+        let mut decoder = FirstDifferenceDecoder::new(&[0, 0, 0]).unwrap();
+        assert_eq!(decoder.decode(&byte_buf[..4]), vec![1, 2, 3, 2]);
+        assert_eq!(decoder.decode(&byte_buf[4..6]), vec![4, 6]);
+        decoder.skip(&byte_buf[6..9]);
+        assert_eq!(decoder.values(), &[3, 6, 9]);
+        assert_eq!(decoder.decode(&byte_buf[9..]), vec![2, 4, 6]);
+    }
+
+    #[test]
+    fn first_differences_with_samples_per_frame() {
+        // Frames of two samples of the first signal and one of the second
+        let byte_buf = [0x01, 0x01, 0x05, 0xFF, 0xFF, 0x05];
+        let mut decoder =
+            FirstDifferenceDecoder::with_samples_per_frame(&[0, 100], &[2, 1]).unwrap();
+        assert_eq!(decoder.decode(&byte_buf), vec![1, 2, 105, 1, 0, 110]);
+        assert!(FirstDifferenceDecoder::with_samples_per_frame(&[0], &[0]).is_err());
+    }
+
+    #[test]
+    fn frame_averages() {
+        assert_eq!(frame_average(&[10, 11]), 11);
+        assert_eq!(frame_average(&[-10, -11]), -11);
+        assert_eq!(frame_average(&[4, INVALID_SAMPLE, 6]), 5);
+        assert_eq!(frame_average(&[INVALID_SAMPLE]), INVALID_SAMPLE);
+        assert_eq!(frame_average(&[]), INVALID_SAMPLE);
+    }
+
+    #[test]
+    fn sixteen_bit_formats() {
+        let byte_buf = [0x34, 0x12, 0x00, 0x80, 0xFF, 0xFF];
+        assert_eq!(parse_16_format(&byte_buf), vec![0x1234, INVALID_SAMPLE, -1]);
+        assert_eq!(parse_61_format(&byte_buf), vec![0x3412, 0x0080, -1]);
+        assert_eq!(parse_160_format(&byte_buf), vec![-28108, 0, 32767]);
+        assert_eq!(parse_160_format(&[0x00, 0x00]), vec![INVALID_SAMPLE]);
+    }
+
+    #[test]
+    fn eight_bit_offset_binary() {
+        assert_eq!(
+            parse_80_format(&[0x80, 0xFF, 0x01, 0x00]),
+            vec![0, 127, -127, INVALID_SAMPLE]
+        );
+    }
+
+    #[test]
+    fn wide_formats() {
+        assert_eq!(
+            parse_24_format(&[0x56, 0x34, 0x12, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x80, 0x01]),
+            vec![0x123456, -1, INVALID_SAMPLE]
+        );
+        assert_eq!(
+            parse_32_format(&[0x78, 0x56, 0x34, 0x12, 0x00, 0x00, 0x00, 0x80]),
+            vec![0x12345678, INVALID_SAMPLE]
+        );
+        // -32768 is a valid sample in the wide formats
+        assert_eq!(parse_24_format(&[0x00, 0x80, 0xFF]), vec![-32768]);
+        assert_eq!(parse_32_format(&(-32768_i32).to_le_bytes()), vec![-32768]);
+    }
+
+    #[test]
+    fn ten_bit_formats() {
+        // Samples 1, -1 and -511, then 511 from an incomplete group
+        let word_1: u16 = (1 << 1) | ((0x201 & 0x1F) << 11);
+        let word_2: u16 = (0x3FF << 1) | ((0x201 >> 5) << 11);
+        let mut byte_buf = vec![];
+        byte_buf.extend_from_slice(&word_1.to_le_bytes());
+        byte_buf.extend_from_slice(&word_2.to_le_bytes());
+        byte_buf.extend_from_slice(&(0x1FF_u16 << 1).to_le_bytes());
+        assert_eq!(parse_310_format(&byte_buf), vec![1, -1, -511, 511]);
+
+        let word: u32 = 1 | (0x3FF << 10) | (0x200 << 20);
+        let mut byte_buf = word.to_le_bytes().to_vec();
+        byte_buf.extend_from_slice(&[0xFF, 0x01]);
+        assert_eq!(
+            parse_311_format(&byte_buf),
+            vec![1, -1, INVALID_SAMPLE, 511]
+        );
+    }
+
+    #[test]
+    fn statistics_skip_invalid_samples() {
+        assert_eq!(
+            statistics(&[4, INVALID_SAMPLE, -2, 7, INVALID_SAMPLE]),
+            Statistics {
+                count: 3,
+                invalid: 2,
+                min: Some(-2),
+                max: Some(7),
+                mean: Some(3.0)
+            }
+        );
+        assert_eq!(
+            statistics(&[INVALID_SAMPLE]),
+            Statistics {
+                count: 0,
+                invalid: 1,
+                min: None,
+                max: None,
+                mean: None
+            }
+        );
+    }
+
+    #[test]
+    fn encode_formats() {
+        let samples = vec![0, -1, 40_000, INVALID_SAMPLE, 1234, -40_000];
+        assert_eq!(
+            parse_16_format(&encode_16_format(&samples)),
+            vec![0, -1, 32767, INVALID_SAMPLE, 1234, -32767]
+        );
+        let samples = vec![0, -1, 40_000, INVALID_SAMPLE, -2_000_000, -32768];
+        assert_eq!(parse_32_format(&encode_32_format(&samples)), samples);
+    }
+
+    #[test]
+    fn invalid_values() {
+        assert_eq!(
+            invalid_value(StorageFormat::_12bit_twos_complement),
+            Some(-2048)
+        );
+        assert_eq!(
+            invalid_value(StorageFormat::_24bit_twos_complement_lsb),
+            Some(-8_388_608)
+        );
+        assert_eq!(invalid_value(StorageFormat::_null), None);
+        assert_eq!(checksum(&[1, INVALID_SAMPLE, -1]), i16::MIN);
+    }
 }
@@ -0,0 +1,408 @@
+//! FLAC-compressed signal formats 508, 516 and 524.
+//!
+//! Each signal file is a FLAC stream with 8, 16 or 24 bits per sample, and each signal stored in
+//! the file is one channel of the stream, in the order the signals are listed in the header.
+//! Decoding is done with `claxon`; encoding uses fixed linear predictors with Rice-coded
+//! residuals, which FLAC decoders accept without any further configuration.
+use std::convert::TryFrom;
+use std::io::{self, Cursor};
+
+use crate::header::StorageFormat;
+
+/// Number of samples per channel in each FLAC frame written by [`encode`].
+const BLOCK_SIZE: usize = 4096;
+
+/// Largest Rice parameter that can be written with the 4-bit parameter field.
+const MAX_RICE_PARAMETER: u32 = 14;
+
+/// Number of bits per sample used by a FLAC storage format, or `None` for other formats.
+pub fn bits_per_sample(format: StorageFormat) -> Option<u32> {
+    match format {
+        StorageFormat::_8bit_flac => Some(8),
+        StorageFormat::_16bit_flac => Some(16),
+        StorageFormat::_24bit_flac => Some(24),
+        _ => None,
+    }
+}
+
+/// Decode a FLAC stream, returning its samples interleaved by channel (as for the other formats,
+/// where the samples of the signals in a file are stored in turn) and the number of channels.
+pub fn decode(buf: &[u8]) -> io::Result<(Vec<i32>, usize)> {
+    let invalid = |e: claxon::Error| io::Error::new(io::ErrorKind::InvalidData, e);
+    let mut reader = claxon::FlacReader::new(Cursor::new(buf)).map_err(invalid)?;
+    let channels = reader.streaminfo().channels as usize;
+    let samples = reader
+        .samples()
+        .collect::<Result<Vec<i32>, _>>()
+        .map_err(invalid)?;
+    Ok((samples, channels))
+}
+
+/// Encode signals (one per channel, all of the same length) as a FLAC stream in the given FLAC
+/// storage format.
+///
+/// Fails with an error of kind `InvalidInput` if `format` is not a FLAC format, if there are no
+/// signals or more than the 8 a stream can hold, if the signals differ in length, or if a sample
+/// does not fit in the format's bits per sample.
+pub fn encode(
+    signals: &[Vec<i32>],
+    format: StorageFormat,
+    sampling_frequency: f32,
+) -> io::Result<Vec<u8>> {
+    let invalid_input = |message: String| Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+    let bits_per_sample = match bits_per_sample(format) {
+        Some(bits_per_sample) => bits_per_sample,
+        None => return invalid_input(format!("format {} is not a FLAC format", format as u32)),
+    };
+    if signals.is_empty() || signals.len() > 8 {
+        return invalid_input(format!(
+            "a FLAC signal file holds between 1 and 8 signals, not {}",
+            signals.len()
+        ));
+    }
+    let length = signals[0].len();
+    if signals.iter().any(|signal| signal.len() != length) {
+        return invalid_input(String::from(
+            "all signals in a FLAC signal file must have the same length",
+        ));
+    }
+    let max = (1 << (bits_per_sample - 1)) - 1;
+    if let Some(&sample) = signals
+        .iter()
+        .flatten()
+        .find(|&&sample| sample < -max - 1 || sample > max)
+    {
+        return invalid_input(format!(
+            "{} does not fit in a sample of format {}",
+            sample, format as u32
+        ));
+    }
+
+    let mut writer = BitWriter::default();
+    writer.write_bytes(b"fLaC");
+    // STREAMINFO metadata block, flagged as the last metadata block
+    writer.write(1, 1);
+    writer.write(0, 7);
+    writer.write(34, 24);
+    writer.write(BLOCK_SIZE as u64, 16);
+    writer.write(BLOCK_SIZE as u64, 16);
+    writer.write(0, 24); // minimum frame size: unknown
+    writer.write(0, 24); // maximum frame size: unknown
+    writer.write(sampling_frequency.round().clamp(1.0, 655_350.0) as u64, 20);
+    writer.write(signals.len() as u64 - 1, 3);
+    writer.write(bits_per_sample as u64 - 1, 5);
+    writer.write(length as u64, 36);
+    writer.write_bytes(&[0; 16]); // MD5 signature: not computed
+
+    for (frame_number, start) in (0..length).step_by(BLOCK_SIZE).enumerate() {
+        let end = (start + BLOCK_SIZE).min(length);
+        let channels: Vec<&[i32]> = signals.iter().map(|signal| &signal[start..end]).collect();
+        write_frame(&mut writer, frame_number as u64, &channels, bits_per_sample);
+    }
+    Ok(writer.bytes)
+}
+
+fn write_frame(
+    writer: &mut BitWriter,
+    frame_number: u64,
+    channels: &[&[i32]],
+    bits_per_sample: u32,
+) {
+    let frame_start = writer.bytes.len();
+    writer.write(0b11_1111_1111_1110, 14);
+    writer.write(0, 1); // reserved
+    writer.write(0, 1); // fixed block size
+    writer.write(0b0111, 4); // block size stored as a 16-bit value after the frame number
+    writer.write(0b0000, 4); // sample rate taken from STREAMINFO
+    writer.write(channels.len() as u64 - 1, 4); // independent channels
+    writer.write(
+        match bits_per_sample {
+            8 => 0b001,
+            16 => 0b100,
+            _ => 0b110,
+        },
+        3,
+    );
+    writer.write(0, 1); // reserved
+    writer.write_utf8(frame_number);
+    writer.write(channels[0].len() as u64 - 1, 16);
+    let crc = crc8(&writer.bytes[frame_start..]);
+    writer.write(crc as u64, 8);
+
+    for channel in channels {
+        write_subframe(writer, channel, bits_per_sample);
+    }
+    writer.align();
+    let crc = crc16(&writer.bytes[frame_start..]);
+    writer.write(crc as u64, 16);
+}
+
+/// Compute the residual of the fixed polynomial predictor of the given order, if it fits in the
+/// 32 bits allowed for residuals.
+fn fixed_residual(samples: &[i32], order: usize) -> Option<Vec<i32>> {
+    let residual: Vec<i64> = (order..samples.len())
+        .map(|i| {
+            let x = |lag: usize| samples[i - lag] as i64;
+            match order {
+                0 => x(0),
+                1 => x(0) - x(1),
+                2 => x(0) - 2 * x(1) + x(2),
+                3 => x(0) - 3 * x(1) + 3 * x(2) - x(3),
+                _ => x(0) - 4 * x(1) + 6 * x(2) - 4 * x(3) + x(4),
+            }
+        })
+        .collect();
+    residual
+        .into_iter()
+        .map(|r| i32::try_from(r).ok())
+        .collect()
+}
+
+fn zigzag(residual: i32) -> u64 {
+    ((residual as i64) << 1 ^ (residual as i64) >> 63) as u64
+}
+
+/// Find the Rice parameter giving the smallest encoding of a residual, returning the parameter
+/// and the encoded size in bits.
+fn best_rice_parameter(residual: &[i32]) -> (u32, u64) {
+    (0..=MAX_RICE_PARAMETER)
+        .map(|k| {
+            let bits = residual
+                .iter()
+                .map(|&r| (zigzag(r) >> k) + 1 + k as u64)
+                .sum();
+            (k, bits)
+        })
+        .min_by_key(|&(_, bits)| bits)
+        .unwrap_or((0, 0))
+}
+
+/// Write a subframe header of the given type, with no wasted bits.
+fn write_subframe_header(writer: &mut BitWriter, subframe_type: u64) {
+    writer.write(0, 1);
+    writer.write(subframe_type, 6);
+    writer.write(0, 1);
+}
+
+fn write_subframe(writer: &mut BitWriter, samples: &[i32], bits_per_sample: u32) {
+    if samples.iter().all(|&s| s == samples[0]) {
+        write_subframe_header(writer, 0b000000);
+        writer.write_signed(samples[0], bits_per_sample);
+        return;
+    }
+
+    let verbatim_bits = samples.len() as u64 * bits_per_sample as u64;
+    let best = (0..=4.min(samples.len() - 1))
+        .filter_map(|order| {
+            let residual = fixed_residual(samples, order)?;
+            let (k, bits) = best_rice_parameter(&residual);
+            Some((
+                order,
+                residual,
+                k,
+                order as u64 * bits_per_sample as u64 + 10 + bits,
+            ))
+        })
+        .min_by_key(|&(_, _, _, bits)| bits);
+
+    match best {
+        Some((order, residual, k, bits)) if bits < verbatim_bits => {
+            write_subframe_header(writer, 0b001000 | order as u64);
+            for &sample in &samples[..order] {
+                writer.write_signed(sample, bits_per_sample);
+            }
+            writer.write(0b00, 2); // Rice coding with 4-bit parameters
+            writer.write(0, 4); // a single partition
+            writer.write(k as u64, 4);
+            for &r in &residual {
+                let u = zigzag(r);
+                writer.write_unary(u >> k);
+                writer.write(u & ((1 << k) - 1), k);
+            }
+        }
+        _ => {
+            write_subframe_header(writer, 0b000001);
+            for &sample in samples {
+                writer.write_signed(sample, bits_per_sample);
+            }
+        }
+    }
+}
+
+/// Writes values MSB-first into a byte buffer.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    accumulator: u64,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u64, bits: u32) {
+        for i in (0..bits).rev() {
+            self.accumulator = (self.accumulator << 1) | ((value >> i) & 1);
+            self.bits += 1;
+            if self.bits == 8 {
+                self.bytes.push(self.accumulator as u8);
+                self.accumulator = 0;
+                self.bits = 0;
+            }
+        }
+    }
+
+    fn write_signed(&mut self, value: i32, bits: u32) {
+        self.write(value as u64 & ((1 << bits) - 1), bits);
+    }
+
+    fn write_unary(&mut self, zeros: u64) {
+        for _ in 0..zeros {
+            self.write(0, 1);
+        }
+        self.write(1, 1);
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.write(byte as u64, 8);
+        }
+    }
+
+    /// Write a frame number in FLAC's UTF-8-like variable-length coding.
+    fn write_utf8(&mut self, value: u64) {
+        if value < 0x80 {
+            self.write(value, 8);
+            return;
+        }
+        let mut continuation_bytes = 1;
+        while value >= 1 << (6 * continuation_bytes + 6 - continuation_bytes) {
+            continuation_bytes += 1;
+        }
+        let lead_marker = (0xFF00_u64 >> (continuation_bytes + 1)) & 0xFF;
+        self.write(lead_marker | (value >> (6 * continuation_bytes)), 8);
+        for i in (0..continuation_bytes).rev() {
+            self.write(0x80 | ((value >> (6 * i)) & 0x3F), 8);
+        }
+    }
+
+    /// Pad the current byte with zero bits.
+    fn align(&mut self) {
+        while self.bits != 0 {
+            self.write(0, 1);
+        }
+    }
+}
+
+fn crc8(bytes: &[u8]) -> u8 {
+    let mut crc = 0_u8;
+    for &byte in bytes {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+fn crc16(bytes: &[u8]) -> u16 {
+    let mut crc = 0_u16;
+    for &byte in bytes {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x8005
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn interleave(signals: &[Vec<i32>]) -> Vec<i32> {
+        (0..signals[0].len())
+            .flat_map(|i| signals.iter().map(move |signal| signal[i]))
+            .collect()
+    }
+
+    #[test]
+    fn round_trip_16bit() {
+        let signals = vec![
+            (0..10_000)
+                .map(|i| ((i as f32 / 20.0).sin() * 1000.0) as i32)
+                .collect::<Vec<i32>>(),
+            (0..10_000)
+                .map(|i| (i * 7919 % 65536) - 32768)
+                .collect::<Vec<i32>>(),
+            vec![-5; 10_000],
+        ];
+        let encoded = encode(&signals, StorageFormat::_16bit_flac, 360.0).unwrap();
+        assert_eq!(decode(&encoded).unwrap(), (interleave(&signals), 3));
+    }
+
+    #[test]
+    fn round_trip_8bit_and_24bit() {
+        let signals = vec![(0..300).map(|i| (i % 256) - 128).collect::<Vec<i32>>()];
+        let encoded = encode(&signals, StorageFormat::_8bit_flac, 250.0).unwrap();
+        assert_eq!(decode(&encoded).unwrap(), (signals[0].clone(), 1));
+
+        let signals = vec![vec![-8_388_608, 8_388_607, 0, 12_345], vec![1, 2, 3, 4]];
+        let encoded = encode(&signals, StorageFormat::_24bit_flac, 1000.0).unwrap();
+        assert_eq!(decode(&encoded).unwrap(), (interleave(&signals), 2));
+    }
+
+    #[test]
+    fn invalid_signals() {
+        let encode_error =
+            |signals: &[Vec<i32>], format| encode(signals, format, 250.0).unwrap_err().kind();
+        assert_eq!(
+            encode_error(&[], StorageFormat::_16bit_flac),
+            io::ErrorKind::InvalidInput
+        );
+        assert_eq!(
+            encode_error(&vec![vec![0]; 9], StorageFormat::_16bit_flac),
+            io::ErrorKind::InvalidInput
+        );
+        assert_eq!(
+            encode_error(&[vec![1, 2], vec![1]], StorageFormat::_16bit_flac),
+            io::ErrorKind::InvalidInput
+        );
+        assert_eq!(
+            encode_error(&[vec![40_000, 1, 2]], StorageFormat::_16bit_flac),
+            io::ErrorKind::InvalidInput
+        );
+        assert_eq!(
+            encode_error(&[vec![-129]], StorageFormat::_8bit_flac),
+            io::ErrorKind::InvalidInput
+        );
+        assert_eq!(
+            encode_error(&[vec![0]], StorageFormat::_16bit_twos_complement),
+            io::ErrorKind::InvalidInput
+        );
+        assert_eq!(bits_per_sample(StorageFormat::_16bit_twos_complement), None);
+    }
+
+    #[test]
+    fn frame_numbers() {
+        let mut writer = BitWriter::default();
+        writer.write_utf8(0x7F);
+        writer.write_utf8(0x80);
+        writer.write_utf8(0x800);
+        assert_eq!(writer.bytes, vec![0x7F, 0xC2, 0x80, 0xE0, 0xA0, 0x80]);
+    }
+
+    #[test]
+    fn corrupt_stream() {
+        assert_eq!(
+            decode(b"not flac").unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+}
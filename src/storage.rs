@@ -0,0 +1,684 @@
+//! Storage backends that WFDB files (headers, signal files and annotation files) can be read
+//! from.
+//!
+//! Paths given to a [`Storage`] are always `/`-separated and relative to the root of the
+//! database, e.g. `100.hea` or `x_mitdb/x_100.dat`.
+use std::fs;
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "http")]
+mod http;
+#[cfg(feature = "http")]
+pub use http::HttpStorage;
+
+#[cfg(feature = "zip")]
+use std::cell::RefCell;
+use std::collections::HashMap;
+#[cfg(any(feature = "zip", feature = "tar"))]
+use std::fs::File;
+
+/// A source of WFDB files.
+pub trait Storage {
+    /// Read the entire contents of the file at `path`.
+    fn read(&self, path: &str) -> io::Result<Vec<u8>>;
+
+    /// Open the file at `path` for streaming reads.
+    fn open(&self, path: &str) -> io::Result<Box<dyn Read + '_>> {
+        Ok(Box::new(Cursor::new(self.read(path)?)))
+    }
+
+    /// Read up to `len` bytes of the file at `path`, starting `offset` bytes into the file. Fewer
+    /// bytes are returned if the end of the file is reached first.
+    fn read_range(&self, path: &str, offset: u64, len: u64) -> io::Result<Vec<u8>> {
+        let mut buf = vec![];
+        let mut reader = self.open(path)?;
+        io::copy(&mut (&mut reader).take(offset), &mut io::sink())?;
+        reader.take(len).read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+}
+
+/// Join a file name listed in a header onto the directory of the record that references it.
+pub(crate) fn join(directory: &str, filename: &str) -> String {
+    if directory.is_empty() || filename.starts_with('/') {
+        String::from(filename)
+    } else {
+        format!("{}/{}", directory, filename)
+    }
+}
+
+/// A reader over a WFDB file that may be stored gzip-compressed, returned by [`open_file`].
+///
+/// Compressed files are decompressed as they are streamed, so only forward seeks are supported:
+/// seeking backwards returns an error of kind `InvalidInput`.
+pub struct FileReader<'a> {
+    inner: Box<dyn Read + 'a>,
+    position: u64,
+}
+
+impl Read for FileReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.position += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for FileReader<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+            SeekFrom::End(_) => None,
+        };
+        match target {
+            Some(target) if target >= self.position => {
+                let skipped = io::copy(
+                    &mut (&mut self.inner).take(target - self.position),
+                    &mut io::sink(),
+                )?;
+                self.position += skipped;
+                Ok(self.position)
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "only forward seeks are supported",
+            )),
+        }
+    }
+}
+
+/// Whether a file name refers to a gzip-compressed file.
+fn is_compressed(path: &str) -> bool {
+    cfg!(feature = "gzip") && path.ends_with(".gz")
+}
+
+/// Find the file that `path` refers to: the file itself, or failing that its gzip-compressed
+/// variant `<path>.gz`. If neither exists, the error is the one for `path`; if `<path>.gz` exists
+/// but cannot be read, it is the one for `<path>.gz`.
+fn resolve<T, F>(path: &str, mut read: F) -> io::Result<(T, bool)>
+where
+    F: FnMut(&str) -> io::Result<T>,
+{
+    match read(path) {
+        Ok(contents) => Ok((contents, is_compressed(path))),
+        Err(e)
+            if e.kind() == io::ErrorKind::NotFound
+                && cfg!(feature = "gzip")
+                && !is_compressed(path) =>
+        {
+            match read(&format!("{}.gz", path)) {
+                Ok(contents) => Ok((contents, true)),
+                Err(gz_error) if gz_error.kind() == io::ErrorKind::NotFound => Err(e),
+                Err(gz_error) => Err(gz_error),
+            }
+        }
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(feature = "gzip")]
+fn decompress<'a, R: Read + 'a>(reader: R) -> Box<dyn Read + 'a> {
+    Box::new(flate2::read::MultiGzDecoder::new(reader))
+}
+
+#[cfg(not(feature = "gzip"))]
+fn decompress<'a, R: Read + 'a>(reader: R) -> Box<dyn Read + 'a> {
+    Box::new(reader)
+}
+
+/// Open a WFDB file (header, signal or annotation file) held in `storage`. If the file does not
+/// exist but a gzip-compressed `<path>.gz` does, or if `path` itself names a `.gz` file, the
+/// contents are transparently decompressed.
+pub fn open_file<'a>(storage: &'a dyn Storage, path: &str) -> io::Result<FileReader<'a>> {
+    let (reader, compressed) = resolve(path, |path| storage.open(path))?;
+    let inner = if compressed {
+        decompress(reader)
+    } else {
+        reader
+    };
+    Ok(FileReader { inner, position: 0 })
+}
+
+/// Read the entire contents of a WFDB file, decompressing it if needed (see [`open_file`]).
+pub fn read_file(storage: &dyn Storage, path: &str) -> io::Result<Vec<u8>> {
+    let (buf, compressed) = resolve(path, |path| storage.read(path))?;
+    if compressed {
+        let mut decompressed = vec![];
+        decompress(&buf[..]).read_to_end(&mut decompressed)?;
+        Ok(decompressed)
+    } else {
+        Ok(buf)
+    }
+}
+
+/// Read the entire contents of a WFDB file stored on a special file, such as a block device, that
+/// must be read in whole blocks of `block_size` bytes.
+pub fn read_file_in_blocks(
+    storage: &dyn Storage,
+    path: &str,
+    block_size: usize,
+) -> io::Result<Vec<u8>> {
+    let mut reader = open_file(storage, path)?;
+    let mut buf = vec![];
+    let mut block = vec![0; block_size];
+    loop {
+        let n = reader.read(&mut block)?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&block[..n]);
+    }
+    Ok(buf)
+}
+
+/// Read up to `len` bytes of a WFDB file starting `offset` bytes into its (decompressed)
+/// contents. Uncompressed files are read with [`Storage::read_range`]; compressed files are
+/// decompressed up to the end of the range.
+pub fn read_file_range(
+    storage: &dyn Storage,
+    path: &str,
+    offset: u64,
+    len: u64,
+) -> io::Result<Vec<u8>> {
+    let mut reader = if is_compressed(path) {
+        open_file(storage, path)?
+    } else {
+        match storage.read_range(path, offset, len) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound && cfg!(feature = "gzip") => {
+                match open_file(storage, &format!("{}.gz", path)) {
+                    Ok(reader) => reader,
+                    Err(gz_error) if gz_error.kind() == io::ErrorKind::NotFound => return Err(e),
+                    Err(gz_error) => return Err(gz_error),
+                }
+            }
+            result => return result,
+        }
+    };
+    reader.seek(SeekFrom::Start(offset))?;
+    let mut buf = vec![];
+    reader.take(len).read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+/// Files in a directory on the local filesystem.
+pub struct FileStorage {
+    root: PathBuf,
+}
+
+impl FileStorage {
+    pub fn new<P: AsRef<Path>>(root: P) -> FileStorage {
+        FileStorage {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    fn path(&self, path: &str) -> PathBuf {
+        let path = Path::new(path);
+        if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            self.root.join(path)
+        }
+    }
+}
+
+impl Storage for FileStorage {
+    fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+        fs::read(self.path(path))
+    }
+
+    fn open(&self, path: &str) -> io::Result<Box<dyn Read + '_>> {
+        Ok(Box::new(fs::File::open(self.path(path))?))
+    }
+
+    fn read_range(&self, path: &str, offset: u64, len: u64) -> io::Result<Vec<u8>> {
+        let mut file = fs::File::open(self.path(path))?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut buf = vec![];
+        file.take(len).read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+}
+
+/// Files held in memory, keyed by path, such as files downloaded by an application or decoded
+/// from another container.
+#[derive(Default)]
+pub struct MemoryStorage {
+    files: HashMap<String, Vec<u8>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> MemoryStorage {
+        MemoryStorage::default()
+    }
+
+    /// Add the file at `path`, replacing any file already stored there.
+    pub fn insert<P: Into<String>>(&mut self, path: P, contents: Vec<u8>) {
+        self.files.insert(path.into(), contents);
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+        self.files
+            .get(path)
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, path.to_string()))
+    }
+
+    fn open(&self, path: &str) -> io::Result<Box<dyn Read + '_>> {
+        match self.files.get(path) {
+            Some(buf) => Ok(Box::new(&buf[..])),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, path.to_string())),
+        }
+    }
+}
+
+/// Find the directory shared by every entry of an archive, if there is one. PhysioNet archives
+/// put the whole database under a single top-level directory (e.g.
+/// `mit-bih-arrhythmia-database-1.0.0/`), which is stripped so that paths match those used on an
+/// extracted copy.
+#[cfg(any(feature = "zip", feature = "tar"))]
+fn common_prefix<'a, I: IntoIterator<Item = &'a str>>(names: I) -> String {
+    let mut prefix: Option<&str> = None;
+    for name in names {
+        let top = match name.find('/') {
+            Some(idx) => &name[..=idx],
+            None => return String::new(),
+        };
+        match prefix {
+            Some(p) if p != top => return String::new(),
+            _ => prefix = Some(top),
+        }
+    }
+    String::from(prefix.unwrap_or(""))
+}
+
+/// Files inside a zip archive, read without extracting it.
+#[cfg(feature = "zip")]
+pub struct ZipStorage<R: Read + Seek> {
+    archive: RefCell<zip::ZipArchive<R>>,
+    prefix: String,
+}
+
+#[cfg(feature = "zip")]
+impl ZipStorage<File> {
+    /// Open the zip archive at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<ZipStorage<File>> {
+        ZipStorage::new(File::open(path)?)
+    }
+}
+
+#[cfg(feature = "zip")]
+impl<R: Read + Seek> ZipStorage<R> {
+    /// Read a zip archive from any seekable source, such as a file or an in-memory buffer.
+    pub fn new(reader: R) -> io::Result<ZipStorage<R>> {
+        let archive = zip::ZipArchive::new(reader)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let prefix = common_prefix(archive.file_names());
+        Ok(ZipStorage {
+            archive: RefCell::new(archive),
+            prefix,
+        })
+    }
+}
+
+#[cfg(feature = "zip")]
+impl<R: Read + Seek> Storage for ZipStorage<R> {
+    fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+        let mut archive = self.archive.borrow_mut();
+        let mut entry = archive
+            .by_name(&format!("{}{}", self.prefix, path))
+            .map_err(|e| match e {
+                zip::result::ZipError::FileNotFound => {
+                    io::Error::new(io::ErrorKind::NotFound, path.to_string())
+                }
+                e => io::Error::new(io::ErrorKind::InvalidData, e),
+            })?;
+        let mut buf = Vec::with_capacity(entry.size() as usize);
+        entry.read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+}
+
+/// Files inside a gzip-compressed tar archive.
+///
+/// Tar archives cannot be read out of order, so the archive is decompressed once when it is
+/// opened and its regular files are kept in memory.
+#[cfg(feature = "tar")]
+pub struct TarGzStorage {
+    files: MemoryStorage,
+}
+
+#[cfg(feature = "tar")]
+impl TarGzStorage {
+    /// Open the `.tar.gz` archive at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<TarGzStorage> {
+        TarGzStorage::new(File::open(path)?)
+    }
+
+    /// Read a `.tar.gz` archive from any `Read` source.
+    pub fn new<R: Read>(reader: R) -> io::Result<TarGzStorage> {
+        let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(reader));
+        let mut files = HashMap::new();
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            let name = entry
+                .path()?
+                .to_string_lossy()
+                .trim_start_matches("./")
+                .to_string();
+            let mut buf = vec![];
+            entry.read_to_end(&mut buf)?;
+            files.insert(name, buf);
+        }
+        let prefix = common_prefix(files.keys().map(String::as_str));
+        if !prefix.is_empty() {
+            files = files
+                .into_iter()
+                .map(|(name, buf)| (name[prefix.len()..].to_string(), buf))
+                .collect();
+        }
+        Ok(TarGzStorage {
+            files: MemoryStorage { files },
+        })
+    }
+}
+
+#[cfg(feature = "tar")]
+impl Storage for TarGzStorage {
+    fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+        self.files.read(path)
+    }
+
+    fn open(&self, path: &str) -> io::Result<Box<dyn Read + '_>> {
+        self.files.open(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn join_record_directory() {
+        assert_eq!(join("", "100.dat"), "100.dat");
+        assert_eq!(join("mitdb", "100.dat"), "mitdb/100.dat");
+        assert_eq!(join("mitdb", "/data/100.dat"), "/data/100.dat");
+    }
+
+    #[test]
+    fn read_storage_range() {
+        let storage = FileStorage::new(env!("CARGO_MANIFEST_DIR"));
+        let contents = storage.read("Cargo.toml").unwrap();
+        assert_eq!(
+            storage.read_range("Cargo.toml", 3, 5).unwrap(),
+            &contents[3..8]
+        );
+        assert_eq!(
+            storage.read_range("Cargo.toml", 3, u64::MAX).unwrap(),
+            &contents[3..]
+        );
+
+        // `MemoryStorage` uses the default `read_range` implementation.
+        let mut storage = MemoryStorage::new();
+        storage.insert("Cargo.toml", contents.clone());
+        assert_eq!(
+            storage.read_range("Cargo.toml", 3, 5).unwrap(),
+            &contents[3..8]
+        );
+        assert_eq!(
+            storage.read_range("Cargo.toml", 3, u64::MAX).unwrap(),
+            &contents[3..]
+        );
+    }
+
+    #[cfg(feature = "gzip")]
+    fn gzip(buf: &[u8]) -> Vec<u8> {
+        use std::io::Write;
+        let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(buf).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn read_uncompressed_file() {
+        let mut storage = MemoryStorage::new();
+        storage.insert("100.dat", vec![1, 2, 3, 4]);
+        assert_eq!(read_file(&storage, "100.dat").unwrap(), vec![1, 2, 3, 4]);
+        assert_eq!(
+            read_file_range(&storage, "100.dat", 1, 2).unwrap(),
+            vec![2, 3]
+        );
+        assert_eq!(
+            read_file(&storage, "101.dat").unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn read_compressed_file() {
+        let contents: Vec<u8> = (0..=255).collect();
+        let mut storage = MemoryStorage::new();
+        storage.insert("100.dat.gz", gzip(&contents));
+        storage.insert("100.hea.gz", gzip(b"100 1"));
+        assert_eq!(read_file(&storage, "100.dat").unwrap(), contents);
+        assert_eq!(read_file(&storage, "100.dat.gz").unwrap(), contents);
+        assert_eq!(
+            read_file_range(&storage, "100.dat", 10, 3).unwrap(),
+            vec![10, 11, 12]
+        );
+        assert_eq!(
+            read_file_range(&storage, "100.dat", 250, 10).unwrap(),
+            vec![250, 251, 252, 253, 254, 255]
+        );
+
+        let mut header = String::new();
+        open_file(&storage, "100.hea")
+            .unwrap()
+            .read_to_string(&mut header)
+            .unwrap();
+        assert_eq!(header, "100 1");
+    }
+
+    /// A file that can only be read in whole blocks, like a tape device.
+    struct BlockDevice(Vec<u8>, usize);
+
+    impl Read for BlockDevice {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            assert_eq!(
+                buf.len() % self.1,
+                0,
+                "read of {} bytes from a block device",
+                buf.len()
+            );
+            let n = buf.len().min(self.0.len());
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0.drain(..n);
+            Ok(n)
+        }
+    }
+
+    impl Storage for BlockDevice {
+        fn read(&self, _path: &str) -> io::Result<Vec<u8>> {
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "block devices can only be opened",
+            ))
+        }
+
+        fn open(&self, _path: &str) -> io::Result<Box<dyn Read + '_>> {
+            Ok(Box::new(BlockDevice(self.0.clone(), self.1)))
+        }
+    }
+
+    #[test]
+    fn read_whole_blocks() {
+        let contents: Vec<u8> = (0..100).collect();
+        assert_eq!(
+            read_file_in_blocks(&BlockDevice(contents.clone(), 16), "tape", 16).unwrap(),
+            contents
+        );
+    }
+
+    /// A storage whose `.gz` files exist but cannot be read.
+    #[cfg(feature = "gzip")]
+    struct UnreadableGzip;
+
+    #[cfg(feature = "gzip")]
+    impl Storage for UnreadableGzip {
+        fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+            if path.ends_with(".gz") {
+                Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    path.to_string(),
+                ))
+            } else {
+                Err(io::Error::new(io::ErrorKind::NotFound, path.to_string()))
+            }
+        }
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn unreadable_compressed_file() {
+        let error = read_file(&UnreadableGzip, "100.dat").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(error.to_string(), "100.dat.gz");
+        let error = read_file_range(&UnreadableGzip, "100.dat", 0, 2).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+        for error in [
+            read_file(&MemoryStorage::new(), "100.dat").unwrap_err(),
+            read_file_range(&MemoryStorage::new(), "100.dat", 0, 2).unwrap_err(),
+        ] {
+            assert_eq!(error.kind(), io::ErrorKind::NotFound);
+            assert_eq!(error.to_string(), "100.dat");
+        }
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn seek_compressed_file_forward() {
+        let contents: Vec<u8> = (0..=255).collect();
+        let mut storage = MemoryStorage::new();
+        storage.insert("100.dat.gz", gzip(&contents));
+        let mut reader = open_file(&storage, "100.dat").unwrap();
+        let mut buf = [0; 2];
+        assert_eq!(reader.seek(SeekFrom::Start(100)).unwrap(), 100);
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [100, 101]);
+        assert_eq!(reader.seek(SeekFrom::Current(8)).unwrap(), 110);
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [110, 111]);
+        assert_eq!(
+            reader.seek(SeekFrom::Start(0)).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+
+    #[cfg(any(feature = "zip", feature = "tar"))]
+    #[test]
+    fn strip_common_prefix() {
+        assert_eq!(common_prefix(vec!["db/100.hea", "db/100.dat"]), "db/");
+        assert_eq!(common_prefix(vec!["db/100.hea", "100.dat"]), "");
+        assert_eq!(common_prefix(vec!["a/100.hea", "b/100.dat"]), "");
+    }
+
+    /// A two-signal record, `100`, with interleaved 16-bit samples [1, 3, 5] and [2, 4, 6], as the
+    /// files of an archive.
+    #[cfg(any(feature = "zip", feature = "tar"))]
+    fn archived_record() -> Vec<(&'static str, Vec<u8>)> {
+        let samples: Vec<u8> = (1..=6_i16).flat_map(|s| s.to_le_bytes()).collect();
+        vec![
+            (
+                "mitdb-1.0.0/100.hea",
+                b"100 2 360 3\n100.dat 16\n100.dat 16\n".to_vec(),
+            ),
+            ("mitdb-1.0.0/100.dat", samples),
+        ]
+    }
+
+    /// Check that the record of [`archived_record`] is decoded whole and by range from `storage`.
+    #[cfg(any(feature = "zip", feature = "tar"))]
+    fn check_archived_record(storage: &dyn Storage) {
+        let record = crate::Record::open_from(storage, "100").unwrap();
+        assert_eq!(record.signals, vec![vec![1, 3, 5], vec![2, 4, 6]]);
+        let files = crate::record::read_signal_files(storage, "100", &record.header, 1..2).unwrap();
+        assert_eq!((files.sample(0, 1), files.sample(1, 1)), (3, 4));
+    }
+
+    #[cfg(feature = "zip")]
+    fn zip_archive(files: &[(&str, Vec<u8>)]) -> ZipStorage<Cursor<Vec<u8>>> {
+        use std::io::Write;
+        let mut buf = Cursor::new(vec![]);
+        {
+            let mut writer = zip::ZipWriter::new(&mut buf);
+            let options = zip::write::SimpleFileOptions::default();
+            for (name, contents) in files {
+                writer.start_file(*name, options).unwrap();
+                writer.write_all(contents).unwrap();
+            }
+            writer.finish().unwrap();
+        }
+        buf.set_position(0);
+        ZipStorage::new(buf).unwrap()
+    }
+
+    #[cfg(feature = "tar")]
+    fn tar_gz_archive(files: &[(&str, Vec<u8>)]) -> TarGzStorage {
+        let mut gz = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        {
+            let mut builder = tar::Builder::new(&mut gz);
+            for (name, contents) in files {
+                let mut header = tar::Header::new_gnu();
+                header.set_size(contents.len() as u64);
+                header.set_cksum();
+                builder
+                    .append_data(&mut header, name, &contents[..])
+                    .unwrap();
+            }
+            builder.finish().unwrap();
+        }
+        TarGzStorage::new(&gz.finish().unwrap()[..]).unwrap()
+    }
+
+    #[cfg(feature = "zip")]
+    #[test]
+    fn read_from_zip() {
+        let storage = zip_archive(&[("mitdb-1.0.0/100.hea", b"100 1 360\n".to_vec())]);
+        assert_eq!(storage.read("100.hea").unwrap(), b"100 1 360\n");
+        assert_eq!(
+            storage.read("101.hea").unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+
+    #[cfg(feature = "zip")]
+    #[test]
+    fn open_record_from_zip() {
+        check_archived_record(&zip_archive(&archived_record()));
+    }
+
+    #[cfg(feature = "tar")]
+    #[test]
+    fn read_from_tar_gz() {
+        let storage = tar_gz_archive(&[("mitdb-1.0.0/100.hea", b"100 1 360\n".to_vec())]);
+        assert_eq!(storage.read("100.hea").unwrap(), b"100 1 360\n");
+        assert_eq!(
+            storage.read("101.hea").unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+
+    #[cfg(feature = "tar")]
+    #[test]
+    fn open_record_from_tar_gz() {
+        check_archived_record(&tar_gz_archive(&archived_record()));
+    }
+}
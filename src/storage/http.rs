@@ -0,0 +1,144 @@
+//! Reading WFDB files over HTTP(S) from a server laid out like PhysioNet's `files/` tree.
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+
+use super::Storage;
+
+/// Files served over HTTP(S), e.g. from `https://physionet.org/files/mitdb/1.0.0/` or an
+/// internal mirror with the same layout.
+///
+/// Partial reads are made with HTTP range requests. When a cache directory is set, whole files
+/// are stored there after they are first downloaded and read from disk afterwards.
+pub struct HttpStorage {
+    base_url: String,
+    cache_dir: Option<PathBuf>,
+    agent: ureq::Agent,
+}
+
+impl HttpStorage {
+    /// Read files relative to `base_url`, without caching.
+    pub fn new(base_url: &str) -> HttpStorage {
+        HttpStorage {
+            base_url: String::from(base_url.trim_end_matches('/')),
+            cache_dir: None,
+            agent: ureq::Agent::new(),
+        }
+    }
+
+    /// Cache downloaded files under `cache_dir`, mirroring the server's layout.
+    pub fn with_cache<P: AsRef<Path>>(mut self, cache_dir: P) -> HttpStorage {
+        self.cache_dir = Some(cache_dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// `path` relative to the base URL. Paths leading out of it, such as signal file names like
+    /// `../../.bashrc` in a header served by the mirror, are rejected, so that they are neither
+    /// requested nor cached outside the cache directory.
+    fn relative_path(path: &str) -> io::Result<&str> {
+        let relative = path.trim_start_matches('/');
+        if Path::new(relative)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            Ok(relative)
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is outside the served directory", path),
+            ))
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.base_url, path)
+    }
+
+    fn cache_path(&self, path: &str) -> Option<PathBuf> {
+        self.cache_dir.as_ref().map(|dir| dir.join(path))
+    }
+
+    fn get(&self, path: &str, range: Option<(u64, u64)>) -> io::Result<ureq::Response> {
+        let mut request = self.agent.get(&self.url(path));
+        if let Some((offset, len)) = range {
+            request = request.set("Range", &format!("bytes={}-{}", offset, offset + len - 1));
+        }
+        request.call().map_err(|e| match e {
+            ureq::Error::Status(404, _) => io::Error::new(io::ErrorKind::NotFound, self.url(path)),
+            ureq::Error::Status(416, _) => {
+                io::Error::new(io::ErrorKind::UnexpectedEof, self.url(path))
+            }
+            e => io::Error::other(e),
+        })
+    }
+
+    /// Store a downloaded file in the cache. The file is written under a temporary name first, so
+    /// that an interrupted download never leaves a truncated file in the cache.
+    fn store(&self, cache_path: &Path, buf: &[u8]) -> io::Result<()> {
+        if let Some(parent) = cache_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut partial = cache_path.as_os_str().to_os_string();
+        partial.push(".part");
+        fs::write(&partial, buf)?;
+        fs::rename(&partial, cache_path)
+    }
+}
+
+impl Storage for HttpStorage {
+    fn read(&self, path: &str) -> io::Result<Vec<u8>> {
+        let path = HttpStorage::relative_path(path)?;
+        let cache_path = self.cache_path(path);
+        if let Some(cache_path) = &cache_path {
+            if cache_path.is_file() {
+                return fs::read(cache_path);
+            }
+        }
+        let mut buf = vec![];
+        self.get(path, None)?.into_reader().read_to_end(&mut buf)?;
+        if let Some(cache_path) = &cache_path {
+            self.store(cache_path, &buf)?;
+        }
+        Ok(buf)
+    }
+
+    fn open(&self, path: &str) -> io::Result<Box<dyn Read + '_>> {
+        let path = HttpStorage::relative_path(path)?;
+        match self.cache_path(path) {
+            Some(_) => Ok(Box::new(io::Cursor::new(self.read(path)?))),
+            None => Ok(Box::new(self.get(path, None)?.into_reader())),
+        }
+    }
+
+    fn read_range(&self, path: &str, offset: u64, len: u64) -> io::Result<Vec<u8>> {
+        let path = HttpStorage::relative_path(path)?;
+        if let Some(cache_path) = self.cache_path(path) {
+            if cache_path.is_file() {
+                return super::FileStorage::new("").read_range(
+                    &cache_path.to_string_lossy(),
+                    offset,
+                    len,
+                );
+            }
+        }
+        if len == 0 {
+            return Ok(vec![]);
+        }
+        let response = match self.get(path, Some((offset, len.min(u64::MAX - offset)))) {
+            Ok(response) => response,
+            // The requested range starts past the end of the file
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(vec![]),
+            Err(e) => return Err(e),
+        };
+        let mut buf = vec![];
+        if response.status() == 206 {
+            response.into_reader().take(len).read_to_end(&mut buf)?;
+        } else {
+            // The server ignored the range and sent the whole file
+            let mut reader = response.into_reader();
+            io::copy(&mut (&mut reader).take(offset), &mut io::sink())?;
+            reader.take(len).read_to_end(&mut buf)?;
+        }
+        Ok(buf)
+    }
+}
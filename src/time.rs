@@ -0,0 +1,246 @@
+//! Times and dates as written in WFDB headers and printed by the WFDB tools.
+//!
+//! A record's base time is a time of day (`HH:MM:SS`, with optional fractional seconds) and its
+//! base date is written `DD/MM/YYYY`. Times within a record are printed as `HH:MM:SS.mmm`.
+use crate::header::{Header, RecordLine};
+
+const SECONDS_PER_DAY: f64 = 86_400.0;
+
+/// Parse a time written as `HH:MM:SS`, `MM:SS` or `SS`, each with optional fractional seconds,
+/// into a number of seconds.
+pub fn parse_time(time: &str) -> Option<f64> {
+    let mut seconds = 0.0;
+    let fields: Vec<&str> = time.trim().split(':').collect();
+    if fields.len() > 3 {
+        return None;
+    }
+    for (idx, field) in fields.iter().enumerate() {
+        let value = if idx + 1 == fields.len() {
+            field
+                .parse::<f64>()
+                .ok()
+                .filter(|v| v.is_finite() && *v >= 0.0)?
+        } else {
+            field.parse::<u32>().ok()? as f64
+        };
+        seconds = seconds * 60.0 + value;
+    }
+    Some(seconds)
+}
+
+/// Parse a date written as `DD/MM/YYYY` into its day, month and year.
+pub fn parse_date(date: &str) -> Option<(u32, u32, i32)> {
+    let fields: Vec<&str> = date.trim().split('/').collect();
+    match fields[..] {
+        [day, month, year] => Some((day.parse().ok()?, month.parse().ok()?, year.parse().ok()?)),
+        _ => None,
+    }
+}
+
+/// Format a number of seconds as `HH:MM:SS.mmm`.
+pub fn format_time(seconds: f64) -> String {
+    let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+/// Format a date as `DD/MM/YYYY`.
+pub fn format_date((day, month, year): (u32, u32, i32)) -> String {
+    format!("{:02}/{:02}/{:04}", day, month, year)
+}
+
+/// The absolute time and date of a point `seconds` into a record, as `HH:MM:SS.mmm DD/MM/YYYY`.
+/// Records without a base time are taken to start at midnight, and the date is left out for
+/// records without a base date.
+pub fn absolute_time(record: &RecordLine, seconds: f64) -> String {
+    let base_time = record
+        .base_time
+        .as_deref()
+        .and_then(parse_time)
+        .unwrap_or(0.0);
+    let time = base_time + seconds;
+    let days = (time / SECONDS_PER_DAY).floor();
+    let time_of_day = format_time(time - days * SECONDS_PER_DAY);
+    match record.base_date.as_deref().and_then(parse_date) {
+        Some(date) => format!(
+            "{} {}",
+            time_of_day,
+            format_date(add_days(date, days as i64))
+        ),
+        None => time_of_day,
+    }
+}
+
+/// Parse an absolute time and date (as made by [`absolute_time`]) into a number of seconds since
+/// 1 January 1970, or since midnight when no date is given.
+pub fn parse_absolute_time(time: &str) -> Option<f64> {
+    let mut fields = time.split_whitespace();
+    let seconds = parse_time(fields.next()?)?;
+    match fields.next() {
+        Some(date) => Some(days_from_civil(parse_date(date)?) as f64 * SECONDS_PER_DAY + seconds),
+        None => Some(seconds),
+    }
+}
+
+/// Parse a time within a record written in the syntax of the WFDB tools' `-f` and `-t` options,
+/// into a number of seconds from the beginning of the record:
+///
+/// - `HH:MM:SS`, `MM:SS` or `SS` (with optional fractional seconds): elapsed time;
+/// - `s<n>`: sample number `n`;
+/// - `[HH:MM:SS DD/MM/YYYY]` or `[HH:MM:SS]`: absolute time, from the record's base time and date.
+///   Without a date, a time of day before the base time is taken to be on the next day;
+/// - `e`: the end of the record, when the header gives its length.
+pub fn parse_time_spec(spec: &str, header: &Header) -> Option<f64> {
+    let spec = spec.trim();
+    let sampling_frequency = header.sampling_frequency();
+    if spec == "e" {
+        return header
+            .record
+            .samples_per_signal
+            .map(|samples| samples as f64 / sampling_frequency);
+    }
+    if let Some(sample) = spec.strip_prefix('s') {
+        return sample
+            .parse::<u64>()
+            .ok()
+            .map(|sample| sample as f64 / sampling_frequency);
+    }
+    let absolute = match spec.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+        Some(absolute) => absolute,
+        None => return parse_time(spec),
+    };
+    let record = &header.record;
+    let base_time = record
+        .base_time
+        .as_deref()
+        .and_then(parse_time)
+        .unwrap_or(0.0);
+    let base_date = record.base_date.as_deref().and_then(parse_date);
+    let mut fields = absolute.split_whitespace();
+    let time = parse_time(fields.next()?)?;
+    match (fields.next().map(parse_date), base_date) {
+        (Some(date), Some(base_date)) => {
+            let days = days_from_civil(date?) - days_from_civil(base_date);
+            Some(days as f64 * SECONDS_PER_DAY + time - base_time).filter(|&t| t >= 0.0)
+        }
+        _ if time < base_time => Some(time + SECONDS_PER_DAY - base_time),
+        _ => Some(time - base_time),
+    }
+}
+
+/// Split a number of seconds since 1 January 1970 into a time of day and a date.
+pub(crate) fn split_timestamp(seconds: f64) -> (f64, (u32, u32, i32)) {
+    let days = (seconds / SECONDS_PER_DAY).floor();
+    (
+        seconds - days * SECONDS_PER_DAY,
+        civil_from_days(days as i64),
+    )
+}
+
+fn add_days(date: (u32, u32, i32), days: i64) -> (u32, u32, i32) {
+    civil_from_days(days_from_civil(date) + days)
+}
+
+/// Number of days between 1 January 1970 and a date in the proleptic Gregorian calendar.
+fn days_from_civil((day, month, year): (u32, u32, i32)) -> i64 {
+    let year = if month <= 2 {
+        year as i64 - 1
+    } else {
+        year as i64
+    };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The date `days` days after 1 January 1970.
+fn civil_from_days(days: i64) -> (u32, u32, i32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (day, month, year as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::{parse_record_line, read_header};
+
+    #[test]
+    fn times() {
+        assert_eq!(parse_time("12:30:15.5"), Some(45_015.5));
+        assert_eq!(parse_time("1:05"), Some(65.0));
+        assert_eq!(parse_time("7.25"), Some(7.25));
+        assert_eq!(parse_time("1:2:3:4"), None);
+        assert_eq!(parse_time("noon"), None);
+        assert_eq!(format_time(45_015.5), "12:30:15.500");
+        assert_eq!(format_time(0.0004), "00:00:00.000");
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(parse_date("29/02/2000"), Some((29, 2, 2000)));
+        assert_eq!(parse_date("2000-02-29"), None);
+        assert_eq!(days_from_civil((1, 1, 1970)), 0);
+        assert_eq!(
+            civil_from_days(days_from_civil((28, 2, 2000)) + 1),
+            (29, 2, 2000)
+        );
+        assert_eq!(
+            civil_from_days(days_from_civil((31, 12, 1999)) + 1),
+            (1, 1, 2000)
+        );
+    }
+
+    #[test]
+    fn absolute_times() {
+        let record = parse_record_line("rec 1 250 1000 23:59:59 31/12/1999").unwrap();
+        assert_eq!(absolute_time(&record, 0.5), "23:59:59.500 31/12/1999");
+        assert_eq!(absolute_time(&record, 1.25), "00:00:00.250 01/01/2000");
+        assert_eq!(
+            parse_absolute_time("00:00:00.250 01/01/2000"),
+            Some(946_684_800.25)
+        );
+        assert_eq!(split_timestamp(946_684_800.25), (0.25, (1, 1, 2000)));
+
+        let record = parse_record_line("rec 1 250 1000 10:00:00").unwrap();
+        assert_eq!(absolute_time(&record, 90.0), "10:01:30.000");
+        let record = parse_record_line("rec 1 250 1000").unwrap();
+        assert_eq!(absolute_time(&record, 90.0), "00:01:30.000");
+    }
+
+    #[test]
+    fn time_specs() {
+        let header = read_header("rec 1 250 1000 23:59:00 31/12/1999\nrec.dat 16").unwrap();
+        assert_eq!(parse_time_spec("1:30", &header), Some(90.0));
+        assert_eq!(parse_time_spec("s500", &header), Some(2.0));
+        assert_eq!(parse_time_spec("e", &header), Some(4.0));
+        assert_eq!(parse_time_spec("[23:59:02.5]", &header), Some(2.5));
+        assert_eq!(
+            parse_time_spec("[00:00:30 01/01/2000]", &header),
+            Some(90.0)
+        );
+        assert_eq!(parse_time_spec("[00:00:30]", &header), Some(90.0));
+        assert_eq!(parse_time_spec("[23:58:00 31/12/1999]", &header), None);
+        assert_eq!(parse_time_spec("sx", &header), None);
+        assert_eq!(
+            parse_time_spec("e", &read_header("rec 1\nrec.dat 16").unwrap()),
+            None
+        );
+    }
+}
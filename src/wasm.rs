@@ -0,0 +1,146 @@
+//! JavaScript bindings for WebAssembly (`wasm32-unknown-unknown`), built as a `cdylib` with
+//! `wasm-bindgen` (see the README).
+//!
+//! Nothing is read from a filesystem: headers are passed as strings, and signal and annotation
+//! files as `Uint8Array` buffers, such as the contents of files fetched by a web page. Headers
+//! are returned as plain objects whose keys are the camel-cased names of the fields of
+//! [`RecordLine`] and [`SignalSpecLine`].
+use std::io;
+
+use js_sys::{Array, Float64Array, Int32Array, Int8Array, Object, Reflect, Uint8Array};
+use wasm_bindgen::prelude::*;
+
+use crate::annotation;
+use crate::header::{self, Header, RecordLine, SignalSpecLine};
+use crate::record::Record;
+
+fn set(object: &Object, key: &str, value: impl Into<JsValue>) {
+    Reflect::set(object, &JsValue::from_str(key), &value.into())
+        .expect("Properties can always be set on plain objects");
+}
+
+fn record_line_object(record: &RecordLine) -> Object {
+    let object = Object::new();
+    set(&object, "recordName", record.record_name.as_str());
+    set(&object, "numberOfSegments", record.number_of_segments);
+    set(&object, "numberOfSignals", record.number_of_signals);
+    set(&object, "samplingFrequency", record.sampling_frequency);
+    set(&object, "counterFrequency", record.counter_frequency);
+    set(&object, "baseCounterValue", record.base_counter_value);
+    set(&object, "samplesPerSignal", record.samples_per_signal);
+    set(&object, "baseTime", record.base_time.clone());
+    set(&object, "baseDate", record.base_date.clone());
+    object
+}
+
+fn signal_object(spec: &SignalSpecLine) -> Object {
+    let object = Object::new();
+    set(&object, "filename", spec.filename.as_str());
+    set(&object, "format", spec.format as u32);
+    set(&object, "samplesPerFrame", spec.samples_per_frame);
+    set(&object, "skew", spec.skew);
+    set(&object, "byteOffset", spec.byte_offset);
+    set(&object, "adcGain", spec.adc_gain);
+    set(&object, "baseline", spec.baseline);
+    set(&object, "units", spec.units.clone());
+    set(&object, "adcResolution", spec.adc_resolution);
+    set(&object, "adcZero", spec.adc_zero);
+    set(&object, "initialValue", spec.initial_value);
+    set(&object, "checksum", spec.checksum);
+    set(&object, "blockSize", spec.block_size);
+    set(&object, "description", spec.description.clone());
+    object
+}
+
+/// A header as an object with a `record` object and a `signals` array of objects.
+fn header_object(header: &Header) -> Object {
+    let object = Object::new();
+    set(&object, "record", record_line_object(&header.record));
+    let signals: Array = header.signal_specs.iter().map(signal_object).collect();
+    set(&object, "signals", signals);
+    object
+}
+
+fn js_error(error: io::Error) -> JsError {
+    JsError::new(&error.to_string())
+}
+
+/// Parse the text of a header file.
+#[wasm_bindgen(js_name = parseHeader)]
+pub fn parse_header(text: &str) -> Result<Object, JsError> {
+    Ok(header_object(&header::read_header(text)?))
+}
+
+/// Decode a record from the text of its header and its signal files, given as an object mapping
+/// each file name in the header to a `Uint8Array`.
+///
+/// Returns an object with the parsed `header`, the `samplingFrequency` and the `signals`: one
+/// `Int32Array` of ADC samples per signal (-2147483648 for invalid samples), or one
+/// `Float64Array` of physical values (NaN for invalid samples) when `physical` is true.
+#[wasm_bindgen(js_name = readRecord)]
+pub fn read_record(header: &str, files: &Object, physical: bool) -> Result<Object, JsError> {
+    let record = Record::from_header_str(header, |filename| {
+        let file = Reflect::get(files, &JsValue::from_str(filename))
+            .ok()
+            .filter(|file| !file.is_undefined())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("Signal file {} was not given", filename),
+                )
+            })?;
+        Ok(Uint8Array::new(&file).to_vec())
+    })
+    .map_err(js_error)?;
+
+    let signals: Array = (0..record.signals.len())
+        .map(|idx| -> JsValue {
+            if physical {
+                let values: Vec<f64> = record
+                    .physical_samples(idx)
+                    .into_iter()
+                    .map(|value| value.unwrap_or(f64::NAN))
+                    .collect();
+                Float64Array::from(&values[..]).into()
+            } else {
+                Int32Array::from(&record.signals[idx][..]).into()
+            }
+        })
+        .collect();
+    let object = Object::new();
+    set(&object, "header", header_object(&record.header));
+    set(&object, "samplingFrequency", record.sampling_frequency());
+    set(&object, "signals", signals);
+    Ok(object)
+}
+
+/// Read the annotations in an annotation file into an object of columns: `sample` (a
+/// `Float64Array`), `code`, `subtype`, `chan` and `num` (typed arrays), `symbol` (an array of
+/// mnemonics, empty for codes without one) and `aux` (an array of strings or nulls).
+#[wasm_bindgen(js_name = readAnnotations)]
+pub fn read_annotations(buf: &[u8]) -> Object {
+    let annotations = annotation::parse_mit_annotations(buf);
+    let samples: Vec<f64> = annotations.iter().map(|a| a.sample as f64).collect();
+    let codes: Vec<u8> = annotations.iter().map(|a| a.code).collect();
+    let subtypes: Vec<i8> = annotations.iter().map(|a| a.subtype).collect();
+    let chans: Vec<u8> = annotations.iter().map(|a| a.chan).collect();
+    let nums: Vec<i8> = annotations.iter().map(|a| a.num).collect();
+    let symbols: Array = annotations
+        .iter()
+        .map(|a| JsValue::from_str(a.mnemonic().unwrap_or("")))
+        .collect();
+    let aux: Array = annotations
+        .iter()
+        .map(|a| a.aux.as_deref().map_or(JsValue::NULL, JsValue::from_str))
+        .collect();
+
+    let object = Object::new();
+    set(&object, "sample", Float64Array::from(&samples[..]));
+    set(&object, "code", Uint8Array::from(&codes[..]));
+    set(&object, "subtype", Int8Array::from(&subtypes[..]));
+    set(&object, "chan", Uint8Array::from(&chans[..]));
+    set(&object, "num", Int8Array::from(&nums[..]));
+    set(&object, "symbol", symbols);
+    set(&object, "aux", aux);
+    object
+}
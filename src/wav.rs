@@ -0,0 +1,400 @@
+//! Converting records to and from RIFF WAV audio files, as `mit2wav` and `wav2mit` do.
+//!
+//! Each signal becomes one channel of PCM audio at the record's sampling frequency. Signal names
+//! are kept in the `ICMT` (comment) entry of a `LIST` `INFO` chunk, one name per line, and the
+//! record name in its `INAM` (title) entry.
+use std::convert::TryFrom;
+use std::io::{self, BufWriter, Read, Write};
+
+use crate::header::{Header, RecordLine, SignalSpecLine, StorageFormat};
+use crate::record::Record;
+use crate::signal;
+
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, String::from(message))
+}
+
+/// Bits per PCM sample able to hold samples of the given ADC resolution.
+fn bits_per_sample(adc_resolution: u32) -> u16 {
+    match adc_resolution {
+        0..=8 => 8,
+        9..=16 => 16,
+        17..=24 => 24,
+        _ => 32,
+    }
+}
+
+/// An `INFO` list entry, padded to an even length.
+fn info_entry(id: &[u8; 4], text: &str) -> Vec<u8> {
+    let mut text = text.as_bytes().to_vec();
+    text.push(0);
+    let mut entry = id.to_vec();
+    entry.extend((text.len() as u32).to_le_bytes());
+    entry.extend(&text);
+    if text.len() % 2 == 1 {
+        entry.push(0);
+    }
+    entry
+}
+
+/// Write a record as a WAV file with one channel per signal, at the record's sampling frequency
+/// (rounded to a whole number of hertz).
+///
+/// Samples are written relative to each signal's ADC zero as 8, 16, 24 or 32-bit PCM, picked to
+/// hold the largest ADC resolution among the signals. Samples that do not fit are clipped, and
+/// invalid samples are written as the most negative PCM value. Fails with `InvalidInput` for records
+/// too large for the 32-bit sizes of a WAV file, 4 GiB of samples or more.
+pub fn write_wav<W: Write>(record: &Record, writer: W) -> io::Result<()> {
+    let mut writer = BufWriter::new(writer);
+    let channels: Vec<usize> = (0..record.signals.len())
+        .filter(|&idx| record.header.signal_specs[idx].format != StorageFormat::_null)
+        .collect();
+    if channels.is_empty() || channels.len() > u16::MAX as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "A WAV file holds between 1 and 65535 channels",
+        ));
+    }
+    let specs = &record.header.signal_specs;
+    let resolution = channels
+        .iter()
+        .map(|&idx| specs[idx].adc_resolution.unwrap_or(12))
+        .max()
+        .unwrap_or(16);
+    let bits = bits_per_sample(resolution);
+    let bytes_per_sample = bits as usize / 8;
+    let sample_rate = record.sampling_frequency().round().max(1.0) as u32;
+    let num_frames = channels
+        .iter()
+        .map(|&idx| record.signals[idx].len().div_ceil(specs[idx].spf()))
+        .max()
+        .unwrap_or(0);
+    let block_align = channels.len() * bytes_per_sample;
+    let too_large = || {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Record is too large for a WAV file",
+        )
+    };
+    let data_len = num_frames.checked_mul(block_align).ok_or_else(too_large)?;
+    let byte_rate =
+        u32::try_from(sample_rate as u64 * block_align as u64).map_err(|_| too_large())?;
+
+    let names = record.signal_names();
+    let channel_names: Vec<&str> = channels.iter().map(|&idx| names[idx].as_str()).collect();
+    let mut info = b"INFO".to_vec();
+    info.extend(info_entry(b"INAM", &record.header.record.record_name));
+    info.extend(info_entry(b"ICMT", &channel_names.join("\n")));
+
+    let riff_len = (4 + (8 + 16) + (8 + info.len()) + 8)
+        .checked_add(data_len + data_len % 2)
+        .ok_or_else(too_large)?;
+    let riff_len = u32::try_from(riff_len).map_err(|_| too_large())?;
+    let info_len = u32::try_from(info.len()).map_err(|_| too_large())?;
+    let data_len = u32::try_from(data_len).map_err(|_| too_large())?;
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&riff_len.to_le_bytes())?;
+    writer.write_all(b"WAVE")?;
+
+    writer.write_all(b"fmt ")?;
+    writer.write_all(&16_u32.to_le_bytes())?;
+    writer.write_all(&WAVE_FORMAT_PCM.to_le_bytes())?;
+    writer.write_all(&(channels.len() as u16).to_le_bytes())?;
+    writer.write_all(&sample_rate.to_le_bytes())?;
+    writer.write_all(&byte_rate.to_le_bytes())?;
+    writer.write_all(&(block_align as u16).to_le_bytes())?;
+    writer.write_all(&bits.to_le_bytes())?;
+
+    writer.write_all(b"LIST")?;
+    writer.write_all(&info_len.to_le_bytes())?;
+    writer.write_all(&info)?;
+
+    writer.write_all(b"data")?;
+    writer.write_all(&data_len.to_le_bytes())?;
+    let (min, max) = match bits {
+        32 => (i32::MIN as i64, i32::MAX as i64),
+        _ => (-(1_i64 << (bits - 1)), (1_i64 << (bits - 1)) - 1),
+    };
+    for frame in 0..num_frames {
+        for &idx in &channels {
+            let sample = record.frame_sample(idx, frame);
+            let value = match signal::is_valid(sample) {
+                true => (sample as i64 - specs[idx].adc_zero.unwrap_or(0) as i64).clamp(min, max),
+                false => min,
+            };
+            match bits {
+                // 8-bit PCM is unsigned, with silence at 128
+                8 => writer.write_all(&[(value + 128) as u8])?,
+                _ => writer.write_all(&(value as i32).to_le_bytes()[..bytes_per_sample])?,
+            }
+        }
+    }
+    if data_len % 2 == 1 {
+        writer.write_all(&[0])?;
+    }
+    writer.flush()
+}
+
+/// The format of the audio in a WAV file, from its `fmt ` chunk.
+struct WavFormat {
+    channels: usize,
+    sample_rate: u32,
+    bits: u16,
+}
+
+fn parse_format(chunk: &[u8]) -> io::Result<WavFormat> {
+    if chunk.len() < 16 {
+        return Err(invalid_data("WAV format chunk is truncated"));
+    }
+    let u16_at = |offset: usize| u16::from_le_bytes([chunk[offset], chunk[offset + 1]]);
+    let mut format_tag = u16_at(0);
+    if format_tag == WAVE_FORMAT_EXTENSIBLE && chunk.len() >= 26 {
+        // The sub-format GUID starts with the format tag it stands for
+        format_tag = u16_at(24);
+    }
+    let format = WavFormat {
+        channels: u16_at(2) as usize,
+        sample_rate: u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]),
+        bits: u16_at(14),
+    };
+    if format_tag != WAVE_FORMAT_PCM || ![8, 16, 24, 32].contains(&format.bits) {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Only 8, 16, 24 and 32-bit PCM WAV files can be read",
+        ));
+    }
+    if format.channels == 0 {
+        return Err(invalid_data("WAV file has no channels"));
+    }
+    Ok(format)
+}
+
+/// Read the entries of a `LIST` `INFO` chunk.
+fn parse_info(chunk: &[u8]) -> Vec<([u8; 4], String)> {
+    let mut entries = vec![];
+    if !chunk.starts_with(b"INFO") {
+        return entries;
+    }
+    let mut offset = 4;
+    while offset + 8 <= chunk.len() {
+        let id = [
+            chunk[offset],
+            chunk[offset + 1],
+            chunk[offset + 2],
+            chunk[offset + 3],
+        ];
+        let len = u32::from_le_bytes([
+            chunk[offset + 4],
+            chunk[offset + 5],
+            chunk[offset + 6],
+            chunk[offset + 7],
+        ]) as usize;
+        let end = (offset + 8 + len).min(chunk.len());
+        let text = &chunk[offset + 8..end];
+        let text = text.split(|&b| b == 0).next().unwrap_or(text);
+        entries.push((id, String::from_utf8_lossy(text).into_owned()));
+        offset = end + len % 2;
+    }
+    entries
+}
+
+/// Build a record from a WAV file, with one format-16 signal per channel stored in
+/// `<record_name>.dat`.
+///
+/// 8-bit samples are centred on zero, and 24 and 32-bit samples are reduced to their 16 most
+/// significant bits. Each signal's gain maps the full PCM range to ±1 in normalized units (`NU`),
+/// and signals are named after the channel names written by [`write_wav`], if any. The record
+/// can then be saved with [`Record::write_in`].
+pub fn read_wav<R: Read>(mut reader: R, record_name: &str) -> io::Result<Record> {
+    let mut buf = vec![];
+    reader.read_to_end(&mut buf)?;
+    if buf.len() < 12 || &buf[0..4] != b"RIFF" || &buf[8..12] != b"WAVE" {
+        return Err(invalid_data("Not a RIFF WAV file"));
+    }
+
+    let mut format = None;
+    let mut data: &[u8] = &[];
+    let mut names = vec![];
+    let mut offset = 12;
+    while offset + 8 <= buf.len() {
+        let id = &buf[offset..offset + 4];
+        let len = u32::from_le_bytes([
+            buf[offset + 4],
+            buf[offset + 5],
+            buf[offset + 6],
+            buf[offset + 7],
+        ]) as usize;
+        let chunk = &buf[offset + 8..(offset + 8 + len).min(buf.len())];
+        match id {
+            b"fmt " => format = Some(parse_format(chunk)?),
+            b"data" => data = chunk,
+            b"LIST" => {
+                if let Some((_, comment)) =
+                    parse_info(chunk).into_iter().find(|(id, _)| id == b"ICMT")
+                {
+                    names = comment.lines().map(String::from).collect();
+                }
+            }
+            _ => {}
+        }
+        offset += 8 + len + len % 2;
+    }
+    let format = format.ok_or_else(|| invalid_data("WAV file has no format chunk"))?;
+
+    let bytes_per_sample = format.bits as usize / 8;
+    let mut signals = vec![vec![]; format.channels];
+    for frame in data.chunks_exact(bytes_per_sample * format.channels) {
+        for (samples, bytes) in signals.iter_mut().zip(frame.chunks_exact(bytes_per_sample)) {
+            let sample = match format.bits {
+                8 => bytes[0] as i32 - 128,
+                16 => i16::from_le_bytes([bytes[0], bytes[1]]) as i32,
+                24 => i16::from_le_bytes([bytes[1], bytes[2]]) as i32,
+                _ => i16::from_le_bytes([bytes[2], bytes[3]]) as i32,
+            };
+            // Samples are imported in format 16, where the most negative value marks invalid
+            // samples as it does in exported files
+            samples.push(signal::mark_invalid(sample, i16::MIN as i32));
+        }
+    }
+
+    let resolution = format.bits.min(16) as u32;
+    let signal_specs = signals
+        .iter()
+        .enumerate()
+        .map(|(idx, samples)| SignalSpecLine {
+            filename: format!("{}.dat", record_name),
+            format: StorageFormat::_16bit_twos_complement,
+            samples_per_frame: None,
+            skew: None,
+            byte_offset: None,
+            adc_gain: Some((1_u32 << (resolution - 1)) as f32),
+            baseline: Some(0),
+            units: Some(String::from("NU")),
+            adc_resolution: Some(resolution),
+            adc_zero: Some(0),
+            initial_value: Some(samples.first().copied().unwrap_or(0)),
+            checksum: Some(signal::checksum(samples)),
+            block_size: Some(0),
+            description: names.get(idx).cloned().filter(|name| !name.is_empty()),
+        })
+        .collect();
+    let sampling_frequency = format.sample_rate as f32;
+    let record = RecordLine {
+        record_name: String::from(record_name),
+        number_of_segments: None,
+        number_of_signals: format.channels as u32,
+        sampling_frequency: Some(sampling_frequency),
+        counter_frequency: Some(sampling_frequency),
+        base_counter_value: Some(0.0),
+        samples_per_signal: Some(signals[0].len() as u32),
+        base_time: None,
+        base_date: None,
+    };
+    Ok(Record {
+        header: Header {
+            record,
+            signal_specs,
+        },
+        signals,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(adc_resolution: u32) -> Record {
+        let header = format!(
+            "rec 2 2000 3\nrec.dat 16 200 {0} 1024 0 0 0 ECG\nrec.dat 16 200 {0} 0 0 0 0 PCG\n",
+            adc_resolution
+        );
+        let samples = [1024, 0, 2047, 100, signal::INVALID_SAMPLE, -100];
+        Record::from_header_str(&header, |_| Ok(signal::encode_16_format(&samples))).unwrap()
+    }
+
+    fn export(record: &Record) -> Vec<u8> {
+        let mut buf = vec![];
+        write_wav(record, &mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn wav_layout() {
+        let wav = export(&record(12));
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(
+            u32::from_le_bytes([wav[4], wav[5], wav[6], wav[7]]) as usize,
+            wav.len() - 8
+        );
+        let format = parse_format(&wav[20..36]).unwrap();
+        assert_eq!(
+            (format.channels, format.sample_rate, format.bits),
+            (2, 2000, 16)
+        );
+        // The data chunk comes last: 3 frames of 2 16-bit samples, relative to the ADC zeros
+        let data: Vec<i16> = wav[wav.len() - 12..]
+            .chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]))
+            .collect();
+        assert_eq!(data, vec![0, 0, 1023, 100, -32768, -100]);
+    }
+
+    #[test]
+    fn too_large_for_wav() {
+        // 600 MHz of two 32-bit channels is more bytes per second than a WAV file can give
+        let mut record = record(32);
+        record.header.record.sampling_frequency = Some(6e8);
+        let error = write_wav(&record, vec![]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn round_trip_16bit() {
+        let imported = read_wav(&export(&record(12))[..], "copy").unwrap();
+        assert_eq!(imported.signal_names(), vec!["ECG", "PCG"]);
+        assert_eq!(imported.sampling_frequency(), 2000.0);
+        assert_eq!(imported.signals[0], vec![0, 1023, signal::INVALID_SAMPLE]);
+        assert_eq!(imported.signals[1], vec![0, 100, -100]);
+        let spec = &imported.header.signal_specs[1];
+        assert_eq!(spec.filename, "copy.dat");
+        assert_eq!(spec.adc_gain, Some(32768.0));
+        assert_eq!(spec.units.as_deref(), Some("NU"));
+        assert_eq!(spec.checksum, Some(0));
+    }
+
+    #[test]
+    fn other_sample_sizes() {
+        let wav = export(&record(8));
+        assert_eq!(parse_format(&wav[20..36]).unwrap().bits, 8);
+        let imported = read_wav(&wav[..], "copy").unwrap();
+        // Clipped to the 8-bit range, with invalid samples at its minimum
+        assert_eq!(imported.signals[0], vec![0, 127, -128]);
+        assert_eq!(imported.header.signal_specs[0].adc_gain, Some(128.0));
+
+        let wav = export(&record(24));
+        assert_eq!(parse_format(&wav[20..36]).unwrap().bits, 24);
+        let imported = read_wav(&wav[..], "copy").unwrap();
+        assert_eq!(imported.signals[1], vec![0, 0, -1]);
+        assert_eq!(imported.header.signal_specs[1].adc_resolution, Some(16));
+
+        let wav = export(&record(32));
+        assert_eq!(parse_format(&wav[20..36]).unwrap().bits, 32);
+    }
+
+    #[test]
+    fn invalid_files() {
+        assert_eq!(
+            read_wav(&b"RIFF\0\0\0\0AVI "[..], "x").unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+        let mut wav = export(&record(12));
+        wav[20] = 3; // IEEE float samples
+        assert_eq!(
+            read_wav(&wav[..], "x").unwrap_err().kind(),
+            io::ErrorKind::Unsupported
+        );
+    }
+}
@@ -0,0 +1,143 @@
+/* Exercises the WFDB-compatible C interface on a small record written by the test itself. */
+#include <stdio.h>
+#include <string.h>
+
+#include "wfdb_rust.h"
+
+static int failures = 0;
+
+#define CHECK(condition)                                                                   \
+    do {                                                                                   \
+        if (!(condition)) {                                                                \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #condition); \
+            failures++;                                                                    \
+        }                                                                                  \
+    } while (0)
+
+static const short SAMPLES[5][2] = {{100, 60}, {200, -32768}, {-100, 35}, {7, 8}, {9, 10}};
+
+static void write_record(void)
+{
+    FILE *header = fopen("rec.hea", "w");
+    fputs("rec 2 360 5 10:00:00 01/02/2003\n"
+          "rec.dat 16 200 12 0 100 0 0 MLII\n"
+          "rec.dat 16 50(10)/mmHg 12 0 60 0 0 BP\n",
+          header);
+    fclose(header);
+
+    FILE *data = fopen("rec.dat", "wb");
+    for (int frame = 0; frame < 5; frame++) {
+        for (int signal = 0; signal < 2; signal++) {
+            unsigned short value = (unsigned short)SAMPLES[frame][signal];
+            fputc(value & 0xff, data);
+            fputc(value >> 8, data);
+        }
+    }
+    fclose(data);
+
+    /* A normal beat at sample 1 with auxiliary information "(N", and a PVC at sample 3 */
+    static const unsigned short words[] = {(1 << 10) | 1, (63 << 10) | 2, ('N' << 8) | '(',
+                                           (5 << 10) | 2, 0};
+    FILE *annotations = fopen("rec.atr", "wb");
+    for (size_t idx = 0; idx < sizeof(words) / sizeof(words[0]); idx++) {
+        fputc(words[idx] & 0xff, annotations);
+        fputc(words[idx] >> 8, annotations);
+    }
+    fclose(annotations);
+}
+
+static void test_signals(void)
+{
+    WFDB_Siginfo info[2];
+    WFDB_Sample vector[2];
+
+    CHECK(isigopen("missing", info, 2) == -1);
+    CHECK(isigopen("rec", info, -2) == 2);
+    CHECK(strcmp(info[1].desc, "BP") == 0);
+
+    CHECK(isigopen("rec", info, 2) == 2);
+    CHECK(strcmp(info[0].fname, "rec.dat") == 0);
+    CHECK(strcmp(info[0].desc, "MLII") == 0);
+    CHECK(strcmp(info[0].units, "mV") == 0);
+    CHECK(strcmp(info[1].units, "mmHg") == 0);
+    CHECK(info[0].gain == 200.0 && info[1].gain == 50.0);
+    CHECK(info[1].baseline == 10);
+    CHECK(info[0].group == 0 && info[1].group == 0);
+    CHECK(info[0].fmt == 16 && info[0].adcres == 12);
+    CHECK(info[1].initval == 60 && info[1].nsamp == 5);
+    CHECK(sampfreq(NULL) == 360.0);
+    CHECK(sampfreq("rec") == 360.0);
+
+    for (int frame = 0; frame < 5; frame++) {
+        CHECK(getvec(vector) == 2);
+        CHECK(vector[0] == SAMPLES[frame][0] && vector[1] == SAMPLES[frame][1]);
+    }
+    CHECK(getvec(vector) == -1);
+
+    CHECK(isigsettime(3) == 0);
+    CHECK(getframe(vector) == 2);
+    CHECK(vector[0] == 7 && vector[1] == 8);
+    CHECK(isigsettime(6) == -1);
+
+    CHECK(isigopen("rec", info, 1) == 1);
+    CHECK(isigsettime(1) == 0);
+    vector[1] = 1234;
+    CHECK(getvec(vector) == 1);
+    CHECK(vector[0] == 200 && vector[1] == 1234);
+}
+
+static void test_annotations(void)
+{
+    WFDB_Anninfo info[] = {{"atr", WFDB_READ}};
+    WFDB_Anninfo missing[] = {{"qrs", WFDB_READ}};
+    WFDB_Annotation annotation;
+
+    CHECK(annopen("rec", missing, 1) == -3);
+    CHECK(getann(0, &annotation) == -2);
+
+    CHECK(annopen("rec", info, 1) == 0);
+    CHECK(getann(0, &annotation) == 0);
+    CHECK(annotation.time == 1 && annotation.anntyp == 1);
+    CHECK(annotation.aux != NULL && annotation.aux[0] == 2);
+    CHECK(annotation.aux != NULL && strcmp((char *)annotation.aux + 1, "(N") == 0);
+    CHECK(getann(0, &annotation) == 0);
+    CHECK(annotation.time == 3 && annotation.anntyp == 5 && annotation.aux == NULL);
+    CHECK(getann(0, &annotation) == -1);
+    CHECK(getann(1, &annotation) == -2);
+}
+
+static void test_times(void)
+{
+    WFDB_Siginfo info[2];
+
+    CHECK(isigopen("rec", info, 2) == 2);
+    CHECK(strtim("s3") == 3);
+    CHECK(strtim("0:01") == 360);
+    CHECK(strtim("1:00:00.5") == 1296180);
+    CHECK(strtim("e") == 5);
+    CHECK(strtim("[10:00:01 01/02/2003]") == -360);
+    CHECK(strtim("[10:00:02]") == -720);
+    CHECK(strtim("later") == 0);
+
+    CHECK(strcmp(timstr(360), " 0:01") == 0);
+    CHECK(strcmp(timstr(360 * 3725), "1:02:05") == 0);
+    CHECK(strcmp(timstr(0), "[10:00:00 01/02/2003]") == 0);
+    CHECK(strcmp(timstr(-360 * 3600 * 14), "[00:00:00 02/02/2003]") == 0);
+
+    wfdbquit();
+    CHECK(getvec((WFDB_Sample[2]){0}) == -1);
+}
+
+int main(void)
+{
+    write_record();
+    test_signals();
+    test_annotations();
+    test_times();
+    if (failures > 0) {
+        fprintf(stderr, "%d checks failed\n", failures);
+        return 1;
+    }
+    printf("All checks passed\n");
+    return 0;
+}
@@ -0,0 +1,366 @@
+//! Tests of the `wfdb-rs` command-line tools, run on small records written to a temporary
+//! directory.
+#![cfg(feature = "std")]
+
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+use wfdb_rust::annotation::{self, Annotation};
+use wfdb_rust::header::read_header;
+use wfdb_rust::signal;
+use wfdb_rust::Record;
+
+/// A temporary directory of records, removed when dropped.
+struct Records(PathBuf);
+
+impl Records {
+    fn record(&self, name: &str) -> String {
+        self.0.join(name).to_str().unwrap().to_owned()
+    }
+}
+
+impl Drop for Records {
+    fn drop(&mut self) {
+        fs::remove_dir_all(&self.0).ok();
+    }
+}
+
+/// A directory holding `rec`, with two signals at 250 Hz and annotations `atr`, `fast`, whose
+/// one signal has two samples per frame at 100 frames per second, and `mixed`, whose first signal
+/// has two samples per frame and second one.
+fn records(name: &str) -> Records {
+    let directory =
+        std::env::temp_dir().join(format!("wfdb-rust-cli-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    Record {
+        header: read_header(
+            "rec 2 250 4 12:00:00 31/12/1999
+rec.dat 16 200 12 0 100 0 0 MLII
+rec.dat 16 10(-10000)/mmHg 12 0 995 0 0 ABP
+",
+        )
+        .unwrap(),
+        signals: vec![
+            vec![100, 0, signal::INVALID_SAMPLE, 2],
+            vec![-9000, -9005, -8999, signal::INVALID_SAMPLE],
+        ],
+    }
+    .write_in(&directory)
+    .unwrap();
+    let annotation = |sample, code, chan, aux: Option<&str>| Annotation {
+        sample,
+        code,
+        subtype: 0,
+        chan,
+        num: 0,
+        aux: aux.map(String::from),
+    };
+    annotation::write_annotations(
+        directory.join("rec"),
+        "atr",
+        &[
+            annotation(0, 28, 0, Some("(N")),
+            annotation(1, 1, 0, None),
+            annotation(2, 5, 1, None),
+            annotation(900_000, 48, 1, None),
+        ],
+    )
+    .unwrap();
+    let invalid = signal::INVALID_SAMPLE;
+    Record {
+        header: read_header("fast 1 100 4\nfast.dat 16x2 100 16 0 0 0 0 PPG\n").unwrap(),
+        signals: vec![vec![10, 20, 30, invalid, invalid, invalid, 5, 7]],
+    }
+    .write_in(&directory)
+    .unwrap();
+    Record {
+        header: read_header(
+            "mixed 2 100 4\nmixed.dat 16x2 100 16 0 0 0 0 PPG\nmixed.dat 16 100 16 0 0 0 0 ABP\n",
+        )
+        .unwrap(),
+        signals: vec![vec![1, 3, 5, 7, 9, 11, 13, 15], vec![10, 20, 30, 40]],
+    }
+    .write_in(&directory)
+    .unwrap();
+    Records(directory)
+}
+
+fn wfdb_rs(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_wfdb-rs"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn wfdb_rs_with_input(args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_wfdb-rs"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(args: &[&str]) -> String {
+    let output = wfdb_rs(args);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn rdsamp_raw() {
+    let records = records("rdsamp-raw");
+    let record = &records.record("rec");
+    assert_eq!(
+        stdout(&["rdsamp", "-r", record]),
+        "0\t100\t-9000\n1\t0\t-9005\n2\t-\t-8999\n3\t2\t-\n"
+    );
+    assert_eq!(
+        stdout(&["rdsamp", "-r", record, "-f", "s1", "-t", "s3", "-s", "ABP", "-c", "-v"]),
+        "'sample #','ABP'\n'','adu'\n1,-9005\n2,-8999\n"
+    );
+    assert_eq!(
+        stdout(&[
+            "rdsamp",
+            "-r",
+            record,
+            "-f",
+            "[12:00:00.008]",
+            "-l",
+            "s1",
+            "-s",
+            "1",
+            "0"
+        ]),
+        "2\t-8999\t-\n"
+    );
+}
+
+#[test]
+fn rdsamp_physical() {
+    let records = records("rdsamp-physical");
+    let record = &records.record("rec");
+    assert_eq!(
+        stdout(&["rdsamp", "-r", record, "-p", "-v", "-t", "s2"]),
+        "'Elapsed time'\t'MLII'\t'ABP'\n'seconds'\t'mV'\t'mmHg'\n\
+         0.000\t0.500\t100.000\n0.004\t0.000\t99.500\n"
+    );
+    assert_eq!(
+        stdout(&["rdsamp", "-r", record, "-pd", "-c", "-f", "s3"]),
+        "'[12:00:00.012 31/12/1999]',0.010,-\n"
+    );
+}
+
+#[test]
+fn rdsamp_json() {
+    let records = records("rdsamp-json");
+    let record = &records.record("rec");
+    assert_eq!(
+        stdout(&["rdsamp", "-r", record, "-p", "--json", "-f", "s1", "-t", "e"]),
+        r#"{
+  "record": "rec",
+  "sampling_frequency": 250,
+  "time": [0.004, 0.008, 0.012],
+  "signals": [
+    {"name": "MLII", "units": "mV", "samples": [0, null, 0.01]},
+    {"name": "ABP", "units": "mmHg", "samples": [99.5, 100.1, null]}
+  ]
+}
+"#
+    );
+}
+
+#[test]
+fn rdsamp_high_resolution() {
+    let records = records("rdsamp-high-resolution");
+    let record = &records.record("fast");
+    assert_eq!(
+        stdout(&["rdsamp", "-r", record]),
+        "0\t15\n1\t30\n2\t-\n3\t6\n"
+    );
+    assert_eq!(
+        stdout(&["rdsamp", "-r", record, "-H", "-f", "s2", "-t", "s4"]),
+        "2\t30\n3\t-\n"
+    );
+}
+
+#[test]
+fn rdsamp_mixed_samples_per_frame() {
+    let records = records("rdsamp-mixed");
+    let record = &records.record("mixed");
+    assert_eq!(
+        stdout(&["rdsamp", "-r", record]),
+        "0\t2\t10\n1\t6\t20\n2\t10\t30\n3\t14\t40\n"
+    );
+    assert_eq!(
+        stdout(&["rdsamp", "-r", record, "-H", "-t", "s4"]),
+        "0\t1\t10\n1\t3\t10\n2\t5\t20\n3\t7\t20\n"
+    );
+}
+
+#[test]
+fn rdsamp_errors() {
+    let records = records("rdsamp-errors");
+    let record = &records.record("rec");
+    let output = wfdb_rs(&["rdsamp", "-r", record, "-s", "V5"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("record has no signal V5"));
+    let output = wfdb_rs(&["rdsamp", "-r", "missing"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(wfdb_rs(&["rdsamp", "-f"]).status.code(), Some(2));
+}
+
+#[test]
+fn rdann() {
+    let records = records("rdann");
+    let record = &records.record("rec");
+    assert_eq!(
+        stdout(&["rdann", "-r", record, "-a", "atr", "-v"]),
+        "      Time   Sample #  Type  Sub Chan  Num\tAux
+    0:00.000        0     +    0    0    0\t(N
+    0:00.004        1     N    0    0    0
+    0:00.008        2     V    0    1    0
+ 1:00:00.000   900000  [48]    0    1    0
+"
+    );
+    assert_eq!(
+        stdout(&["rdann", "-r", record, "-a", "atr", "-p", "N", "V", "-c", "1"]),
+        "    0:00.008        2     V    0    1    0\n"
+    );
+    assert_eq!(
+        stdout(&[
+            "rdann",
+            "-r",
+            record,
+            "-a",
+            "atr",
+            "-f",
+            "s1",
+            "-t",
+            "[12:00:00.008]"
+        ]),
+        "    0:00.004        1     N    0    0    0\n"
+    );
+    let output = wfdb_rs(&["rdann", "-r", record, "-a", "atr", "-p", "X"]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn wrann_round_trip() {
+    let records = records("wrann");
+    let record = &records.record("rec");
+    let text = stdout(&["rdann", "-r", record, "-a", "atr", "-v"]);
+    let output = wfdb_rs_with_input(&["wrann", "-r", record, "-a", "copy"], &text);
+    assert!(output.status.success());
+    assert_eq!(
+        fs::read(format!("{}.copy", record)).unwrap(),
+        fs::read(format!("{}.atr", record)).unwrap()
+    );
+
+    // Annotations edited by hand
+    let edited =
+        text.replace("     V    0    1", "     F    2    1") + "\n[0:10] 2600 ~ 0 0 0\tnoise\n";
+    let output = wfdb_rs_with_input(&["wrann", "-r", record, "-a", "edit"], &edited);
+    assert!(output.status.success());
+    let annotations = annotation::read_annotations(record, "edit").unwrap();
+    assert_eq!(annotations.len(), 5);
+    assert_eq!((annotations[2].code, annotations[2].subtype), (6, 2));
+    assert_eq!(annotations[4].sample, 2600);
+    assert_eq!(annotations[4].aux.as_deref(), Some("noise"));
+
+    // Auxiliary information holding the characters that separate fields and lines
+    let aux = Annotation {
+        aux: Some(String::from("a\tb\nc\r\\d")),
+        ..annotations[4].clone()
+    };
+    annotation::write_annotations(record, "aux", &[aux]).unwrap();
+    let text = stdout(&["rdann", "-r", record, "-a", "aux"]);
+    assert!(text.ends_with("\ta\\tb\\nc\\r\\\\d\n"));
+    let output = wfdb_rs_with_input(&["wrann", "-r", record, "-a", "aux2"], &text);
+    assert!(output.status.success());
+    assert_eq!(
+        fs::read(format!("{}.aux2", record)).unwrap(),
+        fs::read(format!("{}.aux", record)).unwrap()
+    );
+    let output = wfdb_rs_with_input(
+        &["wrann", "-r", record, "-a", "bad"],
+        "0:00.000 0 N 0 0 0\tC:\\x\n",
+    );
+    assert_eq!(output.status.code(), Some(1));
+
+    let output = wfdb_rs_with_input(&["wrann", "-r", record, "-a", "bad"], "0:00.000 0 N 0\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("line 1"));
+}
+
+#[test]
+fn wfdbdesc() {
+    let records = records("wfdbdesc");
+    let record = &records.record("rec");
+    let text = stdout(&["wfdbdesc", record]);
+    assert!(text.starts_with(
+        "Record rec
+
+Starting time: [12:00:00 31/12/1999]
+Length: 00:00:00.016 (4 sample intervals)
+Sampling frequency: 250 Hz
+2 signals
+Group 0, Signal 0:
+ File: rec.dat
+ Description: MLII
+ Gain: 200 adu/mV
+"
+    ));
+    assert!(text.contains(" Baseline: -10000\n Checksum: 0 (the samples give 5764)\n"));
+
+    let description: serde_json::Value =
+        serde_json::from_str(&stdout(&["wfdbdesc", record, "--json"])).unwrap();
+    assert_eq!(description["record"], "rec");
+    assert_eq!(description["length"], 4);
+    assert_eq!(description["duration"], "00:00:00.016");
+    let signal = &description["signals"][1];
+    assert_eq!(signal["units"], "mmHg");
+    assert_eq!(signal["gain"], 10);
+    assert_eq!(signal["checksum_status"], "differs");
+    assert_eq!(signal["computed_checksum"], 5764);
+
+    fs::remove_file(records.0.join("rec.dat")).unwrap();
+    let description: serde_json::Value =
+        serde_json::from_str(&stdout(&["wfdbdesc", record, "--json"])).unwrap();
+    assert_eq!(description["signals"][0]["checksum_status"], "not checked");
+    assert_eq!(wfdb_rs(&["wfdbdesc"]).status.code(), Some(2));
+}
+
+#[test]
+fn wfdbdesc_samples_per_frame() {
+    let records = records("wfdbdesc-samples-per-frame");
+    let record = &records.record("mixed");
+    let text = stdout(&["wfdbdesc", record]);
+    assert!(text.contains(
+        "Length: 00:00:00.040 (4 sample intervals)
+Sampling frequency: 100 Hz
+"
+    ));
+    assert!(text.contains(" Storage format: 16\n Samples per frame: 2\n"));
+
+    let description: serde_json::Value =
+        serde_json::from_str(&stdout(&["wfdbdesc", record, "--json"])).unwrap();
+    assert_eq!(description["sampling_frequency"], 100);
+    assert_eq!(description["length"], 4);
+    assert_eq!(description["duration"], "00:00:00.040");
+    assert_eq!(description["signals"][0]["samples_per_frame"], 2);
+}
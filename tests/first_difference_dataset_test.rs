@@ -0,0 +1,66 @@
+#![cfg(feature = "std")]
+/// Tests using records from the AHA and European ST-T databases, which include signals stored as
+/// 8-bit first differences (format 8).
+///
+/// Note: in order to run these integration tests, the datasets referenced here must be downloaded
+/// and put in the data directory. The European ST-T database can be found at
+/// https://physionet.org/content/edb/1.0.0/; the AHA database is distributed by ECRI.
+extern crate glob;
+
+use wfdb_rust::Record;
+
+fn get_signal_checksum(signal: &[i32]) -> i16 {
+    let mut checksum = 0_i16;
+    for val in signal {
+        checksum = checksum.wrapping_add(*val as i16);
+    }
+    checksum
+}
+
+fn check_dataset(pattern: &str) {
+    let header_files = glob::glob(pattern).expect("Failed to read glob");
+    for path in header_files.flatten() {
+        println!("Reading {:?}", path);
+        let record = Record::open(&path).unwrap();
+        for (spec, signal) in record.header.signal_specs.iter().zip(&record.signals) {
+            assert_eq!(spec.checksum, Some(get_signal_checksum(signal)));
+        }
+    }
+}
+
+/// Tests that a small record in format 8, with its initial values in its header, decodes to known
+/// samples.
+#[test]
+fn parse_first_difference_fixture() {
+    let record = Record::open("tests/data/fd8").unwrap();
+    assert_eq!(
+        record.signals,
+        vec![vec![-50, -51, -49, -177, -50], vec![99, 99, 104, 114, 111]]
+    );
+    for (spec, signal) in record.header.signal_specs.iter().zip(&record.signals) {
+        assert_eq!(spec.checksum, Some(get_signal_checksum(signal)));
+    }
+}
+
+/// Tests that a time window of a record in format 8 is decoded from the start of its file.
+#[cfg(feature = "ndarray")]
+#[test]
+fn read_first_difference_window() {
+    use wfdb_rust::array::{read_array, Units};
+
+    let (_, samples) =
+        read_array::<i32, _>("tests/data/fd8", Units::Adc, Some(0.008..0.016)).unwrap();
+    assert_eq!(samples, ndarray::arr2(&[[-49, 104], [-177, 114]]));
+}
+
+/// Tests that records from the European ST-T database decode to the checksums in their headers.
+#[test]
+fn parse_european_st_t_dataset() {
+    check_dataset("data/european-st-t-database-1.0.0/*.hea");
+}
+
+/// Tests that records from the AHA database decode to the checksums in their headers.
+#[test]
+fn parse_aha_dataset() {
+    check_dataset("data/aha-database/*.hea");
+}
@@ -0,0 +1,179 @@
+//! Tests for reading records over HTTP, served by a minimal static file server on localhost.
+#![cfg(feature = "http")]
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use wfdb_rust::storage::{HttpStorage, Storage};
+use wfdb_rust::Record;
+
+/// The path and byte range (if any) of each request served.
+type Requests = Arc<Mutex<Vec<(String, Option<(usize, usize)>)>>>;
+
+/// Serve `files` over HTTP/1.1, honouring single `Range: bytes=a-b` requests. Returns the base
+/// URL and a log of the requests served.
+fn serve(files: HashMap<&'static str, Vec<u8>>) -> (String, Requests) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base_url = format!(
+        "http://{}/files/mitdb/1.0.0",
+        listener.local_addr().unwrap()
+    );
+    let requests = Requests::default();
+    let log = requests.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut range = None;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some(value) = line
+                    .strip_prefix("Range: bytes=")
+                    .or_else(|| line.strip_prefix("range: bytes="))
+                {
+                    let (start, end) = value.trim().split_once('-').unwrap();
+                    range = Some((
+                        start.parse::<usize>().unwrap(),
+                        end.parse::<usize>().unwrap(),
+                    ));
+                }
+            }
+            let path = request_line.split_whitespace().nth(1).unwrap();
+            let name = path.trim_start_matches("/files/mitdb/1.0.0/");
+            log.lock().unwrap().push((String::from(name), range));
+            let (status, body) = match (files.get(name), range) {
+                (None, _) => ("404 Not Found", vec![]),
+                (Some(body), Some((start, _))) if start >= body.len() => {
+                    ("416 Range Not Satisfiable", vec![])
+                }
+                (Some(body), Some((start, end))) => (
+                    "206 Partial Content",
+                    body[start..=end.min(body.len() - 1)].to_vec(),
+                ),
+                (Some(body), None) => ("200 OK", body.clone()),
+            };
+            write!(
+                stream,
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                status,
+                body.len()
+            )
+            .unwrap();
+            stream.write_all(&body).unwrap();
+        }
+    });
+    (base_url, requests)
+}
+
+fn fixtures() -> HashMap<&'static str, Vec<u8>> {
+    let mut files = HashMap::new();
+    files.insert(
+        "100.hea",
+        b"100 1 360 4\n100.dat 212 200 11 1024 995 -22131 0 MLII\n".to_vec(),
+    );
+    files.insert("100.dat", vec![0xF0, 0x68, 0x80, 0xFF, 0x8F, 0x80]);
+    files
+}
+
+#[test]
+fn open_remote_record() {
+    let (base_url, _) = serve(fixtures());
+    let storage = HttpStorage::new(&base_url);
+    let record = Record::open_from(&storage, "100").unwrap();
+    assert_eq!(record.signals, vec![vec![-1808, 1664, -1, -1920]]);
+    assert_eq!(
+        storage.read("101.hea").unwrap_err().kind(),
+        std::io::ErrorKind::NotFound
+    );
+}
+
+#[test]
+fn remote_range_reads() {
+    let (base_url, _) = serve(fixtures());
+    let storage = HttpStorage::new(&base_url);
+    assert_eq!(
+        storage.read_range("100.dat", 3, 2).unwrap(),
+        vec![0xFF, 0x8F]
+    );
+    assert_eq!(
+        storage.read_range("100.dat", 3, 100).unwrap(),
+        vec![0xFF, 0x8F, 0x80]
+    );
+    assert_eq!(
+        storage.read_range("100.dat", 10, 2).unwrap(),
+        Vec::<u8>::new()
+    );
+}
+
+#[test]
+fn remote_files_are_cached() {
+    let (base_url, requests) = serve(fixtures());
+    let cache_dir =
+        std::env::temp_dir().join(format!("wfdb-rust-http-cache-{}", std::process::id()));
+    let storage = HttpStorage::new(&base_url).with_cache(&cache_dir);
+    let first = Record::open_from(&storage, "100").unwrap();
+    assert_eq!(requests.lock().unwrap().len(), 2);
+    assert!(cache_dir.join("100.dat").is_file());
+
+    let second = Record::open_from(&storage, "100").unwrap();
+    assert_eq!(first, second);
+    assert_eq!(
+        storage.read_range("100.dat", 3, 2).unwrap(),
+        vec![0xFF, 0x8F]
+    );
+    assert_eq!(requests.lock().unwrap().len(), 2);
+    std::fs::remove_dir_all(&cache_dir).unwrap();
+}
+
+#[test]
+fn signal_files_outside_the_served_directory() {
+    let mut files = fixtures();
+    files.insert(
+        "evil.hea",
+        b"evil 1 360 4\n../../evil.dat 212 200 11 1024 995 -22131 0 MLII\n".to_vec(),
+    );
+    let (base_url, requests) = serve(files);
+    let cache_dir =
+        std::env::temp_dir().join(format!("wfdb-rust-http-traversal-{}", std::process::id()));
+    let storage = HttpStorage::new(&base_url).with_cache(cache_dir.join("cache"));
+    assert_eq!(
+        Record::open_from(&storage, "evil").unwrap_err().kind(),
+        std::io::ErrorKind::InvalidInput
+    );
+    for path in ["../evil.dat", "db/../../evil.dat"] {
+        assert_eq!(
+            storage.read_range(path, 0, 2).unwrap_err().kind(),
+            std::io::ErrorKind::InvalidInput
+        );
+    }
+    assert_eq!(requests.lock().unwrap().len(), 1);
+    assert!(!cache_dir.join("evil.dat").exists());
+    std::fs::remove_dir_all(&cache_dir).unwrap();
+}
+
+#[cfg(feature = "ndarray")]
+#[test]
+fn remote_time_window() {
+    use wfdb_rust::array::{read_array_from, Units};
+
+    let (base_url, requests) = serve(fixtures());
+    let storage = HttpStorage::new(&base_url);
+    // 360 Hz, so the window from 2/360 s to 4/360 s holds the samples in the second 3 bytes
+    let (_, array) =
+        read_array_from::<i32>(&storage, "100", Units::Adc, Some(2.0 / 360.0..4.0 / 360.0))
+            .unwrap();
+    assert_eq!(array.column(0).to_vec(), vec![-1, -1920]);
+    assert_eq!(
+        requests.lock().unwrap().last().unwrap(),
+        &(String::from("100.dat"), Some((3, 5)))
+    );
+}
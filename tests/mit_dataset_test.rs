@@ -1,3 +1,4 @@
+#![cfg(feature = "std")]
 /// Tests using the MIT ECG database samples.
 ///
 /// Note: in order to run these integration tests, the datasets referenced here must be downloaded
@@ -5,12 +6,12 @@
 /// PhysioNet and can be found at https://physionet.org/content/mitdb/1.0.0/.
 extern crate glob;
 
-use wfdb_rust::parse_wfdb;
+use wfdb_rust::{parse_wfdb, Record};
 
-fn get_signal_checksum(signal: &[i16]) -> i16 {
+fn get_signal_checksum(signal: &[i32]) -> i16 {
     let mut checksum = 0_i16;
     for val in signal {
-        checksum = checksum.wrapping_add(*val);
+        checksum = checksum.wrapping_add(*val as i16);
     }
     checksum
 }
@@ -20,14 +21,26 @@ fn get_signal_checksum(signal: &[i16]) -> i16 {
 #[test]
 fn parse_mit_dataset() {
     let mit_header_files = glob::glob("data/mit-bih-arrhythmia-database-1.0.0/*.hea").expect("Failed to read glob");
-    for header_file in mit_header_files {
-        if let Ok(path) = header_file {
-            println!("Reading {:?}", path);
-            let (header, signals) = parse_wfdb(&path);
-            for i in 0..signals.len() {
-                assert_eq!(header.signal_specs[i].checksum, Some(get_signal_checksum(&signals[i])));
-            }
+    for path in mit_header_files.flatten() {
+        println!("Reading {:?}", path);
+        let record = parse_wfdb(&path);
+        for (spec, signal) in record.header.signal_specs.iter().zip(&record.signals) {
+            assert_eq!(spec.checksum, Some(get_signal_checksum(signal)));
         }
     }
+}
 
+/// Tests that records can be opened by record name, relative to the database directory.
+#[test]
+fn open_mit_record_by_name() {
+    let mit_header_files =
+        glob::glob("data/mit-bih-arrhythmia-database-1.0.0/*.hea").expect("Failed to read glob");
+    for path in mit_header_files.flatten() {
+        let record_name = path.file_stem().unwrap();
+        let record = Record::open(path.with_file_name(record_name)).unwrap();
+        assert_eq!(
+            record.header.record.record_name,
+            record_name.to_str().unwrap()
+        );
+    }
 }
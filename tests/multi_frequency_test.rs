@@ -0,0 +1,68 @@
+#![cfg(feature = "std")]
+/// Tests using a small multi-frequency record: an ECG with two samples per frame, a blood pressure
+/// signal with one, and a respiration signal with one sample per frame stored one frame late (a
+/// skew of 1), all in the same signal file at 100 frames per second.
+use std::collections::HashMap;
+use std::fs;
+
+use wfdb_rust::Record;
+
+const RECORD: &str = "tests/data/mf";
+
+/// Tests that each signal is decoded by its own number of samples per frame and skew.
+#[test]
+fn decode_multi_frequency_record() {
+    let record = Record::open(RECORD).unwrap();
+    assert_eq!(
+        record.signals,
+        vec![
+            vec![10, 12, 20, 22, 30, 32, 40, 42],
+            vec![100, 200, 300, 400],
+            vec![7, 8, 9, 10],
+        ]
+    );
+    for (spec, signal) in record.header.signal_specs.iter().zip(&record.signals) {
+        assert_eq!(spec.checksum, Some(wfdb_rust::signal::checksum(signal)));
+    }
+}
+
+/// Tests that times count frames at the frame frequency given in the record line.
+#[test]
+fn multi_frequency_times() {
+    let record = Record::open(RECORD).unwrap();
+    assert_eq!(record.sampling_frequency(), 100.0);
+    assert_eq!(record.header.signal_frequency(0), 200.0);
+    assert_eq!(record.header.signal_frequency(1), 100.0);
+    assert_eq!(record.num_samples(), 4);
+    assert_eq!(record.duration(), 0.04);
+    assert_eq!(record.sample_at(0.02), 2);
+    assert_eq!(record.frame_sample(0, 1), 21);
+    assert_eq!(record.frame_sample(2, 3), 10);
+}
+
+/// Tests that writing the record interleaves its signals as they were read, skew included.
+#[test]
+fn write_multi_frequency_record() {
+    let record = Record::open(RECORD).unwrap();
+    let mut files = HashMap::new();
+    record
+        .write_with(|filename, contents| {
+            files.insert(filename.to_owned(), contents.to_vec());
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(
+        files["mf.dat"],
+        fs::read(format!("{}.dat", RECORD)).unwrap()
+    );
+}
+
+/// Tests that a time window holds each signal's frames, with the samples of each frame averaged.
+#[cfg(feature = "ndarray")]
+#[test]
+fn read_multi_frequency_window() {
+    use wfdb_rust::array::{read_array, Units};
+
+    let (_, samples) = read_array::<i32, _>(RECORD, Units::Adc, Some(0.01..0.03)).unwrap();
+    assert_eq!(samples, ndarray::arr2(&[[21, 200, 8], [31, 300, 9]]));
+}
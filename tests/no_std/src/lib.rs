@@ -0,0 +1,32 @@
+//! Uses the `no_std` API of wfdb-rust the way firmware would: parse a header held in memory and
+//! decode a block of samples into physical units.
+#![no_std]
+
+extern crate alloc;
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use wfdb_rust::header::{self, Header, StorageFormat};
+use wfdb_rust::signal;
+
+pub fn parse_header(text: &str) -> Result<Header, header::HeaderError> {
+    header::read_header(text)
+}
+
+pub fn physical_samples(header: &Header, buf: &[u8]) -> Vec<Option<f64>> {
+    let samples = match header.signal_specs[0].format {
+        StorageFormat::_12bit_twos_complement => signal::parse_212_format(buf),
+        _ => signal::parse_16_format(buf),
+    };
+    let specs = &header.signal_specs;
+    samples
+        .iter()
+        .enumerate()
+        .map(|(idx, &sample)| specs[idx % specs.len()].to_physical(sample))
+        .collect()
+}
+
+pub fn format_header(header: &Header) -> String {
+    header.to_string()
+}
@@ -0,0 +1,39 @@
+//! Checks that the crate builds without `std` for a bare-metal Cortex-M4F target
+//! (`thumbv7em-none-eabihf`), through the small `no_std` crate in `tests/no_std`.
+//!
+//! The test is skipped when the target's standard library is not installed; install it with
+//! `rustup target add thumbv7em-none-eabihf`.
+use std::path::Path;
+use std::process::Command;
+
+const TARGET: &str = "thumbv7em-none-eabihf";
+
+fn target_installed() -> bool {
+    let output = Command::new("rustc")
+        .args(["--print", "target-libdir", "--target", TARGET])
+        .output();
+    match output {
+        Ok(output) if output.status.success() => {
+            Path::new(String::from_utf8_lossy(&output.stdout).trim()).exists()
+        }
+        _ => false,
+    }
+}
+
+#[test]
+fn builds_for_bare_metal_target() {
+    if !target_installed() {
+        eprintln!("skipping: the {} target is not installed", TARGET);
+        return;
+    }
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let status = Command::new(env!("CARGO"))
+        .arg("build")
+        .arg("--manifest-path")
+        .arg(root.join("tests/no_std/Cargo.toml"))
+        .args(["--target", TARGET])
+        .env("CARGO_TARGET_DIR", root.join("target/no_std"))
+        .status()
+        .expect("Could not run cargo");
+    assert!(status.success());
+}
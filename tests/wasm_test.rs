@@ -0,0 +1,102 @@
+//! Tests of the WebAssembly bindings, run under Node with
+//! `wasm-pack test --node -- --features wasm --test wasm_test`.
+#![cfg(all(target_arch = "wasm32", feature = "wasm"))]
+
+use js_sys::{Array, Float64Array, Int32Array, Object, Reflect, Uint8Array};
+use wasm_bindgen::JsValue;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+use wfdb_rust::wasm::{parse_header, read_annotations, read_record};
+
+const HEADER: &str = "rec 2 360 3 10:00:00 01/02/2003
+rec.dat 16 200 12 0 100 0 0 MLII
+rec.dat 16 50(10)/mmHg 12 0 60 0 0 BP
+";
+
+fn get(object: &JsValue, key: &str) -> JsValue {
+    Reflect::get(object, &JsValue::from_str(key)).unwrap()
+}
+
+fn files() -> Object {
+    let samples: [i16; 6] = [100, 60, 200, -32768, -100, 35];
+    let bytes: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+    let files = Object::new();
+    Reflect::set(&files, &"rec.dat".into(), &Uint8Array::from(&bytes[..])).unwrap();
+    files
+}
+
+#[wasm_bindgen_test]
+fn header() {
+    let header: JsValue = parse_header(HEADER).unwrap().into();
+    let record = get(&header, "record");
+    assert_eq!(get(&record, "recordName"), "rec");
+    assert_eq!(get(&record, "samplingFrequency"), 360.0);
+    assert_eq!(get(&record, "baseDate"), "01/02/2003");
+    assert!(get(&record, "numberOfSegments").is_undefined());
+
+    let signals = Array::from(&get(&header, "signals"));
+    assert_eq!(signals.length(), 2);
+    assert_eq!(get(&signals.get(1), "units"), "mmHg");
+    assert_eq!(get(&signals.get(1), "baseline"), 10);
+    assert_eq!(get(&signals.get(0), "description"), "MLII");
+}
+
+#[wasm_bindgen_test]
+fn adc_record() {
+    let record: JsValue = read_record(HEADER, &files(), false).unwrap().into();
+    assert_eq!(get(&record, "samplingFrequency"), 360.0);
+    let signals = Array::from(&get(&record, "signals"));
+    assert_eq!(
+        Int32Array::from(signals.get(0)).to_vec(),
+        vec![100, 200, -100]
+    );
+    assert_eq!(
+        Int32Array::from(signals.get(1)).to_vec(),
+        vec![60, i32::MIN, 35]
+    );
+}
+
+#[wasm_bindgen_test]
+fn physical_record() {
+    let record: JsValue = read_record(HEADER, &files(), true).unwrap().into();
+    let signals = Array::from(&get(&record, "signals"));
+    assert_eq!(
+        Float64Array::from(signals.get(0)).to_vec(),
+        vec![0.5, 1.0, -0.5]
+    );
+    let pressure = Float64Array::from(signals.get(1)).to_vec();
+    assert_eq!(pressure[0], 1.0);
+    assert!(pressure[1].is_nan());
+}
+
+#[wasm_bindgen_test]
+fn missing_signal_file() {
+    assert!(read_record(HEADER, &Object::new(), false).is_err());
+}
+
+#[wasm_bindgen_test]
+fn annotations() {
+    // A normal beat at sample 1 with auxiliary information "(N", and a PVC at sample 3
+    let words: [u16; 5] = [
+        1 << 10 | 1,
+        63 << 10 | 2,
+        u16::from_le_bytes(*b"(N"),
+        5 << 10 | 2,
+        0,
+    ];
+    let buf: Vec<u8> = words.iter().flat_map(|w| w.to_le_bytes()).collect();
+    let annotations: JsValue = read_annotations(&buf).into();
+    assert_eq!(
+        Float64Array::from(get(&annotations, "sample")).to_vec(),
+        vec![1.0, 3.0]
+    );
+    assert_eq!(
+        Uint8Array::from(get(&annotations, "code")).to_vec(),
+        vec![1, 5]
+    );
+    let symbols = Array::from(&get(&annotations, "symbol"));
+    assert_eq!(symbols.get(1), "V");
+    let aux = Array::from(&get(&annotations, "aux"));
+    assert_eq!(aux.get(0), "(N");
+    assert!(aux.get(1).is_null());
+}